@@ -9,7 +9,10 @@
 pub mod config;
 
 use tchatchers_core::{
-    async_message::{processor::process, AsyncQueue},
+    async_message::{
+        processor::{process, run_scheduled},
+        AsyncQueue,
+    },
     pool::{get_async_pool, get_pg_pool},
 };
 use tokio::{signal::unix::SignalKind, task::JoinSet, time};
@@ -64,6 +67,13 @@ async fn main() -> anyhow::Result<()> {
             loop {
                 trace!("[{}] Ticking clock", queue_name);
                 interval.tick().await;
+                if queue_name.is_scheduled() {
+                    debug!("[{}] Running scheduled process", queue_name);
+                    if let Err(err) = run_scheduled(queue_name, &pg_pool, &mut redis_conn).await {
+                        error!("[{}] Scheduled process failed: {}", queue_name, err);
+                    }
+                    continue;
+                }
                 debug!("[{}] Waiting to process events", queue_name);
                 // Read events from the queue
                 if let Some(events) = queue_name.read_events_with_timeout(&mut redis_conn).await? {
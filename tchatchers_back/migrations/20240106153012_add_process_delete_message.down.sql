@@ -0,0 +1,2 @@
+-- Add down migration script here
+DELETE FROM PROCESS_KIND WHERE id = 6;
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- Add up migration script here
+INSERT INTO PROCESS_KIND(id, name) VALUES
+(6, 'DeleteMessage');
\ No newline at end of file
@@ -0,0 +1,7 @@
+-- Add down migration script here
+DELETE FROM PROCESS_KIND WHERE id = 7;
+
+DROP TABLE MESSAGE_REVISION;
+
+ALTER TABLE MESSAGE
+DROP COLUMN edited_at;
\ No newline at end of file
@@ -0,0 +1,17 @@
+-- Add up migration script here
+ALTER TABLE MESSAGE
+ADD COLUMN edited_at TIMESTAMPTZ NULL;
+
+CREATE TABLE MESSAGE_REVISION (
+	id SERIAL PRIMARY KEY,
+	message_uuid UUID NOT NULL,
+	content VARCHAR NOT NULL,
+	revised_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_message
+        FOREIGN KEY(message_uuid)
+        REFERENCES MESSAGE(uuid)
+        ON DELETE CASCADE
+);
+
+INSERT INTO PROCESS_KIND(id, name) VALUES
+(7, 'EditMessage');
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DELETE FROM PROCESS_KIND WHERE id = 8;
+
+DROP TABLE MESSAGE_REACTION;
\ No newline at end of file
@@ -0,0 +1,19 @@
+-- Add up migration script here
+CREATE TABLE MESSAGE_REACTION (
+	message_uuid UUID NOT NULL,
+	user_id INTEGER NOT NULL,
+	emoji VARCHAR(32) NOT NULL,
+	created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (message_uuid, user_id, emoji),
+    CONSTRAINT fk_message
+        FOREIGN KEY(message_uuid)
+        REFERENCES MESSAGE(uuid)
+        ON DELETE CASCADE,
+    CONSTRAINT fk_user
+        FOREIGN KEY(user_id)
+        REFERENCES CHATTER(id)
+        ON DELETE CASCADE
+);
+
+INSERT INTO PROCESS_KIND(id, name) VALUES
+(8, 'PersistReaction');
\ No newline at end of file
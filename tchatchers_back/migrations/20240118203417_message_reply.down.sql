@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP INDEX message_reply_to_idx;
+
+ALTER TABLE MESSAGE
+DROP COLUMN reply_to,
+DROP COLUMN is_deleted;
\ No newline at end of file
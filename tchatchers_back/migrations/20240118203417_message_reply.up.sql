@@ -0,0 +1,7 @@
+-- Add up migration script here
+ALTER TABLE MESSAGE
+ADD COLUMN reply_to UUID NULL,
+ADD COLUMN is_deleted BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Replies may be persisted before their parent, hence no foreign key.
+CREATE INDEX message_reply_to_idx ON MESSAGE(reply_to);
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE ROOM_READ_POSITION;
+
+DROP TABLE MESSAGE_READ;
\ No newline at end of file
@@ -0,0 +1,27 @@
+-- Add up migration script here
+CREATE TABLE MESSAGE_READ (
+	message_uuid UUID NOT NULL,
+	user_id INTEGER NOT NULL,
+	read_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (message_uuid, user_id),
+    CONSTRAINT fk_message
+        FOREIGN KEY(message_uuid)
+        REFERENCES MESSAGE(uuid)
+        ON DELETE CASCADE,
+    CONSTRAINT fk_user
+        FOREIGN KEY(user_id)
+        REFERENCES CHATTER(id)
+        ON DELETE CASCADE
+);
+
+-- The timestamp of the latest message read by each user in each room.
+CREATE TABLE ROOM_READ_POSITION (
+	user_id INTEGER NOT NULL,
+	room VARCHAR(128) NOT NULL,
+	last_read_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (user_id, room),
+    CONSTRAINT fk_user
+        FOREIGN KEY(user_id)
+        REFERENCES CHATTER(id)
+        ON DELETE CASCADE
+);
\ No newline at end of file
@@ -0,0 +1,23 @@
+-- Add up migration script here
+CREATE TABLE NOTIFICATION (
+	id SERIAL PRIMARY KEY,
+	user_id INTEGER NOT NULL,
+	author_id INTEGER NOT NULL,
+	message_uuid UUID NOT NULL,
+	room VARCHAR NOT NULL,
+	content VARCHAR NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	read_at TIMESTAMPTZ NULL,
+    UNIQUE (user_id, message_uuid),
+    CONSTRAINT fk_user
+        FOREIGN KEY(user_id)
+        REFERENCES CHATTER(id)
+        ON DELETE CASCADE,
+    CONSTRAINT fk_author
+        FOREIGN KEY(author_id)
+        REFERENCES CHATTER(id)
+        ON DELETE CASCADE
+);
+
+-- Mentions are notified before their message is persisted, hence no foreign key on it.
+CREATE INDEX notification_user_idx ON NOTIFICATION(user_id, created_at);
\ No newline at end of file
@@ -0,0 +1,18 @@
+-- Add up migration script here
+CREATE TABLE ROOM (
+	name VARCHAR(128) PRIMARY KEY,
+	owner_id INTEGER NULL,
+	topic VARCHAR(256) NULL,
+	description VARCHAR(1024) NULL,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_owner
+        FOREIGN KEY(owner_id)
+        REFERENCES CHATTER(id)
+        ON DELETE SET NULL
+);
+
+-- The rooms in which messages have already been posted are registered without any owner.
+INSERT INTO ROOM(name, created_at)
+SELECT room, COALESCE(MIN(timestamp), CURRENT_TIMESTAMP) FROM MESSAGE
+WHERE room NOT LIKE 'dm:%' AND length(room) <= 128
+GROUP BY room;
\ No newline at end of file
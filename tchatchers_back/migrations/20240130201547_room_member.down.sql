@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP FUNCTION can_access_room;
+DROP TABLE ROOM_MEMBER;
+ALTER TABLE ROOM
+DROP COLUMN visibility;
\ No newline at end of file
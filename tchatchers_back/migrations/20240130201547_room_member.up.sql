@@ -0,0 +1,43 @@
+-- Add up migration script here
+ALTER TABLE ROOM
+ADD COLUMN visibility INT4 NOT NULL DEFAULT 1;
+
+-- The members that haven't joined yet are the users invited.
+CREATE TABLE ROOM_MEMBER (
+	room VARCHAR(128) NOT NULL,
+	user_id INTEGER NOT NULL,
+	invited_by INTEGER NULL,
+	invited_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	joined_at TIMESTAMPTZ NULL,
+    PRIMARY KEY (room, user_id),
+    CONSTRAINT fk_room
+        FOREIGN KEY(room)
+        REFERENCES ROOM(name)
+        ON DELETE CASCADE,
+    CONSTRAINT fk_user
+        FOREIGN KEY(user_id)
+        REFERENCES CHATTER(id)
+        ON DELETE CASCADE,
+    CONSTRAINT fk_inviter
+        FOREIGN KEY(invited_by)
+        REFERENCES CHATTER(id)
+        ON DELETE SET NULL
+);
+
+CREATE INDEX room_member_user_idx ON ROOM_MEMBER(user_id);
+
+-- Private rooms (visibility 3) can only be accessed by their owner and members,
+-- the rooms that aren't registered being public.
+CREATE OR REPLACE FUNCTION can_access_room(room_name VARCHAR, member_id INTEGER)
+RETURNS BOOLEAN AS $$
+  SELECT NOT EXISTS (
+    SELECT 1 FROM ROOM r
+    WHERE r.name = room_name
+      AND r.visibility = 3
+      AND r.owner_id IS DISTINCT FROM member_id
+      AND NOT EXISTS (
+        SELECT 1 FROM ROOM_MEMBER m
+        WHERE m.room = r.name AND m.user_id = member_id AND m.joined_at IS NOT NULL
+      )
+  );
+$$ LANGUAGE sql STABLE;
\ No newline at end of file
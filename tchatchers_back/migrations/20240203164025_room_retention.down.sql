@@ -0,0 +1,8 @@
+-- Add down migration script here
+ALTER TABLE PROCESS_REPORT
+DROP COLUMN room;
+DROP TABLE DEFAULT_ROOM_RETENTION;
+ALTER TABLE ROOM
+DROP CONSTRAINT ck_retention,
+DROP COLUMN retention_value,
+DROP COLUMN retention_policy;
\ No newline at end of file
@@ -0,0 +1,28 @@
+-- Add up migration script here
+-- The policies are 1 for a message count, 2 for an age in days and 3 for an unlimited retention.
+ALTER TABLE ROOM
+ADD COLUMN retention_policy INT4 NULL,
+ADD COLUMN retention_value INT4 NULL,
+ADD CONSTRAINT ck_retention CHECK (
+    retention_policy IS NULL AND retention_value IS NULL
+    OR retention_policy = 3 AND retention_value IS NULL
+    OR retention_policy IN (1, 2) AND retention_value > 0
+);
+
+-- The policy of the rooms without any, a single row being allowed.
+CREATE TABLE DEFAULT_ROOM_RETENTION (
+	id BOOLEAN PRIMARY KEY DEFAULT TRUE,
+	policy INT4 NOT NULL,
+	value INT4 NULL,
+    CONSTRAINT ck_single_row CHECK (id),
+    CONSTRAINT ck_retention CHECK (
+        policy = 3 AND value IS NULL
+        OR policy IN (1, 2) AND value > 0
+    )
+);
+
+-- The rooms used to keep their 100 latest messages.
+INSERT INTO DEFAULT_ROOM_RETENTION(policy, value) VALUES (1, 100);
+
+ALTER TABLE PROCESS_REPORT
+ADD COLUMN room VARCHAR(128) NULL;
\ No newline at end of file
@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE ROOM
+DROP CONSTRAINT ck_slow_mode,
+DROP COLUMN is_announcement,
+DROP COLUMN slow_mode;
\ No newline at end of file
@@ -0,0 +1,5 @@
+-- Add up migration script here
+ALTER TABLE ROOM
+ADD COLUMN slow_mode INT4 NULL,
+ADD COLUMN is_announcement BOOLEAN NOT NULL DEFAULT FALSE,
+ADD CONSTRAINT ck_slow_mode CHECK (slow_mode > 0);
\ No newline at end of file
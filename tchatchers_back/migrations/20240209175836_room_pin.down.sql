@@ -0,0 +1,2 @@
+-- Add down migration script here
+DROP TABLE ROOM_PIN;
\ No newline at end of file
@@ -0,0 +1,17 @@
+-- Add up migration script here
+CREATE TABLE ROOM_PIN (
+	message_uuid UUID PRIMARY KEY,
+	room VARCHAR(128) NOT NULL,
+	pinned_by INTEGER NULL,
+	pinned_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_message
+        FOREIGN KEY(message_uuid)
+        REFERENCES MESSAGE(uuid)
+        ON DELETE CASCADE,
+    CONSTRAINT fk_pinner
+        FOREIGN KEY(pinned_by)
+        REFERENCES CHATTER(id)
+        ON DELETE SET NULL
+);
+
+CREATE INDEX room_pin_room_idx ON ROOM_PIN(room);
\ No newline at end of file
@@ -0,0 +1,25 @@
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+//! Gathers the API used to consult the direct conversations of the user.
+
+use axum::extract::State;
+use tchatchers_core::{
+    api_response::ApiGenericResponse, direct_message::DirectConversationSummary,
+};
+
+use crate::{
+    extractor::{Bincode, JwtUserExtractor},
+    AppState,
+};
+
+/// Returns the direct conversations of the user, along with their latest
+/// message and their number of unread messages.
+pub async fn get_direct_conversations(
+    JwtUserExtractor(user): JwtUserExtractor,
+    State(state): State<AppState>,
+) -> Result<Bincode<Vec<DirectConversationSummary>>, ApiGenericResponse> {
+    let conversations =
+        DirectConversationSummary::get_for_user(user.user_id, &state.pg_pool).await?;
+    Ok(Bincode(conversations))
+}
@@ -3,16 +3,24 @@ use axum::{
     response::IntoResponse,
 };
 use tchatchers_core::{
-    api_response::ApiGenericResponse, profile::Profile, report::Report,
-    ws_message::WsMessageContent,
+    api_response::ApiGenericResponse,
+    message_read::MessageReader,
+    message_revision::MessageRevision,
+    report::Report,
+    ws_message::{WsMessage, WsMessageContent},
 };
 use uuid::Uuid;
 
-use crate::{extractor::JwtUserExtractor, AppState};
+use crate::{
+    extractor::{Bincode, JwtUserExtractor, ModeratorExtractor},
+    ws::broadcast_to_room,
+    AppState,
+};
 
 /// Delete a message
 ///
-/// This endpoint allows to delete a message in the DB.
+/// This endpoint allows to delete a message in the DB, and informs the
+/// members of its room that it has been deleted.
 ///
 /// # Arguments
 ///
@@ -22,19 +30,61 @@ pub async fn delete_message(
     Path(message_id): Path<Uuid>,
     state: State<AppState>,
 ) -> impl IntoResponse {
-    if user.user_profile == Profile::User {
-        match WsMessageContent::get_one(&message_id, &state.pg_pool).await? {
-            Some(message) if message.author.id == user.user_id => (),
-            Some(_) => {
-                return Err(ApiGenericResponse::UnsifficentPriviledges);
-            }
-            None => return Err(ApiGenericResponse::MessageDoesNotExist),
-        }
+    let Some(message) = WsMessageContent::get_one(&message_id, &state.pg_pool).await? else {
+        return Err(ApiGenericResponse::MessageDoesNotExist);
+    };
+    if !message.can_be_deleted_by(user.user_id, user.user_profile) {
+        return Err(ApiGenericResponse::UnsifficentPriviledges);
     }
-    WsMessageContent::delete_messages(&vec![message_id], &state.pg_pool).await?;
+    WsMessageContent::delete_messages(&[message_id], &state.pg_pool).await?;
+    broadcast_to_room(&state, &message.room, &WsMessage::Delete(message_id)).await?;
     Ok(ApiGenericResponse::MessageDeleted)
 }
 
+/// Returns the edit history of a message.
+///
+/// Only moderators can consult the former versions of a message.
+///
+/// # Arguments
+///
+/// - message_id : the message's uuid.
+pub async fn get_message_history(
+    ModeratorExtractor(_): ModeratorExtractor,
+    Path(message_id): Path<Uuid>,
+    state: State<AppState>,
+) -> Result<Bincode<Vec<MessageRevision>>, ApiGenericResponse> {
+    if WsMessageContent::get_one(&message_id, &state.pg_pool)
+        .await?
+        .is_none()
+    {
+        return Err(ApiGenericResponse::MessageDoesNotExist);
+    }
+    let revisions = MessageRevision::get_for_message(&message_id, &state.pg_pool).await?;
+    Ok(Bincode(revisions))
+}
+
+/// Returns the users that read a message.
+///
+/// Only the author of a message can know who read it.
+///
+/// # Arguments
+///
+/// - message_id : the message's uuid.
+pub async fn get_message_readers(
+    JwtUserExtractor(user): JwtUserExtractor,
+    Path(message_id): Path<Uuid>,
+    state: State<AppState>,
+) -> Result<Bincode<Vec<MessageReader>>, ApiGenericResponse> {
+    let Some(message) = WsMessageContent::get_one(&message_id, &state.pg_pool).await? else {
+        return Err(ApiGenericResponse::MessageDoesNotExist);
+    };
+    if message.author.id != user.user_id {
+        return Err(ApiGenericResponse::UnsifficentPriviledges);
+    }
+    let readers = MessageReader::get_for_message(&message_id, &state.pg_pool).await?;
+    Ok(Bincode(readers))
+}
+
 /// Report a message.
 ///
 /// # Arguments
@@ -3,5 +3,9 @@
 
 //! Defines the set of API entrypoints that can be called on client side.
 
+pub(crate) mod direct_message;
 pub(crate) mod message;
+pub(crate) mod notification;
+pub(crate) mod room;
+pub(crate) mod room_member;
 pub(crate) mod user;
@@ -0,0 +1,45 @@
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+//! Gathers the API used to consult the notifications of the user.
+
+use axum::extract::{Path, State};
+use tchatchers_core::{api_response::ApiGenericResponse, notification::NotificationInbox};
+
+use crate::{
+    extractor::{Bincode, JwtUserExtractor},
+    AppState,
+};
+
+/// Returns the latest notifications of the user, along with their number of
+/// unread ones.
+pub async fn get_notifications(
+    JwtUserExtractor(user): JwtUserExtractor,
+    State(state): State<AppState>,
+) -> Result<Bincode<NotificationInbox>, ApiGenericResponse> {
+    let inbox = NotificationInbox::get_for_user(user.user_id, &state.pg_pool).await?;
+    Ok(Bincode(inbox))
+}
+
+/// Marks all the notifications of the user as read.
+pub async fn mark_notifications_read(
+    JwtUserExtractor(user): JwtUserExtractor,
+    State(state): State<AppState>,
+) -> Result<ApiGenericResponse, ApiGenericResponse> {
+    NotificationInbox::mark_as_read(user.user_id, None, &state.pg_pool).await?;
+    Ok(ApiGenericResponse::NotificationsRead)
+}
+
+/// Marks a notification of the user as read.
+///
+/// # Arguments
+///
+/// - notification_id : The ID of the notification.
+pub async fn mark_notification_read(
+    JwtUserExtractor(user): JwtUserExtractor,
+    Path(notification_id): Path<i32>,
+    State(state): State<AppState>,
+) -> Result<ApiGenericResponse, ApiGenericResponse> {
+    NotificationInbox::mark_as_read(user.user_id, Some(notification_id), &state.pg_pool).await?;
+    Ok(ApiGenericResponse::NotificationsRead)
+}
@@ -0,0 +1,246 @@
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+use axum::extract::{Path, Query, State};
+use serde::Deserialize;
+use tchatchers_core::{
+    api_response::ApiGenericResponse,
+    message_read::RoomUnread,
+    room::{
+        InsertableRoom, Room, RoomActivity, RoomNameValidator, RoomPostingRules, RoomVisibility,
+        UpdatableRoom, ROOM_LIST_SIZE,
+    },
+    user::PartialUser,
+    ws_message::{MessageCursor, MessagePage, WsMessage, WsMessageContent, DEFAULT_PAGE_SIZE},
+};
+use validator::Validate;
+
+use crate::{
+    extractor::{AdminExtractor, Bincode, JwtUserExtractor, ModeratorExtractor, ValidBincode},
+    ws::{broadcast_to_room, revoke_room_access},
+    AppState,
+};
+
+/// The query parameters used to list the rooms.
+#[derive(Debug, Deserialize)]
+pub struct RoomsParams {
+    /// The beginning of the names of the rooms listed.
+    search: Option<String>,
+    /// The maximum number of rooms returned.
+    limit: Option<i64>,
+}
+
+/// Creates a room, owned by the user.
+///
+/// # Arguments
+///
+/// - new_room : The room to create.
+pub async fn create_room(
+    JwtUserExtractor(user): JwtUserExtractor,
+    State(state): State<AppState>,
+    ValidBincode(new_room): ValidBincode<InsertableRoom>,
+) -> Result<ApiGenericResponse, ApiGenericResponse> {
+    match new_room.insert(Some(user.user_id), &state.pg_pool).await {
+        Ok(_) => Ok(ApiGenericResponse::RoomCreated),
+        Err(e) => {
+            if let Some(database_err) = e.as_database_error() {
+                if database_err.is_unique_violation() {
+                    return Err(ApiGenericResponse::RoomAlreadyExists);
+                }
+            }
+            Err(ApiGenericResponse::from(e))
+        }
+    }
+}
+
+/// Returns the rooms listed to the user, in the alphabetical order.
+///
+/// # Arguments
+///
+/// - params : The beginning of the room names and the number of rooms
+///   returned.
+pub async fn get_rooms(
+    JwtUserExtractor(user): JwtUserExtractor,
+    Query(params): Query<RoomsParams>,
+    State(state): State<AppState>,
+) -> Result<Bincode<Vec<Room>>, ApiGenericResponse> {
+    let limit = params
+        .limit
+        .unwrap_or(ROOM_LIST_SIZE)
+        .clamp(1, ROOM_LIST_SIZE);
+    let search = params.search.unwrap_or_default();
+    let rooms = Room::search(&search, user.user_id, limit, &state.pg_pool).await?;
+    Ok(Bincode(rooms))
+}
+
+/// Returns a room along with its metadata.
+///
+/// The private rooms the user can't access are reported as not found.
+///
+/// # Arguments
+///
+/// - room : The room name.
+pub async fn get_room(
+    JwtUserExtractor(user): JwtUserExtractor,
+    Path(room): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Bincode<Room>, ApiGenericResponse> {
+    RoomNameValidator::from(room.clone()).validate()?;
+    if !Room::can_access(&room, user.user_id, &state.pg_pool).await? {
+        return Err(ApiGenericResponse::RoomNotFound);
+    }
+    match Room::find_by_name(&room, &state.pg_pool).await? {
+        Some(room) => Ok(Bincode(room)),
+        None => Err(ApiGenericResponse::RoomNotFound),
+    }
+}
+
+/// Updates the metadata of a room, and informs its members of the change.
+///
+/// Only the owner of the room and the moderators can update it. When the room
+/// becomes private, the users connected that aren't members are disconnected.
+///
+/// # Arguments
+///
+/// - room : The room name.
+/// - updatable_room : The new metadata of the room.
+pub async fn update_room(
+    JwtUserExtractor(user): JwtUserExtractor,
+    Path(room): Path<String>,
+    State(state): State<AppState>,
+    ValidBincode(updatable_room): ValidBincode<UpdatableRoom>,
+) -> Result<ApiGenericResponse, ApiGenericResponse> {
+    RoomNameValidator::from(room.clone()).validate()?;
+    let Some(existing_room) = Room::find_by_name(&room, &state.pg_pool).await? else {
+        return Err(ApiGenericResponse::RoomNotFound);
+    };
+    if !existing_room.can_be_updated_by(user.user_id, user.user_profile) {
+        return Err(ApiGenericResponse::UnsifficentPriviledges);
+    }
+    let Some(updated_room) = Room::update(&room, &updatable_room, &state.pg_pool).await? else {
+        return Err(ApiGenericResponse::RoomNotFound);
+    };
+    let is_private = updated_room.visibility == RoomVisibility::Private;
+    broadcast_to_room(&state, &room, &WsMessage::RoomUpdated(updated_room)).await?;
+    // The users connected to a room that became private have to be members,
+    // whatever the instance they are connected to.
+    if is_private {
+        let local_members = match state.txs.lock().await.get(&room) {
+            Some(ws_room) => ws_room.members(),
+            None => vec![],
+        };
+        let members = state.room_bus.members(&room, local_members).await;
+        for member in members {
+            if !Room::can_access(&room, member.id, &state.pg_pool).await? {
+                revoke_room_access(&state, &room, member.id).await;
+            }
+        }
+    }
+    Ok(ApiGenericResponse::RoomUpdated)
+}
+
+/// Sets the posting rules of a room, such as its slow mode, and informs its
+/// members of the change.
+///
+/// Only the moderators can set them.
+///
+/// # Arguments
+///
+/// - room : The room name.
+/// - rules : The new posting rules of the room.
+pub async fn set_room_posting_rules(
+    ModeratorExtractor(_): ModeratorExtractor,
+    Path(room): Path<String>,
+    State(state): State<AppState>,
+    ValidBincode(rules): ValidBincode<RoomPostingRules>,
+) -> Result<ApiGenericResponse, ApiGenericResponse> {
+    RoomNameValidator::from(room.clone()).validate()?;
+    let Some(updated_room) = Room::set_posting_rules(&room, &rules, &state.pg_pool).await? else {
+        return Err(ApiGenericResponse::RoomNotFound);
+    };
+    broadcast_to_room(&state, &room, &WsMessage::RoomUpdated(updated_room)).await?;
+    Ok(ApiGenericResponse::RoomUpdated)
+}
+
+/// Returns the users currently connected to a room, whatever the instance
+/// they are connected to.
+///
+/// # Arguments
+///
+/// - room : The room name.
+pub async fn get_room_members(
+    JwtUserExtractor(user): JwtUserExtractor,
+    Path(room): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Bincode<Vec<PartialUser>>, ApiGenericResponse> {
+    RoomNameValidator::from(room.clone()).validate()?;
+    if !Room::can_access(&room, user.user_id, &state.pg_pool).await? {
+        return Err(ApiGenericResponse::RoomNotFound);
+    }
+    let local_members = match state.txs.lock().await.get(&room) {
+        Some(ws_room) => ws_room.members(),
+        None => vec![],
+    };
+    let members = state.room_bus.members(&room, local_members).await;
+    Ok(Bincode(members))
+}
+
+/// Returns the rooms live on this instance, with their number of sockets and
+/// subscribers.
+pub async fn get_rooms_activity(
+    AdminExtractor(_): AdminExtractor,
+    State(state): State<AppState>,
+) -> Bincode<Vec<RoomActivity>> {
+    Bincode(state.txs.lock().await.activity())
+}
+
+/// Returns the number of unread messages of the user in each of the rooms
+/// they already read.
+pub async fn get_unread_counts(
+    JwtUserExtractor(user): JwtUserExtractor,
+    State(state): State<AppState>,
+) -> Result<Bincode<Vec<RoomUnread>>, ApiGenericResponse> {
+    let unread = RoomUnread::get_for_user(user.user_id, &state.pg_pool).await?;
+    Ok(Bincode(unread))
+}
+
+/// The query parameters used to paginate the messages of a room.
+#[derive(Debug, Deserialize)]
+pub struct MessagesParams {
+    /// The cursor before which the messages are returned, formatted as
+    /// `timestamp_uuid`.
+    before: Option<String>,
+    /// The maximum number of messages returned.
+    limit: Option<i64>,
+}
+
+/// Returns a page of the messages of a room, from the latest to the oldest.
+///
+/// # Arguments
+///
+/// - room : The room name.
+/// - params : The cursor and the size of the page.
+pub async fn get_room_messages(
+    JwtUserExtractor(user): JwtUserExtractor,
+    Path(room): Path<String>,
+    Query(params): Query<MessagesParams>,
+    State(state): State<AppState>,
+) -> Result<Bincode<MessagePage>, ApiGenericResponse> {
+    RoomNameValidator::from(room.clone()).validate()?;
+    if !Room::can_access(&room, user.user_id, &state.pg_pool).await? {
+        return Err(ApiGenericResponse::RoomNotFound);
+    }
+    let before = params
+        .before
+        .map(|before| before.parse::<MessageCursor>())
+        .transpose()
+        .map_err(ApiGenericResponse::InvalidCursor)?;
+    let page = WsMessageContent::query_page_for_room(
+        &room,
+        before.as_ref(),
+        params.limit.unwrap_or(DEFAULT_PAGE_SIZE),
+        &state.pg_pool,
+    )
+    .await?;
+    Ok(Bincode(page))
+}
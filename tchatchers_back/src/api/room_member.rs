@@ -0,0 +1,168 @@
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+//! Gathers the API used to manage the members of the rooms, and the
+//! invitations of the user.
+
+use axum::extract::{Path, State};
+use tchatchers_core::{
+    api_response::ApiGenericResponse,
+    authorization_token::AuthorizationToken,
+    room::{Room, RoomNameValidator},
+    room_member::{RoomInvitation, RoomMember},
+    user::PartialUser,
+    ws_message::WsMessage,
+};
+use validator::Validate;
+
+use crate::{
+    extractor::{Bincode, JwtUserExtractor},
+    ws::{broadcast_to_room, revoke_room_access},
+    AppState,
+};
+
+/// Returns a room the user can manage.
+///
+/// # Arguments
+///
+/// - room : The room name.
+/// - user : The token of the user.
+/// - state : The data shared across threads.
+async fn find_managed_room(
+    room: &str,
+    user: &AuthorizationToken,
+    state: &AppState,
+) -> Result<Room, ApiGenericResponse> {
+    RoomNameValidator::from(room.to_string()).validate()?;
+    let Some(room) = Room::find_by_name(room, &state.pg_pool).await? else {
+        return Err(ApiGenericResponse::RoomNotFound);
+    };
+    if !room.can_be_updated_by(user.user_id, user.user_profile) {
+        return Err(ApiGenericResponse::UnsifficentPriviledges);
+    }
+    Ok(room)
+}
+
+/// Returns the members of a room, along with the users invited.
+///
+/// # Arguments
+///
+/// - room : The room name.
+pub async fn get_room_memberships(
+    JwtUserExtractor(user): JwtUserExtractor,
+    Path(room): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Bincode<Vec<RoomMember>>, ApiGenericResponse> {
+    RoomNameValidator::from(room.clone()).validate()?;
+    if !Room::can_access(&room, user.user_id, &state.pg_pool).await? {
+        return Err(ApiGenericResponse::RoomNotFound);
+    }
+    let members = RoomMember::get_for_room(&room, &state.pg_pool).await?;
+    Ok(Bincode(members))
+}
+
+/// Invites a user to become a member of a room.
+///
+/// Only the owner of the room and the moderators can invite users.
+///
+/// # Arguments
+///
+/// - room : The room name.
+/// - user_id : The ID of the user invited.
+pub async fn invite_room_member(
+    JwtUserExtractor(user): JwtUserExtractor,
+    Path((room, user_id)): Path<(String, i32)>,
+    State(state): State<AppState>,
+) -> Result<ApiGenericResponse, ApiGenericResponse> {
+    let room = find_managed_room(&room, &user, &state).await?;
+    if room.owner_id == Some(user_id) {
+        return Err(ApiGenericResponse::UserAlreadyInvited);
+    }
+    match PartialUser::find_by_id(user_id, &state.pg_pool).await? {
+        Some(invited) if invited.is_authorized => {}
+        _ => return Err(ApiGenericResponse::UserNotFound),
+    }
+    match RoomMember::invite(&room.name, user_id, Some(user.user_id), &state.pg_pool).await? {
+        true => Ok(ApiGenericResponse::UserInvited),
+        false => Err(ApiGenericResponse::UserAlreadyInvited),
+    }
+}
+
+/// Removes a member from a room, or cancels their invitation.
+///
+/// Only the owner of the room and the moderators can remove other users,
+/// while any user can leave a room or decline an invitation. The sockets of
+/// the user connected to the room are closed if it is private.
+///
+/// # Arguments
+///
+/// - room : The room name.
+/// - user_id : The ID of the user removed.
+pub async fn remove_room_member(
+    JwtUserExtractor(user): JwtUserExtractor,
+    Path((room, user_id)): Path<(String, i32)>,
+    State(state): State<AppState>,
+) -> Result<ApiGenericResponse, ApiGenericResponse> {
+    if user_id != user.user_id {
+        find_managed_room(&room, &user, &state).await?;
+    }
+    if !RoomMember::remove(&room, user_id, &state.pg_pool).await? {
+        return Err(ApiGenericResponse::MemberNotFound);
+    }
+    if !Room::can_access(&room, user_id, &state.pg_pool).await? {
+        revoke_room_access(&state, &room, user_id).await;
+    }
+    Ok(ApiGenericResponse::MemberRemoved)
+}
+
+/// Transfers the ownership of a room to another user, the former owner
+/// staying a member of it.
+///
+/// Only the owner of the room and the moderators can transfer it.
+///
+/// # Arguments
+///
+/// - room : The room name.
+/// - user_id : The ID of the new owner.
+pub async fn transfer_room_ownership(
+    JwtUserExtractor(user): JwtUserExtractor,
+    Path((room, user_id)): Path<(String, i32)>,
+    State(state): State<AppState>,
+) -> Result<ApiGenericResponse, ApiGenericResponse> {
+    let room = find_managed_room(&room, &user, &state).await?;
+    match PartialUser::find_by_id(user_id, &state.pg_pool).await? {
+        Some(owner) if owner.is_authorized => {}
+        _ => return Err(ApiGenericResponse::UserNotFound),
+    }
+    let Some(room) = Room::transfer_ownership(&room.name, user_id, &state.pg_pool).await? else {
+        return Err(ApiGenericResponse::RoomNotFound);
+    };
+    let name = room.name.clone();
+    broadcast_to_room(&state, &name, &WsMessage::RoomUpdated(room)).await?;
+    Ok(ApiGenericResponse::OwnershipTransferred)
+}
+
+/// Returns the pending invitations of the user.
+pub async fn get_invitations(
+    JwtUserExtractor(user): JwtUserExtractor,
+    State(state): State<AppState>,
+) -> Result<Bincode<Vec<RoomInvitation>>, ApiGenericResponse> {
+    let invitations = RoomInvitation::get_for_user(user.user_id, &state.pg_pool).await?;
+    Ok(Bincode(invitations))
+}
+
+/// Accepts an invitation of the user, who becomes a member of the room.
+///
+/// # Arguments
+///
+/// - room : The room name.
+pub async fn accept_invitation(
+    JwtUserExtractor(user): JwtUserExtractor,
+    Path(room): Path<String>,
+    State(state): State<AppState>,
+) -> Result<ApiGenericResponse, ApiGenericResponse> {
+    match RoomMember::accept(&room, user.user_id, &state.pg_pool).await? {
+        true => Ok(ApiGenericResponse::InvitationAccepted),
+        false => Err(ApiGenericResponse::InvitationNotFound),
+    }
+}
@@ -22,6 +22,7 @@ use tchatchers_core::report::Report;
 use tchatchers_core::serializable_token::SerializableToken;
 use tchatchers_core::user::PartialUser;
 use tchatchers_core::user::{AuthenticableUser, InsertableUser, UpdatableUser, User};
+use tchatchers_core::ws_ticket::WsTicket;
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 use tokio::task::JoinSet;
@@ -98,7 +99,7 @@ pub async fn authenticate(
                 .await;
             anyhow::Ok(())
         }));
-        let jwt: AuthorizationToken = AuthorizationToken::from(user);
+        let jwt = AuthorizationToken::new(user, refresh_token.token_family);
         Ok((
             StatusCode::OK,
             refresh_token.store_in_jar(&state.refresh_token_secret, cookie_jar)?,
@@ -149,6 +150,7 @@ pub async fn reauthenticate(
     // Refresh the token.
     if !refresh_token.is_head_token(&mut redis_conn).await? {
         refresh_token.revoke_family(&mut redis_conn).await?;
+        state.connections.kick(refresh_token.user_id).await;
         return Err(ApiGenericResponse::AuthenticationExpired);
     }
     let refreshed_token = refresh_token.renew();
@@ -173,7 +175,8 @@ pub async fn reauthenticate(
         anyhow::Ok(())
     }));
 
-    let encoded_jwt: String = AuthorizationToken::from(user).encode(&state.jwt_secret)?;
+    let encoded_jwt: String =
+        AuthorizationToken::new(user, refreshed_token.token_family).encode(&state.jwt_secret)?;
 
     // Renew the refresh token and store the updated value in the cookie jar.
     Ok((
@@ -201,6 +204,11 @@ pub async fn logout(
             // Get a Redis connection from the Redis connection pool.
             let mut redis_conn = state.session_pool;
             refresh_token.revoke_family(&mut redis_conn).await?;
+            // Only the sockets of the session logged out are closed.
+            state
+                .connections
+                .kick_session(refresh_token.user_id, refresh_token.token_family)
+                .await;
         }
     }
 
@@ -219,6 +227,25 @@ pub async fn validate(_: JwtUserExtractor) -> impl IntoResponse {
     StatusCode::OK
 }
 
+/// Issues a ticket that can be used once to open an authenticated websocket.
+///
+/// The ticket is short-lived, and has to be passed as the `ticket` query
+/// parameter of the websocket upgrade request.
+///
+/// # Arguments
+///
+/// - jwt : The user's authentication token.
+/// - state : The data shared across thread.
+pub async fn create_ws_ticket(
+    JwtUserExtractor(jwt): JwtUserExtractor,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiGenericResponse> {
+    let ticket = WsTicket::new(jwt.user_id, jwt.exp, jwt.token_family);
+    let mut redis_conn = state.session_pool;
+    ticket.store(&mut redis_conn).await?;
+    Ok((StatusCode::OK, ticket.ticket.to_string()))
+}
+
 /// Update the user's informations.
 ///
 /// There is a check server side to ensure that the user is only able to update
@@ -309,6 +336,7 @@ pub async fn delete_user(
         anyhow::Ok(())
     }));
     User::delete_one(jwt.user_id, &state.pg_pool).await?;
+    state.connections.kick(jwt.user_id).await;
     Ok(StatusCode::OK)
 }
 
@@ -321,6 +349,7 @@ pub async fn revoke_user(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, ApiGenericResponse> {
     User::update_activation_status(user_id, false, &state.pg_pool).await?;
+    state.connections.kick(user_id).await;
     Ok(ApiGenericResponse::RevokedUser)
 }
 
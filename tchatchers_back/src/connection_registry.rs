@@ -0,0 +1,309 @@
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+//! The connection registry keeps track of the authenticated sockets of every
+//! user, so that they can be closed once the user's session ends, or be sent
+//! messages whatever the room they are connected to.
+//!
+//! The sockets are registered along with the token family of the session they
+//! have been opened from, so that logging out of a session only closes its own
+//! sockets.
+//!
+//! When the `ROOM_BUS` env variable is set to `redis`, the users kicked and
+//! the messages sent to users are also published on Redis channels, so that
+//! their sockets connected to the other instances are reached as well.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures_util::StreamExt;
+use redis::{aio::MultiplexedConnection, AsyncCommands};
+use tchatchers_core::{
+    ws_codec::{WireFormat, WsCodec},
+    ws_message::WsMessage,
+};
+use tokio::{
+    sync::{broadcast, Notify},
+    time::{sleep_until, Duration, Instant},
+};
+use uuid::Uuid;
+
+/// The Redis channel on which the users kicked are published.
+const KICK_CHANNEL: &str = "user_kick";
+
+/// The Redis channel on which the sessions kicked are published.
+const SESSION_KICK_CHANNEL: &str = "session_kick";
+
+/// The Redis channel on which the messages sent to users are published.
+const USER_MESSAGE_CHANNEL: &str = "user_message";
+
+/// The codec of the messages sent to users exchanged between the instances.
+const USER_MESSAGE_WIRE_FORMAT: WireFormat = WireFormat::Bincode;
+
+/// An authenticated socket.
+#[derive(Debug)]
+struct SocketHandle {
+    /// The token family of the session the socket has been opened from, if
+    /// known.
+    token_family: Option<Uuid>,
+    /// Notified when the socket's user is kicked.
+    kicked: Arc<Notify>,
+    /// The channel used to send messages to the socket only.
+    tx: broadcast::Sender<WsMessage>,
+}
+
+/// The sockets of a user, indexed by their ID.
+type UserSockets = HashMap<Uuid, SocketHandle>;
+
+/// The registry of the authenticated sockets.
+#[derive(Clone, Debug, Default)]
+pub struct ConnectionRegistry {
+    /// The sockets of every user, indexed by user ID.
+    users: Arc<Mutex<HashMap<i32, UserSockets>>>,
+    /// The connection used to publish the users kicked, if shared with the
+    /// other instances.
+    con: Option<MultiplexedConnection>,
+}
+
+impl ConnectionRegistry {
+    /// Creates the connection registry configured by the user env.
+    ///
+    /// When Redis is used, the users kicked and the messages sent by the other
+    /// instances are applied to this one for as long as the server runs.
+    ///
+    /// # Arguments
+    ///
+    /// - con : The connection used to publish the users kicked.
+    pub async fn from_env(con: MultiplexedConnection) -> Result<Self, redis::RedisError> {
+        let Ok("redis") = std::env::var("ROOM_BUS").as_deref() else {
+            return Ok(Self::default());
+        };
+        let registry = Self {
+            users: Arc::default(),
+            con: Some(con),
+        };
+        let mut pubsub = tchatchers_core::pool::get_pubsub().await?;
+        pubsub
+            .subscribe(&[KICK_CHANNEL, SESSION_KICK_CHANNEL, USER_MESSAGE_CHANNEL])
+            .await?;
+        let users = registry.users.clone();
+        tokio::spawn(async move {
+            let mut messages = pubsub.into_on_message();
+            while let Some(msg) = messages.next().await {
+                match msg.get_channel_name() {
+                    KICK_CHANNEL => {
+                        if let Ok(user_id) = msg.get_payload::<i32>() {
+                            Self::kick_local(&users, user_id, None);
+                        }
+                    }
+                    // Sessions are identified by their user ID and token family.
+                    SESSION_KICK_CHANNEL => {
+                        let payload = msg.get_payload_bytes();
+                        let Some((user_id, token_family)) = payload.split_first_chunk::<4>() else {
+                            continue;
+                        };
+                        if let Ok(token_family) = Uuid::from_slice(token_family) {
+                            Self::kick_local(
+                                &users,
+                                i32::from_be_bytes(*user_id),
+                                Some(token_family),
+                            );
+                        }
+                    }
+                    // Messages are prefixed by the ID of the user they are sent to.
+                    _ => {
+                        let payload = msg.get_payload_bytes();
+                        let Some((user_id, frame)) = payload.split_first_chunk::<4>() else {
+                            continue;
+                        };
+                        match USER_MESSAGE_WIRE_FORMAT.decode(frame) {
+                            Ok(msg) => Self::send_local(&users, i32::from_be_bytes(*user_id), msg),
+                            Err(e) => tracing::error!("Could not decode a message to a user : {e}"),
+                        }
+                    }
+                }
+            }
+            tracing::error!(
+                "The connection registry stopped receiving the events of other instances"
+            );
+        });
+        Ok(registry)
+    }
+
+    /// Registers an authenticated socket.
+    ///
+    /// The socket is unregistered once the returned session is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// - user_id : The ID of the socket's user.
+    /// - token_family : The token family of the session the socket has been
+    ///   opened from, if known.
+    /// - exp : The timestamp at which the user's token expires.
+    /// - tx : The channel used to send messages to the socket only.
+    pub fn register(
+        &self,
+        user_id: i32,
+        token_family: Option<Uuid>,
+        exp: i64,
+        tx: broadcast::Sender<WsMessage>,
+    ) -> Session {
+        let socket_id = Uuid::new_v4();
+        let kicked = Arc::new(Notify::new());
+        self.users
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(user_id)
+            .or_default()
+            .insert(
+                socket_id,
+                SocketHandle {
+                    token_family,
+                    kicked: kicked.clone(),
+                    tx,
+                },
+            );
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        let remaining = (exp - now).max(0);
+        Session {
+            users: self.users.clone(),
+            user_id,
+            socket_id,
+            kicked,
+            expires_at: Instant::now() + Duration::from_secs(remaining as u64),
+        }
+    }
+
+    /// Closes all the sockets of a user, on this instance and on the other
+    /// ones.
+    ///
+    /// # Arguments
+    ///
+    /// - user_id : The ID of the user kicked.
+    pub async fn kick(&self, user_id: i32) {
+        Self::kick_local(&self.users, user_id, None);
+        if let Some(con) = &self.con {
+            let mut con = con.clone();
+            if let Err(e) = con.publish::<_, _, ()>(KICK_CHANNEL, user_id).await {
+                tracing::error!("Could not publish the kick of the user {user_id} : {e}");
+            }
+        }
+    }
+
+    /// Closes the sockets opened from a session of a user, on this instance
+    /// and on the other ones.
+    ///
+    /// # Arguments
+    ///
+    /// - user_id : The ID of the user.
+    /// - token_family : The token family of the session kicked.
+    pub async fn kick_session(&self, user_id: i32, token_family: Uuid) {
+        Self::kick_local(&self.users, user_id, Some(token_family));
+        if let Some(con) = &self.con {
+            let payload = [user_id.to_be_bytes().as_slice(), token_family.as_bytes()].concat();
+            let mut con = con.clone();
+            if let Err(e) = con.publish::<_, _, ()>(SESSION_KICK_CHANNEL, payload).await {
+                tracing::error!(
+                    "Could not publish the kick of a session of the user {user_id} : {e}"
+                );
+            }
+        }
+    }
+
+    /// Sends a message to all the sockets of a user, on this instance and on
+    /// the other ones.
+    ///
+    /// # Arguments
+    ///
+    /// - user_id : The ID of the user.
+    /// - msg : The message to send.
+    pub async fn send(&self, user_id: i32, msg: WsMessage) {
+        // The instance receiving its own publications, the local sockets are
+        // reached through Redis unless the message can't be published.
+        if let Some(con) = &self.con {
+            match USER_MESSAGE_WIRE_FORMAT.encode(&msg) {
+                Ok(frame) => {
+                    let payload = [user_id.to_be_bytes().as_slice(), &frame].concat();
+                    let mut con = con.clone();
+                    match con.publish::<_, _, ()>(USER_MESSAGE_CHANNEL, payload).await {
+                        Ok(()) => return,
+                        Err(e) => tracing::error!(
+                            "Could not publish a message to the user {user_id} : {e}"
+                        ),
+                    }
+                }
+                Err(e) => tracing::error!("Could not encode a message to the user {user_id} : {e}"),
+            }
+        }
+        Self::send_local(&self.users, user_id, msg);
+    }
+
+    /// Closes the sockets of a user connected to this instance, only the ones
+    /// of the given session if any.
+    fn kick_local(
+        users: &Mutex<HashMap<i32, UserSockets>>,
+        user_id: i32,
+        token_family: Option<Uuid>,
+    ) {
+        let users = users.lock().unwrap_or_else(|e| e.into_inner());
+        for socket in users
+            .get(&user_id)
+            .into_iter()
+            .flat_map(HashMap::values)
+            .filter(|socket| token_family.is_none() || socket.token_family == token_family)
+        {
+            socket.kicked.notify_one();
+        }
+    }
+
+    /// Sends a message to the sockets of a user connected to this instance.
+    fn send_local(users: &Mutex<HashMap<i32, UserSockets>>, user_id: i32, msg: WsMessage) {
+        let users = users.lock().unwrap_or_else(|e| e.into_inner());
+        for socket in users.get(&user_id).into_iter().flat_map(HashMap::values) {
+            let _ = socket.tx.send(msg.clone());
+        }
+    }
+}
+
+/// The registration of an authenticated socket.
+#[derive(Debug)]
+pub struct Session {
+    /// The sockets of every user, from which the socket is removed on drop.
+    users: Arc<Mutex<HashMap<i32, UserSockets>>>,
+    /// The ID of the socket's user.
+    user_id: i32,
+    /// The ID of the socket.
+    socket_id: Uuid,
+    /// Notified when the user is kicked.
+    kicked: Arc<Notify>,
+    /// When the user's token expires.
+    expires_at: Instant,
+}
+
+impl Session {
+    /// Waits until the user is kicked or their token expires.
+    pub async fn ended(&self) {
+        tokio::select! {
+            _ = self.kicked.notified() => {}
+            _ = sleep_until(self.expires_at) => {}
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let mut users = self.users.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sockets) = users.get_mut(&self.user_id) {
+            sockets.remove(&self.socket_id);
+            if sockets.is_empty() {
+                users.remove(&self.user_id);
+            }
+        }
+    }
+}
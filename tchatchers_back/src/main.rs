@@ -8,11 +8,26 @@
 //! Redis) and communicate then in a convenient way with the client application.
 
 pub mod api;
+pub mod connection_registry;
 pub mod extractor;
+pub mod rate_limit;
+pub mod room_bus;
 pub mod ws;
 
+use api::direct_message::get_direct_conversations;
 use api::message::delete_message;
+use api::message::get_message_history;
+use api::message::get_message_readers;
 use api::message::report_message;
+use api::notification::{get_notifications, mark_notification_read, mark_notifications_read};
+use api::room::{
+    create_room, get_room, get_room_members, get_room_messages, get_rooms, get_rooms_activity,
+    get_unread_counts, set_room_posting_rules, update_room,
+};
+use api::room_member::{
+    accept_invitation, get_invitations, get_room_memberships, invite_room_member,
+    remove_room_member, transfer_room_ownership,
+};
 use api::user::*;
 use axum::http::header::AUTHORIZATION;
 use axum::http::header::COOKIE;
@@ -21,12 +36,16 @@ use axum::http::HeaderName;
 use axum::http::HeaderValue;
 use axum::routing::delete;
 use axum::routing::get_service;
+use axum::routing::put;
 use axum::{
     http::StatusCode,
     routing::{get, post},
     Router,
 };
+use connection_registry::ConnectionRegistry;
+use rate_limit::RateLimiter;
 use redis::aio::MultiplexedConnection;
+use room_bus::RoomBus;
 use sqlx::postgres::PgPool;
 use std::future::IntoFuture;
 use std::iter::once;
@@ -53,6 +72,12 @@ use ws::WsRooms;
 
 const REFRESH_TOKEN_PATH: &str = "refresh_token";
 
+/// The git revision the server has been built from.
+const GIT_REV: &str = match option_env!("GIT_REV") {
+    Some(rev) => rev,
+    None => "unknown",
+};
+
 #[derive(Clone)]
 /// The data that is shared across the processes.
 pub struct AppState {
@@ -68,6 +93,19 @@ pub struct AppState {
     session_pool: MultiplexedConnection,
     /// Redis async pool.
     async_pool: MultiplexedConnection,
+    /// Delay after which a websocket that didn't authenticate is closed.
+    ws_authentication_timeout: Duration,
+    /// The maximum number of rooms that can be live at the same time.
+    ws_max_rooms: usize,
+    /// The number of messages a socket can lag behind its room before having
+    /// to resync.
+    ws_room_capacity: usize,
+    /// The bus sharing the rooms' frames with the other instances.
+    room_bus: RoomBus,
+    /// The flood control of the frames sent over the websockets.
+    rate_limiter: RateLimiter,
+    /// The authenticated sockets of every user.
+    connections: ConnectionRegistry,
 }
 
 #[tokio::main]
@@ -83,6 +121,21 @@ async fn main() -> anyhow::Result<()> {
     let jwt_secret = std::env::var("JWT_SECRET").expect("No jwt secret has been defined");
     let refresh_token_secret = std::env::var("REFRESH_TOKEN_SECRET")
         .expect("No refresh token signature key has been defined");
+    let ws_authentication_timeout = Duration::from_secs(
+        std::env::var("WS_AUTHENTICATION_TIMEOUT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(10),
+    );
+    let ws_max_rooms = std::env::var("WS_MAX_ROOMS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1_000);
+    let ws_room_capacity = std::env::var("WS_ROOM_CAPACITY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|capacity| *capacity > 0)
+        .unwrap_or(1_000);
     let (pg_pool, session_pool, async_pool) = join!(
         tchatchers_core::pool::get_pg_pool(),
         tchatchers_core::pool::get_session_pool(),
@@ -95,13 +148,23 @@ async fn main() -> anyhow::Result<()> {
         .run(&pg_pool)
         .await
         .expect("Could not apply migrations on the database");
+    let txs = Arc::new(Mutex::new(WsRooms::default()));
+    let room_bus = RoomBus::from_env(session_pool.clone(), txs.clone()).await?;
+    let rate_limiter = RateLimiter::from_env(session_pool.clone());
+    let connections = ConnectionRegistry::from_env(session_pool.clone()).await?;
     let shared_state = AppState {
         refresh_token_secret,
         jwt_secret,
-        txs: Arc::new(Mutex::new(WsRooms::default())),
+        txs,
         pg_pool,
         session_pool,
         async_pool,
+        ws_authentication_timeout,
+        ws_max_rooms,
+        ws_room_capacity,
+        room_bus,
+        rate_limiter,
+        connections,
     };
 
     let app = Router::new()
@@ -119,8 +182,42 @@ async fn main() -> anyhow::Result<()> {
         .route("/api/logout", get(logout))
         .route("/api/validate", get(validate))
         .route("/api/whoami", get(whoami))
+        .route("/api/ws_ticket", post(create_ws_ticket))
+        .route("/api/dm", get(get_direct_conversations))
         .route("/api/message/{message_id}", delete(delete_message))
         .route("/api/message/{message_id}/report", post(report_message))
+        .route(
+            "/api/message/{message_id}/history",
+            get(get_message_history),
+        )
+        .route(
+            "/api/message/{message_id}/readers",
+            get(get_message_readers),
+        )
+        .route("/api/invitations", get(get_invitations))
+        .route("/api/invitations/{room}/accept", post(accept_invitation))
+        .route("/api/notifications", get(get_notifications))
+        .route("/api/notifications/read", post(mark_notifications_read))
+        .route(
+            "/api/notifications/{notification_id}/read",
+            post(mark_notification_read),
+        )
+        .route("/api/room/{room}", get(get_room).put(update_room))
+        .route("/api/room/{room}/members", get(get_room_members))
+        .route("/api/room/{room}/memberships", get(get_room_memberships))
+        .route(
+            "/api/room/{room}/memberships/{user_id}",
+            post(invite_room_member).delete(remove_room_member),
+        )
+        .route(
+            "/api/room/{room}/owner/{user_id}",
+            post(transfer_room_ownership),
+        )
+        .route("/api/room/{room}/messages", get(get_room_messages))
+        .route("/api/room/{room}/posting", put(set_room_posting_rules))
+        .route("/api/rooms", get(get_rooms).post(create_room))
+        .route("/api/rooms/activity", get(get_rooms_activity))
+        .route("/api/rooms/unread", get(get_unread_counts))
         .route("/ws/{room}", get(ws_handler))
         .nest_service(
             "/static",
@@ -130,7 +227,7 @@ async fn main() -> anyhow::Result<()> {
         .with_state(shared_state)
         .layer(SetResponseHeaderLayer::overriding(
             HeaderName::from_static("x-rev-id"),
-            HeaderValue::from_static(option_env!("GIT_REV").unwrap_or("unknown")),
+            HeaderValue::from_static(GIT_REV),
         ))
         .layer(
             TraceLayer::new_for_http()
@@ -0,0 +1,506 @@
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+//! Flood control of the frames sent by the clients over the websockets.
+//!
+//! Every kind of frame is limited by a token bucket, both per connection and
+//! per user. The limits are configured with the `WS_RATE_LIMIT_<KIND>` env
+//! variables, formatted as `<capacity>/<seconds>`, meaning that `capacity`
+//! frames can be sent at once and that the bucket is fully refilled after
+//! `seconds`.
+//!
+//! The per user buckets are kept in memory, unless `WS_RATE_LIMIT_BACKEND` is
+//! set to `redis`, in which case they are shared by all the instances.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use redis::aio::MultiplexedConnection;
+use tchatchers_core::ws_message::WsMessage;
+
+/// The window during which the over limit frames of a connection are counted.
+const STRIKE_WINDOW: Duration = Duration::from_secs(60);
+
+/// How often the full per user buckets kept in memory are pruned.
+const BUCKET_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Atomically takes a token from a bucket stored in Redis.
+///
+/// Returns the number of milliseconds to wait before a token is available, or
+/// 0 if one has been taken.
+const REDIS_TOKEN_BUCKET: &str = r"
+local capacity = tonumber(ARGV[1])
+local refill_per_ms = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(bucket[1]) or capacity
+local ts = tonumber(bucket[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - ts) * refill_per_ms)
+local retry_after = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+else
+    retry_after = math.ceil((1 - tokens) / refill_per_ms)
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity / refill_per_ms))
+return retry_after
+";
+
+/// The kinds of frames that are limited independently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameKind {
+    /// New messages.
+    Send,
+    /// Read acknowledgements.
+    Seen,
+    /// Pings.
+    Ping,
+    /// Any other action on the room or its messages.
+    Action,
+}
+
+impl FrameKind {
+    /// All the kinds of frames.
+    const ALL: [FrameKind; 4] = [Self::Send, Self::Seen, Self::Ping, Self::Action];
+
+    /// Returns the kind of a frame, or none if it isn't limited.
+    ///
+    /// # Arguments
+    ///
+    /// - msg : The frame received.
+    pub fn of(msg: &WsMessage) -> Option<Self> {
+        match msg {
+            WsMessage::Send(_) => Some(Self::Send),
+            WsMessage::Seen(_) => Some(Self::Seen),
+            WsMessage::Ping => Some(Self::Ping),
+            WsMessage::Close | WsMessage::Pong | WsMessage::ClientKeepAlive => None,
+            _ => Some(Self::Action),
+        }
+    }
+
+    /// The name of the kind, used in the env variables and the Redis keys.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Send => "SEND",
+            Self::Seen => "SEEN",
+            Self::Ping => "PING",
+            Self::Action => "ACTION",
+        }
+    }
+
+    /// The limit applied when none is configured.
+    fn default_limit(&self) -> RateLimit {
+        match self {
+            Self::Send => RateLimit::new(10, 10),
+            Self::Seen => RateLimit::new(60, 10),
+            Self::Ping => RateLimit::new(10, 10),
+            Self::Action => RateLimit::new(60, 10),
+        }
+    }
+}
+
+/// The limit of a kind of frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// The number of frames that can be sent at once.
+    capacity: f64,
+    /// The number of frames regained per second.
+    refill_per_sec: f64,
+}
+
+impl RateLimit {
+    /// Creates a limit of `capacity` frames, fully regained after `seconds`.
+    fn new(capacity: u32, seconds: u32) -> Self {
+        Self {
+            capacity: capacity as f64,
+            refill_per_sec: capacity as f64 / seconds as f64,
+        }
+    }
+
+    /// The number of frames that can be sent at once.
+    pub fn burst(&self) -> u32 {
+        self.capacity as u32
+    }
+
+    /// The number of seconds after which the bucket is fully refilled.
+    pub fn window(&self) -> u32 {
+        (self.capacity / self.refill_per_sec).round() as u32
+    }
+
+    /// Parses a limit formatted as `<capacity>/<seconds>`.
+    fn parse(value: &str) -> Option<Self> {
+        let (capacity, seconds) = value.split_once('/')?;
+        let (capacity, seconds): (u32, u32) =
+            (capacity.trim().parse().ok()?, seconds.trim().parse().ok()?);
+        (capacity > 0 && seconds > 0).then(|| Self::new(capacity, seconds))
+    }
+}
+
+/// A bucket of tokens, one being taken for every frame sent.
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    /// The tokens left.
+    tokens: f64,
+    /// When the tokens have been refilled for the last time.
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Creates a full bucket.
+    fn new(limit: &RateLimit) -> Self {
+        Self {
+            tokens: limit.capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Refills the bucket, without taking any token from it.
+    ///
+    /// Returns the delay after which a token will be available if the bucket
+    /// is empty.
+    fn peek(&mut self, limit: &RateLimit) -> Option<Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.refill_per_sec).min(limit.capacity);
+        self.last_refill = now;
+        (self.tokens < 1.0)
+            .then(|| Duration::from_secs_f64((1.0 - self.tokens) / limit.refill_per_sec))
+    }
+
+    /// Takes a token from the bucket.
+    ///
+    /// Returns the delay after which a token will be available if the bucket
+    /// is empty.
+    fn take(&mut self, limit: &RateLimit) -> Option<Duration> {
+        let retry_after = self.peek(limit);
+        if retry_after.is_none() {
+            self.tokens -= 1.0;
+        }
+        retry_after
+    }
+
+    /// Whether the bucket would be full by now.
+    fn is_full(&self, limit: &RateLimit) -> bool {
+        let elapsed = self.last_refill.elapsed().as_secs_f64();
+        self.tokens + elapsed * limit.refill_per_sec >= limit.capacity
+    }
+}
+
+/// The outcome of the rate limiting of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The frame can be processed.
+    Allowed,
+    /// The frame has to be dropped, and the client can retry after the given
+    /// number of seconds.
+    Limited { retry_after: u64 },
+    /// The connection sent too many frames over the limit and has to be
+    /// closed, the client being able to reconnect after the given number of
+    /// seconds.
+    Disconnect { retry_after: u64 },
+}
+
+/// The rate limiter shared by all the connections.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    /// The limits of each kind of frame.
+    limits: HashMap<FrameKind, RateLimit>,
+    /// The number of over limit frames after which a connection is closed.
+    max_strikes: u32,
+    /// The per user buckets, when kept in memory.
+    users: Arc<Mutex<HashMap<(i32, FrameKind), TokenBucket>>>,
+    /// The connection used to store the per user buckets, when kept in Redis.
+    redis: Option<MultiplexedConnection>,
+}
+
+impl RateLimiter {
+    /// Creates the rate limiter configured by the user env.
+    ///
+    /// When the per user buckets are kept in memory, the full ones are pruned
+    /// periodically for as long as the server runs.
+    ///
+    /// # Arguments
+    ///
+    /// - con : The connection used to store the per user buckets if Redis is
+    ///   used as backend.
+    pub fn from_env(con: MultiplexedConnection) -> Self {
+        let limits = FrameKind::ALL
+            .into_iter()
+            .map(|kind| {
+                let limit = std::env::var(format!("WS_RATE_LIMIT_{}", kind.name()))
+                    .ok()
+                    .and_then(|v| RateLimit::parse(&v))
+                    .unwrap_or_else(|| kind.default_limit());
+                (kind, limit)
+            })
+            .collect();
+        let max_strikes = std::env::var("WS_RATE_LIMIT_MAX_STRIKES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(20);
+        let redis = match std::env::var("WS_RATE_LIMIT_BACKEND").as_deref() {
+            Ok("redis") => Some(con),
+            _ => None,
+        };
+        let limiter = Self {
+            limits,
+            max_strikes,
+            users: Arc::new(Mutex::new(HashMap::new())),
+            redis,
+        };
+        if limiter.redis.is_none() {
+            let (users, limits) = (limiter.users.clone(), limiter.limits.clone());
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(BUCKET_PRUNE_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    users
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .retain(|(_, kind), bucket| !bucket.is_full(&limits[kind]));
+                }
+            });
+        }
+        limiter
+    }
+
+    /// The limit of a kind of frames.
+    ///
+    /// # Arguments
+    ///
+    /// - kind : The kind of frames.
+    pub fn limit(&self, kind: FrameKind) -> RateLimit {
+        self.limits[&kind]
+    }
+
+    /// Takes a token from the bucket of a user.
+    ///
+    /// Redis errors don't prevent the frame from being processed.
+    async fn take_for_user(
+        &self,
+        user_id: i32,
+        kind: FrameKind,
+        limit: &RateLimit,
+    ) -> Option<Duration> {
+        if let Some(con) = &self.redis {
+            let mut con = con.clone();
+            let retry_after: Result<u64, redis::RedisError> = redis::cmd("EVAL")
+                .arg(REDIS_TOKEN_BUCKET)
+                .arg(1)
+                .arg(format!("ws_rate_limit:{user_id}:{}", kind.name()))
+                .arg(limit.capacity)
+                .arg(limit.refill_per_sec / 1_000.0)
+                .query_async(&mut con)
+                .await;
+            return match retry_after {
+                Ok(0) => None,
+                Ok(ms) => Some(Duration::from_millis(ms)),
+                Err(e) => {
+                    tracing::error!("Could not rate limit the user {user_id} : {e}");
+                    None
+                }
+            };
+        }
+        self.users
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry((user_id, kind))
+            .or_insert_with(|| TokenBucket::new(limit))
+            .take(limit)
+    }
+}
+
+/// The rate limiting state of a connection.
+#[derive(Debug)]
+pub struct ConnectionLimiter {
+    /// The limiter shared by all the connections.
+    limiter: RateLimiter,
+    /// The buckets of the connection.
+    buckets: HashMap<FrameKind, TokenBucket>,
+    /// The number of over limit frames sent within the current window.
+    strikes: u32,
+    /// When the current strike window started.
+    strike_window_start: Instant,
+}
+
+impl ConnectionLimiter {
+    /// Creates the rate limiting state of a new connection.
+    pub fn new(limiter: RateLimiter) -> Self {
+        Self {
+            limiter,
+            buckets: HashMap::new(),
+            strikes: 0,
+            strike_window_start: Instant::now(),
+        }
+    }
+
+    /// Checks whether a frame can be processed.
+    ///
+    /// # Arguments
+    ///
+    /// - user_id : The ID of the authenticated user of the connection, if any.
+    /// - kind : The kind of the frame.
+    pub async fn check(&mut self, user_id: Option<i32>, kind: FrameKind) -> Verdict {
+        let limit = self.limiter.limits[&kind];
+        let bucket = self
+            .buckets
+            .entry(kind)
+            .or_insert_with(|| TokenBucket::new(&limit));
+        // The token of the connection is only taken once the user's one has
+        // been, so that it isn't lost when the frame is limited per user.
+        let mut retry_after = bucket.peek(&limit);
+        if let (None, Some(user_id)) = (retry_after, user_id) {
+            retry_after = self.limiter.take_for_user(user_id, kind, &limit).await;
+        }
+        if retry_after.is_none() {
+            bucket.take(&limit);
+        }
+        let Some(retry_after) = retry_after else {
+            return Verdict::Allowed;
+        };
+        if self.strike_window_start.elapsed() > STRIKE_WINDOW {
+            self.strikes = 0;
+            self.strike_window_start = Instant::now();
+        }
+        self.strikes += 1;
+        if self.strikes > self.limiter.max_strikes {
+            Verdict::Disconnect {
+                retry_after: STRIKE_WINDOW.as_secs(),
+            }
+        } else {
+            Verdict::Limited {
+                retry_after: retry_after.as_secs_f64().ceil() as u64,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a limiter keeping the per user buckets in memory.
+    fn limiter(limit: RateLimit, max_strikes: u32) -> RateLimiter {
+        RateLimiter {
+            limits: FrameKind::ALL
+                .into_iter()
+                .map(|kind| (kind, limit))
+                .collect(),
+            max_strikes,
+            users: Arc::default(),
+            redis: None,
+        }
+    }
+
+    #[test]
+    fn parse_limits() {
+        let limit = RateLimit::parse(" 10 / 5 ").unwrap();
+        assert_eq!((limit.burst(), limit.window()), (10, 5));
+        for value in ["10", "0/5", "10/0", "-1/5", "a/5", "10/5/1"] {
+            assert_eq!(RateLimit::parse(value), None, "{value}");
+        }
+    }
+
+    #[test]
+    fn bucket_empties_then_refills() {
+        let limit = RateLimit::new(2, 10);
+        let mut bucket = TokenBucket::new(&limit);
+        assert_eq!(bucket.take(&limit), None);
+        assert_eq!(bucket.take(&limit), None);
+        let retry_after = bucket.take(&limit).unwrap();
+        assert!(retry_after > Duration::from_secs(4) && retry_after <= Duration::from_secs(5));
+        assert!(!bucket.is_full(&limit));
+
+        // A token is regained every 5 seconds.
+        bucket.last_refill -= Duration::from_secs(5);
+        assert_eq!(bucket.take(&limit), None);
+        assert!(bucket.take(&limit).is_some());
+
+        // The bucket doesn't hold more than its capacity.
+        bucket.last_refill -= Duration::from_secs(60);
+        assert!(bucket.is_full(&limit));
+        assert_eq!(bucket.take(&limit), None);
+        assert_eq!(bucket.take(&limit), None);
+        assert!(bucket.take(&limit).is_some());
+    }
+
+    #[test]
+    fn peek_doesnt_take_any_token() {
+        let limit = RateLimit::new(1, 10);
+        let mut bucket = TokenBucket::new(&limit);
+        assert_eq!(bucket.peek(&limit), None);
+        assert_eq!(bucket.peek(&limit), None);
+        assert_eq!(bucket.take(&limit), None);
+        assert!(bucket.peek(&limit).is_some());
+    }
+
+    #[tokio::test]
+    async fn connection_is_closed_after_too_many_strikes() {
+        let mut connection = ConnectionLimiter::new(limiter(RateLimit::new(1, 3600), 2));
+        assert_eq!(
+            connection.check(None, FrameKind::Send).await,
+            Verdict::Allowed
+        );
+        for _ in 0..2 {
+            assert!(matches!(
+                connection.check(None, FrameKind::Send).await,
+                Verdict::Limited { retry_after } if retry_after > 3_000
+            ));
+        }
+        assert_eq!(
+            connection.check(None, FrameKind::Send).await,
+            Verdict::Disconnect {
+                retry_after: STRIKE_WINDOW.as_secs()
+            }
+        );
+        // The other kinds of frames have their own buckets.
+        assert_eq!(
+            connection.check(None, FrameKind::Ping).await,
+            Verdict::Allowed
+        );
+    }
+
+    #[tokio::test]
+    async fn strikes_are_forgotten_after_their_window() {
+        let mut connection = ConnectionLimiter::new(limiter(RateLimit::new(1, 3600), 1));
+        assert_eq!(
+            connection.check(None, FrameKind::Send).await,
+            Verdict::Allowed
+        );
+        assert!(matches!(
+            connection.check(None, FrameKind::Send).await,
+            Verdict::Limited { .. }
+        ));
+        connection.strike_window_start -= STRIKE_WINDOW + Duration::from_secs(1);
+        assert!(matches!(
+            connection.check(None, FrameKind::Send).await,
+            Verdict::Limited { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn user_bucket_is_shared_by_their_connections() {
+        let limiter = limiter(RateLimit::new(1, 3600), 10);
+        let mut first = ConnectionLimiter::new(limiter.clone());
+        let mut second = ConnectionLimiter::new(limiter);
+        assert_eq!(
+            first.check(Some(1), FrameKind::Send).await,
+            Verdict::Allowed
+        );
+        assert!(matches!(
+            second.check(Some(1), FrameKind::Send).await,
+            Verdict::Limited { .. }
+        ));
+        // The connection's token isn't taken when the frame is limited per user.
+        assert!(second.buckets[&FrameKind::Send].tokens >= 1.0);
+        assert_eq!(
+            second.check(Some(2), FrameKind::Send).await,
+            Verdict::Allowed
+        );
+    }
+}
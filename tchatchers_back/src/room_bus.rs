@@ -0,0 +1,312 @@
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+//! The room bus shares the frames broadcast within the rooms between the
+//! instances of the server.
+//!
+//! By default, the frames are only shared among the sockets of the current
+//! instance. When the `ROOM_BUS` env variable is set to `redis`, they are also
+//! published on a Redis channel per room, and the frames published by the other
+//! instances are relayed to the local sockets of the room.
+//!
+//! The frames exchanged between the instances are encoded with bincode,
+//! whatever the codecs negotiated by the sockets.
+//!
+//! With Redis, the members of the rooms are also shared between the instances,
+//! each instance keeping its members in a Redis hash per room. The members of
+//! the instances that stopped refreshing their heartbeat are ignored and
+//! cleaned up.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use futures_util::StreamExt;
+use redis::{aio::MultiplexedConnection, AsyncCommands};
+use tchatchers_core::{
+    user::PartialUser,
+    ws_codec::{WireFormat, WsCodec},
+};
+use tokio::sync::{broadcast, Mutex};
+use uuid::Uuid;
+
+use crate::ws::{SharedMessage, WsRooms};
+
+/// The codec of the frames exchanged between the instances.
+const BUS_WIRE_FORMAT: WireFormat = WireFormat::Bincode;
+
+/// The prefix of the Redis channels used for the rooms.
+const ROOM_CHANNEL_PREFIX: &str = "room:";
+
+/// The prefix of the Redis hashes storing the members of the rooms.
+const PRESENCE_KEY_PREFIX: &str = "presence:";
+
+/// The prefix of the Redis keys telling that an instance is still running.
+const INSTANCE_KEY_PREFIX: &str = "instance:";
+
+/// How often an instance refreshes its heartbeat.
+const INSTANCE_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The number of seconds after which an instance that stopped refreshing its
+/// heartbeat is considered as stopped.
+const INSTANCE_HEARTBEAT_TTL: u64 = 30;
+
+/// The bus on which the frames of the rooms are shared.
+#[derive(Clone, Debug)]
+pub enum RoomBus {
+    /// The frames are only shared among the sockets of this instance.
+    InMemory,
+    /// The frames are also shared with the other instances through Redis.
+    Redis {
+        /// The ID of this instance, used to ignore the frames it published.
+        instance_id: Uuid,
+        /// The connection used to publish the frames.
+        con: MultiplexedConnection,
+    },
+}
+
+impl RoomBus {
+    /// Creates the room bus configured by the user env.
+    ///
+    /// When Redis is used, the frames published by the other instances are
+    /// relayed to the local rooms for as long as the server runs.
+    ///
+    /// # Arguments
+    ///
+    /// - con : The connection used to publish the frames.
+    /// - txs : The local rooms, to which the remote frames are relayed.
+    pub async fn from_env(
+        con: MultiplexedConnection,
+        txs: Arc<Mutex<WsRooms>>,
+    ) -> Result<Self, redis::RedisError> {
+        match std::env::var("ROOM_BUS").as_deref() {
+            Ok("redis") => {
+                let instance_id = Uuid::new_v4();
+                let mut pubsub = tchatchers_core::pool::get_pubsub().await?;
+                pubsub.psubscribe(format!("{ROOM_CHANNEL_PREFIX}*")).await?;
+                tokio::spawn(async move {
+                    let mut messages = pubsub.into_on_message();
+                    while let Some(msg) = messages.next().await {
+                        let Some(room) = msg.get_channel_name().strip_prefix(ROOM_CHANNEL_PREFIX)
+                        else {
+                            continue;
+                        };
+                        // Frames are prefixed by the ID of the instance that published them.
+                        let payload = msg.get_payload_bytes();
+                        if payload.len() < 16 || payload[..16] == *instance_id.as_bytes() {
+                            continue;
+                        }
+                        let msg = match BUS_WIRE_FORMAT.decode(&payload[16..]) {
+                            Ok(msg) => msg,
+                            Err(e) => {
+                                tracing::error!(
+                                    "Could not decode a frame of the room {room} : {e}"
+                                );
+                                continue;
+                            }
+                        };
+                        if let Some(ws_room) = txs.lock().await.get_mut(room) {
+                            ws_room.record(&msg);
+                            let _ = ws_room.tx.send(SharedMessage::new(msg.into()));
+                        }
+                    }
+                    tracing::error!("The room bus stopped receiving the frames of other instances");
+                });
+                let mut heartbeat_con = con.clone();
+                tokio::spawn(async move {
+                    let key = format!("{INSTANCE_KEY_PREFIX}{instance_id}");
+                    let mut interval = tokio::time::interval(INSTANCE_HEARTBEAT_INTERVAL);
+                    loop {
+                        interval.tick().await;
+                        if let Err(e) = heartbeat_con
+                            .set_ex::<_, _, ()>(&key, 1, INSTANCE_HEARTBEAT_TTL)
+                            .await
+                        {
+                            tracing::error!(
+                                "Could not refresh the heartbeat of the instance : {e}"
+                            );
+                        }
+                    }
+                });
+                Ok(Self::Redis { instance_id, con })
+            }
+            _ => Ok(Self::InMemory),
+        }
+    }
+
+    /// Broadcasts a message to the sockets of a room, on this instance and on
+    /// the other ones.
+    ///
+    /// # Arguments
+    ///
+    /// - room : The room name.
+    /// - tx : The channel of the room on this instance.
+    /// - msg : The message to broadcast.
+    pub async fn send(
+        &self,
+        room: &str,
+        tx: &broadcast::Sender<SharedMessage>,
+        msg: SharedMessage,
+    ) {
+        self.publish(room, &msg).await;
+        let _ = tx.send(msg);
+    }
+
+    /// Shares a message with the other instances, if any.
+    ///
+    /// # Arguments
+    ///
+    /// - room : The room name.
+    /// - msg : The message to share.
+    pub async fn publish(&self, room: &str, msg: &SharedMessage) {
+        if let Self::Redis { instance_id, con } = self {
+            let frame = match msg.encode(BUS_WIRE_FORMAT) {
+                Ok(frame) => frame,
+                Err(e) => {
+                    tracing::error!("Could not encode a frame of the room {room} : {e}");
+                    return;
+                }
+            };
+            let payload = [instance_id.as_bytes().as_slice(), frame].concat();
+            let mut con = con.clone();
+            if let Err(e) = con
+                .publish::<_, _, ()>(format!("{ROOM_CHANNEL_PREFIX}{room}"), payload)
+                .await
+            {
+                tracing::error!("Could not publish a frame of the room {room} : {e}");
+            }
+        }
+    }
+
+    /// Shares with the other instances that a user joined a room from this
+    /// instance.
+    ///
+    /// # Arguments
+    ///
+    /// - room : The room name.
+    /// - user : The user that joined the room.
+    pub async fn add_member(&self, room: &str, user: &PartialUser) {
+        if let Self::Redis { instance_id, con } = self {
+            let payload = match bincode::serialize(user) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    tracing::error!("Could not encode a member of the room {room} : {e}");
+                    return;
+                }
+            };
+            let mut con = con.clone();
+            if let Err(e) = con
+                .hset::<_, _, _, ()>(
+                    format!("{PRESENCE_KEY_PREFIX}{room}"),
+                    format!("{instance_id}:{}", user.id),
+                    payload,
+                )
+                .await
+            {
+                tracing::error!("Could not register a member of the room {room} : {e}");
+            }
+        }
+    }
+
+    /// Shares with the other instances that a user left a room from this
+    /// instance.
+    ///
+    /// # Arguments
+    ///
+    /// - room : The room name.
+    /// - user_id : The ID of the user that left the room.
+    pub async fn remove_member(&self, room: &str, user_id: i32) {
+        if let Self::Redis { instance_id, con } = self {
+            let mut con = con.clone();
+            if let Err(e) = con
+                .hdel::<_, _, ()>(
+                    format!("{PRESENCE_KEY_PREFIX}{room}"),
+                    format!("{instance_id}:{user_id}"),
+                )
+                .await
+            {
+                tracing::error!("Could not unregister a member of the room {room} : {e}");
+            }
+        }
+    }
+
+    /// Whether a user is connected to a room from another instance.
+    ///
+    /// # Arguments
+    ///
+    /// - room : The room name.
+    /// - user_id : The ID of the user.
+    pub async fn is_remote_member(&self, room: &str, user_id: i32) -> bool {
+        self.members(room, vec![])
+            .await
+            .iter()
+            .any(|member| member.id == user_id)
+    }
+
+    /// Returns the users connected to a room, on this instance and on the
+    /// other ones.
+    ///
+    /// Only the local members are returned if the other instances can't be
+    /// reached.
+    ///
+    /// # Arguments
+    ///
+    /// - room : The room name.
+    /// - local_members : The users connected to the room on this instance.
+    pub async fn members(&self, room: &str, local_members: Vec<PartialUser>) -> Vec<PartialUser> {
+        let Self::Redis { instance_id, con } = self else {
+            return local_members;
+        };
+        let key = format!("{PRESENCE_KEY_PREFIX}{room}");
+        let mut con = con.clone();
+        let entries: HashMap<String, Vec<u8>> = match con.hgetall(&key).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::error!("Could not retrieve the members of the room {room} : {e}");
+                return local_members;
+            }
+        };
+        let this_instance = instance_id.to_string();
+        let mut live_instances: HashMap<String, bool> = HashMap::new();
+        let mut stale_fields: Vec<String> = vec![];
+        let mut seen: HashSet<i32> = local_members.iter().map(|user| user.id).collect();
+        let mut members = local_members;
+        for (field, payload) in entries {
+            let Some((instance, _)) = field.split_once(':') else {
+                continue;
+            };
+            // The members of this instance are known locally.
+            if instance == this_instance {
+                continue;
+            }
+            let is_live = match live_instances.get(instance) {
+                Some(is_live) => *is_live,
+                None => {
+                    let is_live = con
+                        .exists(format!("{INSTANCE_KEY_PREFIX}{instance}"))
+                        .await
+                        .unwrap_or(true);
+                    live_instances.insert(instance.to_string(), is_live);
+                    is_live
+                }
+            };
+            if !is_live {
+                stale_fields.push(field);
+                continue;
+            }
+            match bincode::deserialize::<PartialUser>(&payload) {
+                Ok(user) if seen.insert(user.id) => members.push(user),
+                Ok(_) => {}
+                Err(e) => tracing::error!("Could not decode a member of the room {room} : {e}"),
+            }
+        }
+        if !stale_fields.is_empty() {
+            if let Err(e) = con.hdel::<_, _, ()>(&key, stale_fields).await {
+                tracing::error!("Could not clean up the members of the room {room} : {e}");
+            }
+        }
+        members
+    }
+}
@@ -4,35 +4,206 @@
 //! The websocket is used to communicate between users within rooms.
 //!
 //! Websockets are isolated to each others, with one existing for each room.
+//!
+//! A room only lives in memory while sockets are connected to it, and is
+//! reaped once the last one leaves.
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     ops::{Deref, DerefMut},
+    sync::{Arc, OnceLock},
+    time::Duration,
 };
 
-use crate::AppState;
+use crate::{
+    connection_registry::Session,
+    rate_limit::{ConnectionLimiter, FrameKind, Verdict},
+    AppState, GIT_REV,
+};
 use axum::{
-    extract::{ws::Message, ws::WebSocket, Path, State, WebSocketUpgrade},
+    extract::{
+        ws::{CloseFrame, Message, WebSocket},
+        Path, Query, State, WebSocketUpgrade,
+    },
+    http::{header::SEC_WEBSOCKET_PROTOCOL, HeaderMap},
     response::IntoResponse,
 };
-use futures_util::{join, SinkExt, StreamExt};
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
 use tchatchers_core::{
     api_response::ApiGenericResponse,
     async_message::AsyncMessage,
     authorization_token::AuthorizationToken,
-    room::RoomNameValidator,
+    direct_message::{can_join, DirectConversation},
+    message_read::MessageRead,
+    message_revision::MessageEdition,
+    notification::{mentioned_logins, Notification},
+    profile::Profile,
+    reaction::{Reaction, ReactionUpdate},
+    room::{Room, RoomActivity, RoomNameValidator},
+    room_pin::RoomPin,
     serializable_token::SerializableToken,
-    ws_message::{WsMessage, WsMessageContent, WsReceptionStatus},
+    user::PartialUser,
+    ws_codec::{EncodedMessage, WireFormat, WsCodec},
+    ws_message::{
+        PostRejection, WsCapability, WsLimits, WsMessage, WsMessageContent, WsReceptionStatus,
+        MAX_PAGE_SIZE, WS_INCOMPATIBLE_VERSION_CLOSE_CODE, WS_MIN_PROTOCOL_VERSION,
+        WS_PROTOCOL_VERSION,
+    },
+    ws_ticket::WsTicket,
+};
+use tokio::{
+    sync::{
+        broadcast::{self, error::RecvError},
+        oneshot,
+    },
+    time::{sleep_until, timeout, Instant},
 };
-use tokio::sync::broadcast;
+use uuid::Uuid;
 use validator::Validate;
 
+/// Minimum delay between two typing notifications relayed for the same socket.
+const TYPING_RELAY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Delay after which a user that didn't notify that they are still typing is
+/// considered as having stopped.
+const TYPING_EXPIRY: Duration = Duration::from_secs(5);
+
+/// Delay given to a socket to receive its last messages once it is closed by
+/// the server.
+const CLOSE_FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The number of latest messages of a room kept in memory, so that they can
+/// be replayed to the reconnecting sockets before being persisted.
+const RECENT_MESSAGES_CAPACITY: usize = 100;
+
+/// A message broadcast to the sockets of a room.
+pub type SharedMessage = Arc<EncodedMessage>;
+
+/// The data shared by all the sockets of a room.
+#[derive(Debug)]
+pub struct WsRoom {
+    /// The channel used to broadcast messages to the room's sockets.
+    pub tx: broadcast::Sender<SharedMessage>,
+    /// The authenticated users connected to the room, with their number of
+    /// sockets, indexed by their ID.
+    members: HashMap<i32, (PartialUser, usize)>,
+    /// The number of sockets connected to the room, authenticated or not.
+    sockets: usize,
+    /// The latest messages of the room, from the oldest to the latest.
+    recent: VecDeque<WsMessageContent>,
+}
+
+impl WsRoom {
+    /// Creates a new room without any member.
+    ///
+    /// # Arguments
+    ///
+    /// - capacity : The number of messages a socket can lag behind before missing some.
+    fn new(capacity: usize) -> Self {
+        let (tx, _rx) = broadcast::channel(capacity);
+        Self {
+            tx,
+            members: HashMap::new(),
+            sockets: 0,
+            recent: VecDeque::with_capacity(RECENT_MESSAGES_CAPACITY),
+        }
+    }
+
+    /// Registers a socket of the user in the room.
+    ///
+    /// Returns whether it is the first socket of the user in the room.
+    fn join(&mut self, user: &PartialUser) -> bool {
+        let (_, sockets) = self
+            .members
+            .entry(user.id)
+            .or_insert_with(|| (user.clone(), 0));
+        *sockets += 1;
+        *sockets == 1
+    }
+
+    /// Unregisters a socket of the user from the room.
+    ///
+    /// Returns whether it was the last socket of the user in the room.
+    fn leave(&mut self, user_id: i32) -> bool {
+        match self.members.get_mut(&user_id) {
+            Some((_, sockets)) if *sockets > 1 => {
+                *sockets -= 1;
+                false
+            }
+            Some(_) => {
+                self.members.remove(&user_id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the users connected to the room.
+    pub fn members(&self) -> Vec<PartialUser> {
+        self.members
+            .values()
+            .map(|(user, _)| user.clone())
+            .collect()
+    }
+
+    /// Whether a message broadcast to the room changes its latest messages.
+    pub fn is_recorded(msg: &WsMessage) -> bool {
+        matches!(
+            msg,
+            WsMessage::Receive(_) | WsMessage::Delete(_) | WsMessage::MessageEdited { .. }
+        )
+    }
+
+    /// Keeps the latest messages of the room up to date with a message
+    /// broadcast to it.
+    ///
+    /// # Arguments
+    ///
+    /// - msg : The message broadcast.
+    pub fn record(&mut self, msg: &WsMessage) {
+        match msg {
+            WsMessage::Receive(message) => {
+                // Messages sent concurrently can be broadcast out of order.
+                let index = self
+                    .recent
+                    .partition_point(|recent| recent.cursor() < message.cursor());
+                self.recent.insert(index, message.clone());
+                if self.recent.len() > RECENT_MESSAGES_CAPACITY {
+                    self.recent.pop_front();
+                }
+            }
+            // Deleted messages are only kept as tombstones if replied to.
+            WsMessage::Delete(uuid) => {
+                if self.recent.iter().any(|m| m.reply_to == Some(*uuid)) {
+                    if let Some(message) = self.recent.iter_mut().find(|m| m.uuid == *uuid) {
+                        message.tombstone();
+                    }
+                } else {
+                    self.recent.retain(|m| m.uuid != *uuid);
+                }
+            }
+            WsMessage::MessageEdited {
+                uuid,
+                content,
+                edited_at,
+            } => {
+                if let Some(message) = self.recent.iter_mut().find(|m| m.uuid == *uuid) {
+                    message.content.clone_from(content);
+                    message.edited_at = Some(*edited_at);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 /// Hashmap that contains the room name as key and the websocket data as value.
 #[derive(Default, Debug)]
-pub struct WsRooms(HashMap<String, broadcast::Sender<Vec<u8>>>);
+pub struct WsRooms(HashMap<String, WsRoom>);
 
 impl Deref for WsRooms {
-    type Target = HashMap<String, broadcast::Sender<Vec<u8>>>;
+    type Target = HashMap<String, WsRoom>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -45,6 +216,137 @@ impl DerefMut for WsRooms {
     }
 }
 
+impl WsRooms {
+    /// Registers a new socket in a room, creating the room if needed.
+    ///
+    /// Returns the channel used to broadcast messages to the room.
+    ///
+    /// # Arguments
+    ///
+    /// - room : The room name.
+    /// - capacity : The capacity of the room's channel, if it has to be created.
+    fn connect(&mut self, room: &str, capacity: usize) -> broadcast::Sender<SharedMessage> {
+        let ws_room = self
+            .entry(room.to_string())
+            .or_insert_with(|| WsRoom::new(capacity));
+        ws_room.sockets += 1;
+        ws_room.tx.clone()
+    }
+
+    /// Unregisters a socket from a room, reaping the room once nobody is
+    /// connected nor subscribed to it anymore.
+    fn disconnect(&mut self, room: &str) {
+        if let Some(ws_room) = self.get_mut(room) {
+            ws_room.sockets = ws_room.sockets.saturating_sub(1);
+            if ws_room.sockets == 0 && ws_room.tx.receiver_count() == 0 {
+                self.remove(room);
+            }
+        }
+    }
+
+    /// Returns the activity of the live rooms, from the most to the least
+    /// crowded.
+    pub fn activity(&self) -> Vec<RoomActivity> {
+        let mut activity: Vec<RoomActivity> = self
+            .iter()
+            .map(|(name, ws_room)| RoomActivity {
+                name: name.clone(),
+                sockets: ws_room.sockets,
+                subscribers: ws_room.tx.receiver_count(),
+                members: ws_room.members.len(),
+            })
+            .collect();
+        activity.sort_by(|a, b| b.sockets.cmp(&a.sockets).then(a.name.cmp(&b.name)));
+        activity
+    }
+}
+
+/// The query parameters accepted on the websocket upgrade.
+#[derive(Debug, Deserialize)]
+pub struct WsParams {
+    /// A ticket issued by `/api/ws_ticket`, authenticating the socket once upgraded.
+    ticket: Option<Uuid>,
+}
+
+/// Whether a user can access a room.
+///
+/// Direct conversations can only be accessed by their two users, and private
+/// rooms by their owner and members.
+///
+/// # Arguments
+///
+/// - state : The data shared across threads.
+/// - room : The room name.
+/// - user_id : The ID of the user.
+pub async fn can_access(
+    state: &AppState,
+    room: &str,
+    user_id: i32,
+) -> Result<bool, ApiGenericResponse> {
+    Ok(can_join(room, user_id) && Room::can_access(room, user_id, &state.pg_pool).await?)
+}
+
+/// Takes the turn of a user to post in a room under slow mode.
+///
+/// Returns the number of seconds to wait before posting again if the user
+/// already posted during the slow mode delay. The turns being kept in Redis,
+/// they are shared by all the instances, and Redis errors don't prevent the
+/// message from being posted.
+///
+/// # Arguments
+///
+/// - state : The data shared across threads.
+/// - room : The room name.
+/// - user_id : The ID of the user.
+/// - slow_mode : The number of seconds to wait between two messages.
+async fn take_slow_mode_turn(
+    state: &AppState,
+    room: &str,
+    user_id: i32,
+    slow_mode: i32,
+) -> Option<u64> {
+    let key = format!("slow_mode:{room}:{user_id}");
+    let mut con = state.session_pool.clone();
+    let taken: Result<Option<String>, redis::RedisError> = redis::cmd("SET")
+        .arg(&key)
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(slow_mode)
+        .query_async(&mut con)
+        .await;
+    match taken {
+        Ok(Some(_)) => None,
+        Ok(None) => {
+            let ttl: i64 = redis::cmd("TTL")
+                .arg(&key)
+                .query_async(&mut con)
+                .await
+                .unwrap_or(1);
+            Some(ttl.max(1) as u64)
+        }
+        Err(e) => {
+            tracing::error!("Could not apply the slow mode of the room {room} : {e}");
+            None
+        }
+    }
+}
+
+/// Closes the sockets of a user connected to a room they can't access anymore,
+/// whatever the instance they are connected to.
+///
+/// # Arguments
+///
+/// - state : The data shared across threads.
+/// - room : The room name.
+/// - user_id : The ID of the user.
+pub async fn revoke_room_access(state: &AppState, room: &str, user_id: i32) {
+    state
+        .connections
+        .send(user_id, WsMessage::RoomAccessRevoked(room.to_string()))
+        .await;
+}
+
 /// The HTTP entry point.
 ///
 /// # Arguments
@@ -52,116 +354,827 @@ impl DerefMut for WsRooms {
 /// - ws : The 'Upgrade' header, mandatory.
 /// - state : The data shared across threads, used to retrieve existing rooms.
 /// - room : the room name.
-/// - jwt : The authenticated user infos.
+/// - params : The upgrade query parameters, holding the optional WS ticket.
+/// - headers : The upgrade headers, listing the codecs supported by the client.
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
     Path(room): Path<String>,
+    Query(params): Query<WsParams>,
+    headers: HeaderMap,
 ) -> Result<impl IntoResponse, ApiGenericResponse> {
-    let room_name_validator: RoomNameValidator = RoomNameValidator::from(room.clone());
-    if let Err(e) = room_name_validator.validate() {
-        return Err(ApiGenericResponse::from(e));
+    // Direct conversations aren't public rooms, their access is checked once authenticated.
+    if DirectConversation::from_room(&room).is_none() {
+        let room_name_validator: RoomNameValidator = RoomNameValidator::from(room.clone());
+        if let Err(e) = room_name_validator.validate() {
+            return Err(ApiGenericResponse::from(e));
+        }
+    }
+    {
+        let rooms = state.txs.lock().await;
+        if !rooms.contains_key(&room) && rooms.len() >= state.ws_max_rooms {
+            return Err(ApiGenericResponse::TooManyRooms);
+        }
+    }
+    let author = match params.ticket {
+        Some(ticket) => {
+            let mut redis_conn = state.session_pool.clone();
+            let Some(ticket) = WsTicket::consume(&ticket, &mut redis_conn).await? else {
+                return Err(ApiGenericResponse::AuthenticationExpired);
+            };
+            let Some(user) = PartialUser::find_by_id(ticket.user_id, &state.pg_pool).await? else {
+                return Err(ApiGenericResponse::AccountNotFound);
+            };
+            if !user.is_authorized {
+                return Err(ApiGenericResponse::AccessRevoked);
+            }
+            if !can_access(&state, &room, user.id).await? {
+                return Err(ApiGenericResponse::UnsifficentPriviledges);
+            }
+            Some((user, ticket.exp, ticket.token_family))
+        }
+        None => None,
+    };
+    // Sockets that don't negotiate any codec are using JSON.
+    let wire_format = headers
+        .get(SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|offered| offered.to_str().ok())
+        .and_then(WireFormat::negotiate);
+    let ws = ws.protocols(wire_format.map(|format| format.protocol()));
+    let wire_format = wire_format.unwrap_or_default();
+    Ok(ws.on_upgrade(move |socket| handle_socket(socket, state, room, author, wire_format)))
+}
+
+/// Sends a message to all the sockets subscribed to the given room.
+///
+/// # Arguments
+///
+/// - state : The data shared across threads, used to retrieve existing rooms.
+/// - room : The room name.
+/// - msg : The message to broadcast.
+pub async fn broadcast_to_room(
+    state: &AppState,
+    room: &str,
+    msg: &WsMessage,
+) -> Result<(), ApiGenericResponse> {
+    let tx = state.txs.lock().await.get_mut(room).map(|ws_room| {
+        ws_room.record(msg);
+        ws_room.tx.clone()
+    });
+    let msg = SharedMessage::new(msg.clone().into());
+    match tx {
+        Some(tx) => state.room_bus.send(room, &tx, msg).await,
+        // The room can still have sockets on other instances.
+        None => state.room_bus.publish(room, &msg).await,
+    }
+    Ok(())
+}
+
+/// Sends a message to all the sockets of the room the socket is connected to.
+///
+/// # Arguments
+///
+/// - state : The data shared across threads.
+/// - room : The room name.
+/// - shared_tx : The channel used to broadcast messages to the room.
+/// - msg : The message to broadcast.
+async fn send_to_room(
+    state: &AppState,
+    room: &str,
+    shared_tx: &broadcast::Sender<SharedMessage>,
+    msg: &WsMessage,
+) -> Result<(), ApiGenericResponse> {
+    if WsRoom::is_recorded(msg) {
+        if let Some(ws_room) = state.txs.lock().await.get_mut(room) {
+            ws_room.record(msg);
+        }
+    }
+    state
+        .room_bus
+        .send(room, shared_tx, SharedMessage::new(msg.clone().into()))
+        .await;
+    Ok(())
+}
+
+/// Registers the socket's user as a member of the room.
+///
+/// The socket is sent the room's members and metadata, if registered, while
+/// the other members are informed of the user's arrival if it is their first socket in
+/// the room, whatever the instance.
+///
+/// # Arguments
+///
+/// - state : The data shared across threads, used to retrieve existing rooms.
+/// - room : The room name.
+/// - user : The authenticated user of the socket.
+/// - member : The user registered for the socket, set once joined.
+/// - private_tx : The channel used to send messages to the socket only.
+async fn join_room(
+    state: &AppState,
+    room: &str,
+    user: &PartialUser,
+    member: &OnceLock<PartialUser>,
+    private_tx: &broadcast::Sender<WsMessage>,
+) -> Result<(), ApiGenericResponse> {
+    if member.set(user.clone()).is_err() {
+        return Ok(());
+    }
+    let mut rooms = state.txs.lock().await;
+    let ws_room = rooms
+        .entry(room.to_string())
+        .or_insert_with(|| WsRoom::new(state.ws_room_capacity));
+    if ws_room.join(user) {
+        // The users already connected from another instance aren't announced twice.
+        if !state.room_bus.is_remote_member(room, user.id).await {
+            let msg = SharedMessage::new(WsMessage::UserJoined(user.clone()).into());
+            state.room_bus.send(room, &ws_room.tx, msg).await;
+        }
+        state.room_bus.add_member(room, user).await;
+    }
+    let local_members = ws_room.members();
+    drop(rooms);
+    let members = state.room_bus.members(room, local_members).await;
+    let _ = private_tx.send(WsMessage::PresenceSnapshot(members));
+    // Joining a room doesn't register it, the rooms having to be created to be
+    // registered.
+    if DirectConversation::from_room(room).is_none() {
+        if let Some(room) = Room::find_by_name(room, &state.pg_pool).await? {
+            let _ = private_tx.send(WsMessage::RoomUpdated(room));
+        }
+    }
+    Ok(())
+}
+
+/// Unregisters the socket's user from the room's members.
+///
+/// The other members are informed of the user's departure if it was their
+/// last socket in the room, whatever the instance.
+///
+/// # Arguments
+///
+/// - state : The data shared across threads, used to retrieve existing rooms.
+/// - room : The room name.
+/// - user : The user registered for the socket.
+async fn leave_room(
+    state: &AppState,
+    room: &str,
+    user: &PartialUser,
+) -> Result<(), ApiGenericResponse> {
+    let mut rooms = state.txs.lock().await;
+    if let Some(ws_room) = rooms.get_mut(room) {
+        if ws_room.leave(user.id) {
+            state.room_bus.remove_member(room, user.id).await;
+            // The users still connected from another instance haven't left the room.
+            if !state.room_bus.is_remote_member(room, user.id).await {
+                let msg = SharedMessage::new(WsMessage::UserLeft(user.clone()).into());
+                state.room_bus.send(room, &ws_room.tx, msg).await;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Relays a reaction added or removed by the user to the room, and persists it.
+///
+/// Reactions that aren't valid or that target a message of another room are
+/// rejected.
+///
+/// # Arguments
+///
+/// - state : The data shared across threads.
+/// - room : The room name.
+/// - author : The authenticated user of the socket.
+/// - reaction : The reaction sent by the client.
+/// - is_added : Whether the reaction is added or removed.
+/// - shared_tx : The channel used to broadcast messages to the room.
+/// - private_tx : The channel used to send messages to the socket only.
+async fn update_reaction(
+    state: &AppState,
+    room: &str,
+    author: &PartialUser,
+    reaction: Reaction,
+    is_added: bool,
+    shared_tx: &broadcast::Sender<SharedMessage>,
+    private_tx: &broadcast::Sender<WsMessage>,
+) -> Result<(), ApiGenericResponse> {
+    let reaction = Reaction {
+        user_id: author.id,
+        ..reaction
+    };
+    // Messages that aren't persisted yet can still be reacted to, the reactions
+    // being persisted once the messages are.
+    if !reaction.is_valid()
+        || matches!(
+            WsMessageContent::get_one(&reaction.message_uuid, &state.pg_pool).await?,
+            Some(message) if message.room != room || message.is_deleted
+        )
+    {
+        let _ = private_tx.send(WsMessage::MessageRejected(reaction.message_uuid));
+        return Ok(());
+    }
+    let (msg, update) = if is_added {
+        (
+            WsMessage::React(reaction.clone()),
+            ReactionUpdate::Add(reaction),
+        )
+    } else {
+        (
+            WsMessage::Unreact(reaction.clone()),
+            ReactionUpdate::Remove(reaction),
+        )
+    };
+    send_to_room(state, room, shared_tx, &msg).await?;
+    let mut redis_conn = state.async_pool.clone();
+    tokio::spawn(async move {
+        AsyncMessage::PersistReaction(update)
+            .spawn(&mut redis_conn)
+            .await;
+    });
+    Ok(())
+}
+
+/// Pins or unpins a message of the room, and relays the change to the room.
+///
+/// Only the owner of the room and the moderators can pin messages, the
+/// messages having to be persisted before being pinned.
+///
+/// # Arguments
+///
+/// - state : The data shared across threads.
+/// - room : The room name.
+/// - author : The authenticated user of the socket.
+/// - message_uuid : The message to pin or unpin.
+/// - is_pinned : Whether the message is pinned or unpinned.
+/// - shared_tx : The channel used to broadcast messages to the room.
+/// - private_tx : The channel used to send messages to the socket only.
+async fn update_pin(
+    state: &AppState,
+    room: &str,
+    author: &PartialUser,
+    message_uuid: Uuid,
+    is_pinned: bool,
+    shared_tx: &broadcast::Sender<SharedMessage>,
+    private_tx: &broadcast::Sender<WsMessage>,
+) -> Result<(), ApiGenericResponse> {
+    let can_pin = author.profile >= Profile::Moderator
+        || Room::find_by_name(room, &state.pg_pool)
+            .await?
+            .is_some_and(|metadata| metadata.can_be_updated_by(author.id, author.profile));
+    let msg = match can_pin {
+        true if is_pinned => RoomPin::pin(room, &message_uuid, author.id, &state.pg_pool)
+            .await?
+            .map(WsMessage::Pinned),
+        true => RoomPin::unpin(room, &message_uuid, &state.pg_pool)
+            .await?
+            .then_some(WsMessage::Unpinned(message_uuid)),
+        false => None,
+    };
+    match msg {
+        Some(msg) => send_to_room(state, room, shared_tx, &msg).await?,
+        None => {
+            let _ = private_tx.send(WsMessage::MessageRejected(message_uuid));
+        }
+    }
+    Ok(())
+}
+
+/// Notifies the users mentioned in a message, and pushes the notifications to
+/// their sockets.
+///
+/// # Arguments
+///
+/// - state : The data shared across threads.
+/// - logins : The logins mentioned in the message.
+/// - message : The message sent.
+async fn notify_mentions(state: AppState, logins: Vec<String>, message: WsMessageContent) {
+    let notifications = match Notification::notify_mentions(
+        &logins,
+        &message.uuid,
+        &message.room,
+        &message.content,
+        message.author.id,
+        &state.pg_pool,
+    )
+    .await
+    {
+        Ok(notifications) => notifications,
+        Err(e) => {
+            tracing::error!("Could not notify the mentions of {} : {e}", message.uuid);
+            return;
+        }
+    };
+    for notification in notifications {
+        let user_id = notification.user_id;
+        state
+            .connections
+            .send(user_id, WsMessage::Mentioned(notification))
+            .await;
+    }
+}
+
+/// Checks that the message replied to, if any, can be replied to from the room.
+///
+/// Messages that aren't persisted yet can still be replied to.
+///
+/// # Arguments
+///
+/// - state : The data shared across threads.
+/// - room : The room name.
+/// - reply_to : The UUID of the message replied to.
+async fn is_valid_reply(
+    state: &AppState,
+    room: &str,
+    reply_to: Option<Uuid>,
+) -> Result<bool, ApiGenericResponse> {
+    let Some(reply_to) = reply_to else {
+        return Ok(true);
+    };
+    Ok(!matches!(
+        WsMessageContent::get_one(&reply_to, &state.pg_pool).await?,
+        Some(parent) if parent.room != room || parent.is_deleted
+    ))
+}
+
+/// Returns the messages of the room posted after the one a socket resumes
+/// from.
+///
+/// The persisted messages are completed by the latest ones of the room, which
+/// might not have been persisted yet.
+///
+/// # Arguments
+///
+/// - state : The data shared across threads.
+/// - room : The room name.
+/// - since_uuid : The UUID of the last message received by the socket.
+async fn resume(
+    state: &AppState,
+    room: &str,
+    since_uuid: &Uuid,
+) -> Result<WsMessage, ApiGenericResponse> {
+    let recent: Vec<WsMessageContent> = state
+        .txs
+        .lock()
+        .await
+        .get(room)
+        .map(|ws_room| ws_room.recent.iter().cloned().collect())
+        .unwrap_or_default();
+    let since = match recent.iter().find(|message| message.uuid == *since_uuid) {
+        Some(message) => Some(message.cursor()),
+        None => WsMessageContent::get_one(since_uuid, &state.pg_pool)
+            .await?
+            .filter(|message| message.room == room)
+            .map(|message| message.cursor()),
+    };
+    let Some(since) = since else {
+        return Ok(WsMessage::MessagesResumed {
+            messages: vec![],
+            is_complete: false,
+        });
+    };
+    let (mut messages, has_more) =
+        WsMessageContent::query_since_for_room(room, &since, MAX_PAGE_SIZE, &state.pg_pool).await?;
+    if !has_more {
+        let unpersisted: Vec<WsMessageContent> = recent
+            .into_iter()
+            .filter(|message| {
+                message.cursor() > since && !messages.iter().any(|m| m.uuid == message.uuid)
+            })
+            .collect();
+        messages.extend(unpersisted);
+        messages.sort_by_key(WsMessageContent::cursor);
+    }
+    Ok(WsMessage::MessagesResumed {
+        messages,
+        is_complete: !has_more,
+    })
+}
+
+/// Receives the next message of the room.
+///
+/// Waits forever if the socket hasn't subscribed to the room yet.
+async fn recv_shared(
+    shared_rx: &mut Option<broadcast::Receiver<SharedMessage>>,
+) -> Result<SharedMessage, RecvError> {
+    match shared_rx {
+        Some(shared_rx) => shared_rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Exchanges the protocol versions and the capabilities of the client and the
+/// server, the client being expected to start the connection with a
+/// [WsMessage::Hello].
+///
+/// Returns the capabilities of the client, or none if the connection has to be
+/// dropped, the clients speaking an unsupported version of the protocol being
+/// sent a close frame.
+///
+/// # Arguments
+///
+/// - socket : The struct used to communicate between the client and the server.
+/// - state : The data shared across threads.
+/// - wire_format : The codec negotiated for the socket.
+async fn handshake(
+    socket: &mut WebSocket,
+    state: &AppState,
+    wire_format: WireFormat,
+) -> Option<Vec<WsCapability>> {
+    let hello = loop {
+        match timeout(state.ws_authentication_timeout, socket.recv()).await {
+            Ok(Some(Ok(Message::Ping(_) | Message::Pong(_)))) => continue,
+            Ok(Some(Ok(Message::Binary(frame)))) => break wire_format.decode(&frame).ok(),
+            Ok(Some(Ok(Message::Text(_)))) => break None,
+            _ => return None,
+        }
+    };
+    let capabilities = match hello {
+        Some(WsMessage::Hello {
+            protocol_version,
+            capabilities,
+        }) if (WS_MIN_PROTOCOL_VERSION..=WS_PROTOCOL_VERSION).contains(&protocol_version) => {
+            capabilities
+        }
+        _ => {
+            let close_frame = CloseFrame {
+                code: WS_INCOMPATIBLE_VERSION_CLOSE_CODE,
+                reason: "Incompatible protocol version, please reload".into(),
+            };
+            let _ = socket.send(Message::Close(Some(close_frame))).await;
+            return None;
+        }
+    };
+    let send_limit = state.rate_limiter.limit(FrameKind::Send);
+    let welcome = WsMessage::Welcome {
+        server_version: GIT_REV.into(),
+        capabilities: WsCapability::ALL.to_vec(),
+        limits: WsLimits {
+            max_page_size: MAX_PAGE_SIZE,
+            authentication_timeout: state.ws_authentication_timeout.as_secs(),
+            send_burst: send_limit.burst(),
+            send_window: send_limit.window(),
+        },
+    };
+    let frame = wire_format.encode(&welcome).ok()?;
+    socket.send(Message::Binary(frame.into())).await.ok()?;
+    Some(capabilities)
+}
+
+/// Waits until the session of the socket ends.
+///
+/// Waits forever if the socket isn't authenticated yet.
+async fn session_ended(session: &Option<Session>) {
+    match session {
+        Some(session) => session.ended().await,
+        None => std::future::pending().await,
     }
-    Ok(ws.on_upgrade(|socket| handle_socket(socket, state, room)))
 }
 
 /// The socket handler
 ///
+/// The socket only receives its private messages until it is authenticated,
+/// and is then subscribed to the room's messages.
+///
 /// # Arguments
 ///
 /// - socket : The struct used to communicate between the client and the server.
 /// - state : The data shared across threads.
 /// - room : The room name.
-/// - user : The connected user's infos.
-async fn handle_socket(socket: WebSocket, state: AppState, room: String) {
-    let (mut sender, mut receiver) = socket.split();
-    let shared_tx = {
-        let mut rooms = state.txs.lock().await;
-        match rooms.get(&room) {
-            Some(v) => v.clone(),
-            None => {
-                let (tx, _rx) = broadcast::channel(1000);
-                rooms.insert(room.clone(), tx.clone());
-                tx
-            }
+/// - authenticated : The connected user's infos along with the expiracy time
+///   of their token and the token family of their session, if already
+///   authenticated by a ticket.
+/// - wire_format : The codec negotiated for the socket.
+async fn handle_socket(
+    mut socket: WebSocket,
+    state: AppState,
+    room: String,
+    authenticated: Option<(PartialUser, i64, Option<Uuid>)>,
+    wire_format: WireFormat,
+) {
+    let Some(capabilities) = handshake(&mut socket, &state, wire_format).await else {
+        return;
+    };
+    let (private_tx, mut private_rx) = broadcast::channel(16);
+    // The session of the socket ends once its user is kicked or its token expires.
+    let (mut author, mut session) = match authenticated {
+        Some((user, exp, token_family)) => {
+            let session =
+                state
+                    .connections
+                    .register(user.id, token_family, exp, private_tx.clone());
+            (Some(user), Some(session))
         }
+        None => (None, None),
     };
-    let mut shared_rx: broadcast::Receiver<Vec<u8>> = shared_tx.subscribe();
-    let (private_tx, mut private_rx) = broadcast::channel(2);
+    let (mut sender, mut receiver) = socket.split();
+    let shared_tx = state
+        .txs
+        .lock()
+        .await
+        .connect(&room, state.ws_room_capacity);
+    let (subscription_tx, mut subscription_rx) = oneshot::channel();
+    let mut subscription_tx = Some(subscription_tx);
+    let member: Arc<OnceLock<PartialUser>> = Arc::new(OnceLock::new());
 
+    if let Some(author) = &author {
+        if let Some(subscription_tx) = subscription_tx.take() {
+            let _ = subscription_tx.send(shared_tx.subscribe());
+        }
+        let _ = private_tx.send(WsMessage::AuthenticationValidated);
+        let _ = join_room(&state, &room, author, &member, &private_tx).await;
+    }
+
+    let revoked_room = room.clone();
     let mut send_task = tokio::spawn(async move {
-        while let Ok(msg) = tokio::select! {v = private_rx.recv() => v, v = shared_rx.recv() => v }
-        {
+        let mut shared_rx: Option<broadcast::Receiver<SharedMessage>> = None;
+        loop {
+            let mut is_revoked = false;
+            // The messages of the features the client doesn't support aren't sent.
+            let is_supported = |msg: &WsMessage| {
+                WsCapability::required_by(msg).is_none_or(|c| capabilities.contains(&c))
+            };
+            let frame = tokio::select! {
+                v = private_rx.recv() => {
+                    // Revoked accesses are sent to all the sockets of a user, whatever their room.
+                    if let Ok(WsMessage::RoomAccessRevoked(room)) = &v {
+                        if *room != revoked_room {
+                            continue;
+                        }
+                        is_revoked = true;
+                    }
+                    v.map(|msg| is_supported(&msg).then(|| wire_format.encode(&msg)))
+                }
+                // Messages of the room are encoded once for all the sockets sharing the codec.
+                v = recv_shared(&mut shared_rx) => v.map(|msg| {
+                    is_supported(msg.message())
+                        .then(|| msg.encode(wire_format).map(<[u8]>::to_vec))
+                }),
+                Ok(rx) = &mut subscription_rx, if shared_rx.is_none() => {
+                    shared_rx = Some(rx);
+                    continue;
+                }
+            };
+            let frame = match frame {
+                Ok(Some(frame)) => frame,
+                Ok(None) => continue,
+                // Slow sockets are asked to resync rather than being closed.
+                Err(RecvError::Lagged(missed)) => wire_format.encode(&WsMessage::Resync(missed)),
+                Err(RecvError::Closed) => break,
+            };
+            let frame = match frame {
+                Ok(frame) => frame,
+                Err(e) => {
+                    tracing::error!("Could not encode a message of the room : {e}");
+                    continue;
+                }
+            };
             // In any websocket error, break loop.
-            if sender.send(Message::Binary(msg.into())).await.is_err() {
+            if sender.send(Message::Binary(frame.into())).await.is_err() || is_revoked {
                 break;
             }
         }
     });
 
+    // Kept to unregister the user from the room's members once the socket is closed.
+    let (room_state, room_name, recv_member) = (state.clone(), room.clone(), member.clone());
+
     // This task will receive messages from client and send them to broadcast subscribers.
     let mut recv_task: tokio::task::JoinHandle<Result<(), ApiGenericResponse>> =
         tokio::spawn(async move {
-            let mut is_authenticated = false;
-            while let Some(Ok(Message::Binary(text))) = receiver.next().await {
-                if let Ok(msg) = serde_json::from_slice(&text) {
+            // Sockets that don't authenticate within the grace period are closed.
+            let authentication_deadline = Instant::now() + state.ws_authentication_timeout;
+            // Typing notifications are only relayed once in a while, and expire if not renewed.
+            let mut last_typing_relay: Option<Instant> = None;
+            let mut typing_deadline: Option<Instant> = None;
+            let mut limiter = ConnectionLimiter::new(state.rate_limiter.clone());
+            loop {
+                let typing_expiry = typing_deadline.unwrap_or(authentication_deadline);
+                let next = tokio::select! {
+                    next = receiver.next() => next,
+                    _ = sleep_until(authentication_deadline), if author.is_none() => {
+                        let _ = private_tx
+                            .send(WsMessage::AuthenticationRequired);
+                        break;
+                    }
+                    _ = session_ended(&session) => {
+                        let _ = private_tx.send(WsMessage::AuthenticationExpired);
+                        break;
+                    }
+                    _ = sleep_until(typing_expiry), if typing_deadline.is_some() => {
+                        typing_deadline = None;
+                        last_typing_relay = None;
+                        if let Some(author) = &author {
+                            let msg = WsMessage::StoppedTyping(author.clone());
+                            send_to_room(&state, &room, &shared_tx, &msg).await?;
+                        }
+                        continue;
+                    }
+                };
+                let Some(Ok(Message::Binary(text))) = next else {
+                    break;
+                };
+                if let Ok(msg) = wire_format.decode(&text) {
+                    if let Some(kind) = FrameKind::of(&msg) {
+                        match limiter.check(author.as_ref().map(|a| a.id), kind).await {
+                            Verdict::Allowed => {}
+                            Verdict::Limited { retry_after } => {
+                                let _ = private_tx.send(WsMessage::RateLimited { retry_after });
+                                continue;
+                            }
+                            Verdict::Disconnect { retry_after } => {
+                                let _ = private_tx.send(WsMessage::RateLimited { retry_after });
+                                break;
+                            }
+                        }
+                    }
+                    // Members can be removed from private rooms anytime.
+                    let uses_room = matches!(
+                        msg,
+                        WsMessage::Send(_)
+                            | WsMessage::RetrieveMessages(_)
+                            | WsMessage::Resume { .. }
+                            | WsMessage::RetrieveThread(_)
+                            | WsMessage::RetrieveMessagesBefore { .. }
+                            | WsMessage::Seen(_)
+                            | WsMessage::Delete(_)
+                            | WsMessage::Edit { .. }
+                            | WsMessage::React(_)
+                            | WsMessage::Unreact(_)
+                            | WsMessage::Pin(_)
+                            | WsMessage::Unpin(_)
+                    );
+                    if let Some(author) = author.as_ref().filter(|_| uses_room) {
+                        if !can_access(&state, &room, author.id).await? {
+                            let _ = private_tx.send(WsMessage::RoomAccessRevoked(room.clone()));
+                            break;
+                        }
+                    }
                     match msg {
                         WsMessage::Close => break,
                         WsMessage::Ping => {
-                            let _ = private_tx.send(serde_json::to_vec(&WsMessage::Pong)?);
+                            let _ = private_tx.send(WsMessage::Pong);
                         }
                         WsMessage::Pong | WsMessage::ClientKeepAlive => continue,
-                        WsMessage::Send(mut ws_message) => {
-                            if !is_authenticated {
-                                let _ = private_tx
-                                    .send(serde_json::to_vec(&WsMessage::AuthenticationRequired)?);
+                        WsMessage::Send(ws_message) => {
+                            let Some(author) = &author else {
+                                let _ = private_tx.send(WsMessage::AuthenticationRequired);
                                 break;
+                            };
+                            if ws_message.author.id != author.id
+                                || ws_message.room != room
+                                || !is_valid_reply(&state, &room, ws_message.reply_to).await?
+                            {
+                                let _ =
+                                    private_tx.send(WsMessage::MessageRejected(ws_message.uuid));
+                                continue;
                             }
-                            ws_message.reception_status = WsReceptionStatus::Sent;
-                            let redis_conn = state.async_pool.clone();
-                            let _ = shared_tx
-                                .send(serde_json::to_vec(&WsMessage::Receive(ws_message.clone()))?);
+                            let rejection = match Room::find_by_name(&room, &state.pg_pool).await? {
+                                Some(metadata) if !metadata.can_post(author.profile) => {
+                                    Some(PostRejection::AnnouncementOnly)
+                                }
+                                Some(metadata) => match metadata.slow_mode_for(author.profile) {
+                                    Some(slow_mode) => {
+                                        take_slow_mode_turn(&state, &room, author.id, slow_mode)
+                                            .await
+                                            .map(|retry_after| PostRejection::SlowMode {
+                                                retry_after,
+                                            })
+                                    }
+                                    None => None,
+                                },
+                                None => None,
+                            };
+                            if let Some(reason) = rejection {
+                                let _ = private_tx.send(WsMessage::PostRejected {
+                                    uuid: ws_message.uuid,
+                                    reason,
+                                });
+                                continue;
+                            }
+                            if typing_deadline.take().is_some() {
+                                last_typing_relay = None;
+                                send_to_room(
+                                    &state,
+                                    &room,
+                                    &shared_tx,
+                                    &WsMessage::StoppedTyping(author.clone()),
+                                )
+                                .await?;
+                            }
+                            // Only the content is trusted from the client, the rest is
+                            // filled server side.
+                            let ws_message = WsMessageContent {
+                                content: ws_message.content,
+                                reply_to: ws_message.reply_to,
+                                author: author.clone(),
+                                room: room.clone(),
+                                reception_status: WsReceptionStatus::Sent,
+                                ..WsMessageContent::default()
+                            };
+                            send_to_room(
+                                &state,
+                                &room,
+                                &shared_tx,
+                                &WsMessage::Receive(ws_message.clone()),
+                            )
+                            .await?;
+                            // Mentions in direct conversations would disclose them.
+                            let logins = match DirectConversation::from_room(&room) {
+                                Some(_) => vec![],
+                                None => mentioned_logins(&ws_message.content),
+                            };
+                            if !logins.is_empty() {
+                                tokio::spawn(notify_mentions(
+                                    state.clone(),
+                                    logins,
+                                    ws_message.clone(),
+                                ));
+                            }
+                            let mut redis_conn = state.async_pool.clone();
                             tokio::spawn(async move {
-                                let (mut pool1, mut pool2) =
-                                    (redis_conn.clone(), redis_conn.clone());
-                                join!(
-                                    AsyncMessage::CleanRoom(ws_message.clone().room)
-                                        .spawn(&mut pool1),
-                                    AsyncMessage::PersistMessage(ws_message).spawn(&mut pool2)
-                                );
-                                anyhow::Ok(())
+                                AsyncMessage::PersistMessage(ws_message)
+                                    .spawn(&mut redis_conn)
+                                    .await;
                             });
                         }
                         WsMessage::RetrieveMessages(session_id) => {
-                            if !is_authenticated {
-                                let _ = private_tx
-                                    .send(serde_json::to_vec(&WsMessage::AuthenticationRequired)?);
+                            let Some(author) = &author else {
+                                let _ = private_tx.send(WsMessage::AuthenticationRequired);
                                 break;
-                            }
+                            };
                             let messages: Vec<WsMessageContent> =
                                 WsMessageContent::query_all_for_room(&room, &state.pg_pool).await?;
-                            let _ = private_tx.send(serde_json::to_vec(
-                                &WsMessage::MessagesRetrieved {
+                            let pins = RoomPin::get_for_room(&room, &state.pg_pool).await?;
+                            let last_read_at =
+                                MessageRead::get_read_position(author.id, &room, &state.pg_pool)
+                                    .await?;
+                            let _ = private_tx.send(WsMessage::MessagesRetrieved {
+                                messages,
+                                session_id,
+                                pins,
+                                last_read_at,
+                            });
+                        }
+                        WsMessage::Resume { since_uuid } => {
+                            if author.is_none() {
+                                let _ = private_tx.send(WsMessage::AuthenticationRequired);
+                                break;
+                            }
+                            let msg = resume(&state, &room, &since_uuid).await?;
+                            let _ = private_tx.send(msg);
+                        }
+                        WsMessage::RetrieveThread(uuid) => {
+                            if author.is_none() {
+                                let _ = private_tx.send(WsMessage::AuthenticationRequired);
+                                break;
+                            }
+                            let messages =
+                                WsMessageContent::query_thread(&uuid, &room, &state.pg_pool)
+                                    .await?;
+                            let msg = match messages.is_empty() {
+                                true => WsMessage::MessageRejected(uuid),
+                                false => WsMessage::ThreadRetrieved {
+                                    root: uuid,
                                     messages,
-                                    session_id,
                                 },
-                            )?);
+                            };
+                            let _ = private_tx.send(msg);
                         }
-                        WsMessage::Seen(messages) => {
-                            if !is_authenticated {
-                                let _ = private_tx
-                                    .send(serde_json::to_vec(&WsMessage::AuthenticationRequired)?);
+                        WsMessage::RetrieveMessagesBefore { cursor, limit } => {
+                            if author.is_none() {
+                                let _ = private_tx.send(WsMessage::AuthenticationRequired);
                                 break;
                             }
-                            let _ = shared_tx.send(serde_json::to_vec(&WsMessage::MessagesSeen(
-                                messages.clone(),
-                            ))?);
+                            let page = WsMessageContent::query_page_for_room(
+                                &room,
+                                Some(&cursor),
+                                limit,
+                                &state.pg_pool,
+                            )
+                            .await?;
+                            let _ = private_tx.send(WsMessage::MessagesBeforeRetrieved(page));
+                        }
+                        WsMessage::Seen(messages) => {
+                            let Some(author) = &author else {
+                                let _ = private_tx.send(WsMessage::AuthenticationRequired);
+                                break;
+                            };
+                            send_to_room(
+                                &state,
+                                &room,
+                                &shared_tx,
+                                &WsMessage::MessagesSeen {
+                                    reader: author.clone(),
+                                    messages: messages.clone(),
+                                },
+                            )
+                            .await?;
                             let redis_pool = state.async_pool.clone();
                             for message in messages.into_iter() {
                                 let mut redis_pool = redis_pool.clone();
+                                let read = MessageRead::new(message, author.id);
                                 std::mem::drop(tokio::task::spawn(async move {
                                     AsyncMessage::spawn(
-                                        AsyncMessage::MessageSeen(message),
+                                        AsyncMessage::MessageSeen(read),
                                         &mut redis_pool,
                                     )
                                     .await;
@@ -169,27 +1182,240 @@ async fn handle_socket(socket: WebSocket, state: AppState, room: String) {
                                 }));
                             }
                         }
+                        WsMessage::Delete(message_id) => {
+                            let Some(author) = &author else {
+                                let _ = private_tx.send(WsMessage::AuthenticationRequired);
+                                break;
+                            };
+                            match WsMessageContent::get_one(&message_id, &state.pg_pool).await? {
+                                Some(message)
+                                    if message.room == room
+                                        && message.can_be_deleted_by(author.id, author.profile) =>
+                                {
+                                    send_to_room(
+                                        &state,
+                                        &room,
+                                        &shared_tx,
+                                        &WsMessage::Delete(message_id),
+                                    )
+                                    .await?;
+                                    let mut redis_conn = state.async_pool.clone();
+                                    tokio::spawn(async move {
+                                        AsyncMessage::DeleteMessage(message_id)
+                                            .spawn(&mut redis_conn)
+                                            .await;
+                                    });
+                                }
+                                _ => {
+                                    let _ = private_tx.send(WsMessage::MessageRejected(message_id));
+                                }
+                            }
+                        }
+                        WsMessage::Edit { uuid, content } => {
+                            let Some(author) = &author else {
+                                let _ = private_tx.send(WsMessage::AuthenticationRequired);
+                                break;
+                            };
+                            match WsMessageContent::get_one(&uuid, &state.pg_pool).await? {
+                                Some(message)
+                                    if message.room == room
+                                        && message.author.id == author.id
+                                        && !message.is_deleted
+                                        && !content.trim().is_empty() =>
+                                {
+                                    let edition = MessageEdition::new(uuid, content);
+                                    send_to_room(
+                                        &state,
+                                        &room,
+                                        &shared_tx,
+                                        &WsMessage::MessageEdited {
+                                            uuid,
+                                            content: edition.content.clone(),
+                                            edited_at: edition.edited_at,
+                                        },
+                                    )
+                                    .await?;
+                                    let mut redis_conn = state.async_pool.clone();
+                                    tokio::spawn(async move {
+                                        AsyncMessage::EditMessage(edition)
+                                            .spawn(&mut redis_conn)
+                                            .await;
+                                    });
+                                }
+                                _ => {
+                                    let _ = private_tx.send(WsMessage::MessageRejected(uuid));
+                                }
+                            }
+                        }
+                        WsMessage::Typing(_) => {
+                            let Some(author) = &author else {
+                                let _ = private_tx.send(WsMessage::AuthenticationRequired);
+                                break;
+                            };
+                            let now = Instant::now();
+                            typing_deadline = Some(now + TYPING_EXPIRY);
+                            if last_typing_relay
+                                .is_none_or(|last_relay| now >= last_relay + TYPING_RELAY_INTERVAL)
+                            {
+                                last_typing_relay = Some(now);
+                                send_to_room(
+                                    &state,
+                                    &room,
+                                    &shared_tx,
+                                    &WsMessage::Typing(author.clone()),
+                                )
+                                .await?;
+                            }
+                        }
+                        WsMessage::StoppedTyping(_) => {
+                            let Some(author) = &author else {
+                                let _ = private_tx.send(WsMessage::AuthenticationRequired);
+                                break;
+                            };
+                            if typing_deadline.take().is_some() {
+                                last_typing_relay = None;
+                                send_to_room(
+                                    &state,
+                                    &room,
+                                    &shared_tx,
+                                    &WsMessage::StoppedTyping(author.clone()),
+                                )
+                                .await?;
+                            }
+                        }
+                        WsMessage::React(reaction) => {
+                            let Some(author) = &author else {
+                                let _ = private_tx.send(WsMessage::AuthenticationRequired);
+                                break;
+                            };
+                            update_reaction(
+                                &state,
+                                &room,
+                                author,
+                                reaction,
+                                true,
+                                &shared_tx,
+                                &private_tx,
+                            )
+                            .await?;
+                        }
+                        WsMessage::Unreact(reaction) => {
+                            let Some(author) = &author else {
+                                let _ = private_tx.send(WsMessage::AuthenticationRequired);
+                                break;
+                            };
+                            update_reaction(
+                                &state,
+                                &room,
+                                author,
+                                reaction,
+                                false,
+                                &shared_tx,
+                                &private_tx,
+                            )
+                            .await?;
+                        }
+                        WsMessage::Pin(message_uuid) => {
+                            let Some(author) = &author else {
+                                let _ = private_tx.send(WsMessage::AuthenticationRequired);
+                                break;
+                            };
+                            update_pin(
+                                &state,
+                                &room,
+                                author,
+                                message_uuid,
+                                true,
+                                &shared_tx,
+                                &private_tx,
+                            )
+                            .await?;
+                        }
+                        WsMessage::Unpin(message_uuid) => {
+                            let Some(author) = &author else {
+                                let _ = private_tx.send(WsMessage::AuthenticationRequired);
+                                break;
+                            };
+                            update_pin(
+                                &state,
+                                &room,
+                                author,
+                                message_uuid,
+                                false,
+                                &shared_tx,
+                                &private_tx,
+                            )
+                            .await?;
+                        }
                         WsMessage::Authenticate(bearer) => {
-                            if let Ok(_token) =
-                                AuthorizationToken::decode(&bearer, &state.jwt_secret)
+                            let user = match AuthorizationToken::decode(&bearer, &state.jwt_secret)
                             {
-                                is_authenticated = true;
-                                let _ = private_tx
-                                    .send(serde_json::to_vec(&WsMessage::AuthenticationValidated)?);
+                                Ok(token) => PartialUser::find_by_id(token.user_id, &state.pg_pool)
+                                    .await?
+                                    .map(|user| (user, token.exp, token.token_family)),
+                                Err(_) => None,
+                            };
+                            let user = match user {
+                                Some((user, exp, token_family)) if user.is_authorized => {
+                                    can_access(&state, &room, user.id).await?.then_some((
+                                        user,
+                                        exp,
+                                        token_family,
+                                    ))
+                                }
+                                _ => None,
+                            };
+                            if let Some((user, exp, token_family)) = user {
+                                if let Some(subscription_tx) = subscription_tx.take() {
+                                    let _ = subscription_tx.send(shared_tx.subscribe());
+                                }
+                                let _ = private_tx.send(WsMessage::AuthenticationValidated);
+                                join_room(&state, &room, &user, &recv_member, &private_tx).await?;
+                                session = Some(state.connections.register(
+                                    user.id,
+                                    token_family,
+                                    exp,
+                                    private_tx.clone(),
+                                ));
+                                author = Some(user);
                             } else {
-                                let _ = private_tx
-                                    .send(serde_json::to_vec(&WsMessage::AuthenticationExpired)?);
+                                let _ = private_tx.send(WsMessage::AuthenticationExpired);
                             }
                         }
                         _ => {}
                     }
                 }
             }
+            // A socket leaving while its user was typing shouldn't leave a stale indicator.
+            if let (Some(author), Some(_)) = (&author, typing_deadline) {
+                send_to_room(
+                    &state,
+                    &room,
+                    &shared_tx,
+                    &WsMessage::StoppedTyping(author.clone()),
+                )
+                .await?;
+            }
             Ok(())
         });
 
+    // The aborted task is awaited so that its room subscription is dropped.
     tokio::select! {
-        _ = (&mut send_task) => recv_task.abort(),
-        _ = (&mut recv_task) => send_task.abort(),
+        _ = (&mut send_task) => {
+            recv_task.abort();
+            let _ = recv_task.await;
+        }
+        _ = (&mut recv_task) => {
+            // The last messages sent to the socket, such as the reason it is
+            // closed, are flushed before closing it.
+            let _ = timeout(CLOSE_FLUSH_TIMEOUT, &mut send_task).await;
+            send_task.abort();
+            let _ = send_task.await;
+        }
     };
+
+    if let Some(user) = member.get() {
+        let _ = leave_room(&room_state, &room_name, user).await;
+    }
+    room_state.txs.lock().await.disconnect(&room_name);
 }
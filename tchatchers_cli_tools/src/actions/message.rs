@@ -1,7 +1,7 @@
 use sqlx::types::Uuid;
-use tchatchers_core::ws_message::WsMessageContent;
+use tchatchers_core::{message_revision::MessageRevision, ws_message::WsMessageContent};
 
-use crate::errors::CliError;
+use crate::errors::{CliError, ErrorKind};
 
 pub struct MessageAction;
 
@@ -20,4 +20,35 @@ impl MessageAction {
         WsMessageContent::delete_messages(&messages_uuid, &pool).await?;
         Ok(())
     }
+
+    /// Prints the edit history of a message, from its oldest version to its current one.
+    ///
+    /// # Arguments
+    ///
+    /// * `message_uuid` - The UUID of the message.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), CliError>` - Returns `Ok(())` if the operation was successful, otherwise returns an error of type `CliError`.
+    pub async fn history(message_uuid: Uuid) -> Result<(), CliError> {
+        let pool = tchatchers_core::pool::get_pg_pool().await?;
+        let Some(message) = WsMessageContent::get_one(&message_uuid, &pool).await? else {
+            return Err(CliError::new(
+                format!("No message found with UUID {message_uuid}"),
+                ErrorKind::CliInputError,
+            ));
+        };
+        let revisions = MessageRevision::get_for_message(&message_uuid, &pool).await?;
+        println!("History of message {message_uuid}\n---\n");
+        revisions
+            .iter()
+            .enumerate()
+            .for_each(|(i, r)| println!("- #{} ({}) : {}\n", i + 1, r.revised_at, r.content));
+        println!(
+            "- Current ({}) : {}",
+            message.edited_at.unwrap_or(message.timestamp),
+            message.content
+        );
+        Ok(())
+    }
 }
@@ -1,5 +1,8 @@
 use tchatchers_core::{
-    async_message::{processor::process, AsyncQueue, QueueReport},
+    async_message::{
+        processor::{process, run_scheduled},
+        AsyncQueue, QueueReport,
+    },
     pool,
 };
 use tokio::task::JoinSet;
@@ -126,7 +129,17 @@ impl QueueArgAction {
     /// # Arguments
     ///
     /// - `queue`: An `AsyncQueue` representing the queue to process events from.
+    ///   The scheduled queues are run directly instead.
     pub async fn process(queue: AsyncQueue) -> Result<(), CliError> {
+        if queue.is_scheduled() {
+            let (mut redis_pool, pg_pool) =
+                (pool::get_async_pool().await?, pool::get_pg_pool().await?);
+            if let Err(err) = run_scheduled(queue, &pg_pool, &mut redis_pool).await {
+                return Err(CliError::new(err.to_string(), ErrorKind::GenericError));
+            }
+            println!("[{queue}] Scheduled process run with success");
+            return Ok(());
+        }
         let (mut redis_pool, pg_pool) = (pool::get_async_pool().await?, pool::get_pg_pool().await?);
         if let Some(events) = queue.read_events_with_timeout(&mut redis_pool).await? {
             let events_number = events.len();
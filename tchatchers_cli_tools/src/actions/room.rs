@@ -1,5 +1,16 @@
-use crate::errors::CliError;
-use tchatchers_core::ws_message::{WsMessageContent, WsMessageStats};
+use crate::{
+    common::user::UserIdentifier,
+    errors::{CliError, ErrorKind},
+};
+use tchatchers_core::{
+    room::{InsertableRoom, Room},
+    room_member::RoomMember,
+    room_retention::RetentionPolicy,
+    user::PartialUser,
+    validation_error_message::ValidationErrorMessage,
+    ws_message::{WsMessageContent, WsMessageStats},
+};
+use validator::Validate;
 
 /// Struct for performing actions related to chat rooms.
 pub struct RoomAction;
@@ -89,4 +100,290 @@ impl RoomAction {
             .for_each(|(i, m)| println!("- #{} : {m:#?}\n", i + 1));
         Ok(())
     }
+
+    /// Creates a room without any owner.
+    ///
+    /// # Arguments
+    ///
+    /// * `room` - The room to create.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CliError` if the room isn't valid, if it already exists, or if the operation fails.
+    pub async fn create(room: InsertableRoom) -> Result<(), CliError> {
+        if let Err(e) = room.validate() {
+            return Err(CliError::new(
+                ValidationErrorMessage::from(e).to_string(),
+                ErrorKind::CliInputError,
+            ));
+        }
+        let pool = tchatchers_core::pool::get_pg_pool().await?;
+        if Room::find_by_name(&room.name, &pool).await?.is_some() {
+            return Err(CliError::new(
+                format!("The room '{}' already exists", room.name),
+                ErrorKind::CliInputError,
+            ));
+        }
+        let room = room.insert(None, &pool).await?;
+        println!("Room '{}' created", room.name);
+        Ok(())
+    }
+
+    /// Prints the metadata of a room.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_name` - The name of the room.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CliError` if the room doesn't exist or if the operation fails.
+    pub async fn info(room_name: &str) -> Result<(), CliError> {
+        let pool = tchatchers_core::pool::get_pg_pool().await?;
+        let room = Self::find_room(room_name, &pool).await?;
+        println!("{room:#?}");
+        Ok(())
+    }
+
+    /// Sets the topic of a room.
+    ///
+    /// The members currently connected to the room will only see the new topic once they join it again.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_name` - The name of the room.
+    /// * `topic` - The new topic, none clearing it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CliError` if the room doesn't exist or if the operation fails.
+    pub async fn set_topic(room_name: &str, topic: Option<&str>) -> Result<(), CliError> {
+        let pool = tchatchers_core::pool::get_pg_pool().await?;
+        let Some(room) = Room::set_topic(room_name, topic, &pool).await? else {
+            return Err(CliError::new(
+                format!("No room found with name '{room_name}'"),
+                ErrorKind::CliInputError,
+            ));
+        };
+        match room.topic {
+            Some(topic) => println!("Topic of room '{}' set to '{topic}'", room.name),
+            None => println!("Topic of room '{}' cleared", room.name),
+        }
+        Ok(())
+    }
+
+    /// Prints the members of a room, along with the users invited.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_name` - The name of the room.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CliError` if the room doesn't exist or if the operation fails.
+    pub async fn list_members(room_name: &str) -> Result<(), CliError> {
+        let pool = tchatchers_core::pool::get_pg_pool().await?;
+        let room = Self::find_room(room_name, &pool).await?;
+        let members = RoomMember::get_for_room(&room.name, &pool).await?;
+        println!("Members of room '{}'\n---\n", room.name);
+        if let Some(owner_id) = room.owner_id {
+            println!("- Owner : #{owner_id}\n");
+        }
+        members.iter().for_each(|m| match m.joined_at {
+            Some(joined_at) => println!(
+                "- {} (#{}) : joined on {joined_at}",
+                m.user.login, m.user.id
+            ),
+            None => println!(
+                "- {} (#{}) : invited on {}",
+                m.user.login, m.user.id, m.invited_at
+            ),
+        });
+        Ok(())
+    }
+
+    /// Invites a user to become a member of a room.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_name` - The name of the room.
+    /// * `user_identifier` - The user invited.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CliError` if the room or the user doesn't exist, or if the operation fails.
+    pub async fn invite_member(
+        room_name: &str,
+        user_identifier: UserIdentifier,
+    ) -> Result<(), CliError> {
+        let pool = tchatchers_core::pool::get_pg_pool().await?;
+        let room = Self::find_room(room_name, &pool).await?;
+        let user = Self::find_user(user_identifier, &pool).await?;
+        if room.owner_id == Some(user.id)
+            || !RoomMember::invite(&room.name, user.id, None, &pool).await?
+        {
+            eprintln!("The user is already a member of the room or invited to it. Nothing has been updated in consequence.");
+        } else {
+            println!("User '{}' invited to room '{}'", user.login, room.name);
+        }
+        Ok(())
+    }
+
+    /// Removes a member from a room, or cancels their invitation.
+    ///
+    /// The sockets of the user connected to the room are only closed once they try to use it.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_name` - The name of the room.
+    /// * `user_identifier` - The user removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CliError` if the user doesn't exist, or if the operation fails.
+    pub async fn remove_member(
+        room_name: &str,
+        user_identifier: UserIdentifier,
+    ) -> Result<(), CliError> {
+        let pool = tchatchers_core::pool::get_pg_pool().await?;
+        let user = Self::find_user(user_identifier, &pool).await?;
+        if RoomMember::remove(room_name, user.id, &pool).await? {
+            println!("User '{}' removed from room '{room_name}'", user.login);
+        } else {
+            eprintln!(
+                "The user wasn't a member of the room. Nothing has been updated in consequence."
+            );
+        }
+        Ok(())
+    }
+
+    /// Transfers the ownership of a room to a user, the former owner staying a member of it.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_name` - The name of the room.
+    /// * `user_identifier` - The new owner.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CliError` if the room or the user doesn't exist, or if the operation fails.
+    pub async fn transfer_ownership(
+        room_name: &str,
+        user_identifier: UserIdentifier,
+    ) -> Result<(), CliError> {
+        let pool = tchatchers_core::pool::get_pg_pool().await?;
+        let user = Self::find_user(user_identifier, &pool).await?;
+        let Some(room) = Room::transfer_ownership(room_name, user.id, &pool).await? else {
+            return Err(CliError::new(
+                format!("No room found with name '{room_name}'"),
+                ErrorKind::CliInputError,
+            ));
+        };
+        println!("Room '{}' now owned by '{}'", room.name, user.login);
+        Ok(())
+    }
+
+    /// Prints the retention policy of a room, or the default one if no room is given.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_name` - The name of the room, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CliError` if the room doesn't exist or if the operation fails.
+    pub async fn show_retention(room_name: Option<&str>) -> Result<(), CliError> {
+        let pool = tchatchers_core::pool::get_pg_pool().await?;
+        let default = RetentionPolicy::get_default(&pool).await?;
+        let Some(room_name) = room_name else {
+            println!("The rooms keep {default} by default");
+            return Ok(());
+        };
+        let room = Self::find_room(room_name, &pool).await?;
+        match RetentionPolicy::get_for_room(&room.name, &pool).await? {
+            Some(policy) => println!("Room '{}' keeps {policy}", room.name),
+            None => println!(
+                "Room '{}' follows the default policy, keeping {default}",
+                room.name
+            ),
+        }
+        Ok(())
+    }
+
+    /// Sets the retention policy of a room, or the default one if no room is given.
+    ///
+    /// The messages past the new policy are pruned on the next sweep.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_name` - The name of the room, if any.
+    /// * `policy` - The new policy, none making the room follow the default one.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CliError` if the policy isn't valid, if the room doesn't exist or if the operation fails.
+    pub async fn set_retention(
+        room_name: Option<&str>,
+        policy: Option<RetentionPolicy>,
+    ) -> Result<(), CliError> {
+        if policy.is_some_and(|policy| !policy.is_valid()) {
+            return Err(CliError::new(
+                "The retention policy must keep at least one message or day".into(),
+                ErrorKind::CliInputError,
+            ));
+        }
+        let pool = tchatchers_core::pool::get_pg_pool().await?;
+        match (room_name, policy) {
+            (None, None) => {
+                return Err(CliError::new(
+                    "A room must be given to make it follow the default policy".into(),
+                    ErrorKind::CliInputError,
+                ))
+            }
+            (None, Some(policy)) => {
+                policy.set_default(&pool).await?;
+                println!("The rooms now keep {policy} by default");
+            }
+            (Some(room_name), policy) => {
+                if !RetentionPolicy::set_for_room(room_name, policy, &pool).await? {
+                    return Err(CliError::new(
+                        format!("No room found with name '{room_name}'"),
+                        ErrorKind::CliInputError,
+                    ));
+                }
+                match policy {
+                    Some(policy) => println!("Room '{room_name}' now keeps {policy}"),
+                    None => println!("Room '{room_name}' now follows the default policy"),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a room from its name.
+    async fn find_room(room_name: &str, pool: &sqlx::PgPool) -> Result<Room, CliError> {
+        Room::find_by_name(room_name, pool).await?.ok_or_else(|| {
+            CliError::new(
+                format!("No room found with name '{room_name}'"),
+                ErrorKind::CliInputError,
+            )
+        })
+    }
+
+    /// Returns a user from their identifier.
+    async fn find_user(
+        user_identifier: UserIdentifier,
+        pool: &sqlx::PgPool,
+    ) -> Result<PartialUser, CliError> {
+        let user = match &user_identifier {
+            UserIdentifier::Id { value } => PartialUser::find_by_id(*value, pool).await?,
+            UserIdentifier::Login { value } => PartialUser::find_by_login(value, pool).await?,
+        };
+        user.ok_or_else(|| {
+            CliError::new(
+                format!("No user found with {user_identifier}"),
+                ErrorKind::CliInputError,
+            )
+        })
+    }
 }
@@ -9,4 +9,10 @@ pub enum MessageArgAction {
         /// UUIDs of the messages to delete.
         messages_uuid: Vec<Uuid>,
     },
+    /// Print the edit history of a message.
+    #[command(about = "Print the edit history of a message")]
+    History {
+        /// UUID of the message.
+        message_uuid: Uuid,
+    },
 }
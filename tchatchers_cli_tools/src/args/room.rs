@@ -1,3 +1,7 @@
+use tchatchers_core::{room::RoomVisibility, room_retention::RetentionPolicy};
+
+use crate::common::user::UserIdentifier;
+
 /// The actions that can be performed on the rooms, which are entities that store messages sent by users.
 #[derive(Debug, Clone, clap::Subcommand)]
 pub enum RoomArgAction {
@@ -10,4 +14,118 @@ pub enum RoomArgAction {
     /// Prints global activity of the application's rooms.
     #[command(about = "Print global activity of the application's rooms")]
     Activity,
+    /// Creates a room, without any owner.
+    #[command(about = "Creates a room, without any owner")]
+    Create {
+        room_name: String,
+        #[arg(long, help = "The topic of the room.")]
+        topic: Option<String>,
+        #[arg(long, help = "The description of the room.")]
+        description: Option<String>,
+        #[arg(
+            long,
+            value_enum,
+            default_value_t,
+            help = "Who can find and access the room."
+        )]
+        visibility: RoomVisibility,
+    },
+    /// Prints the metadata of a room.
+    #[command(about = "Prints the metadata of a room")]
+    Info { room_name: String },
+    /// Sets the topic of a room, clearing it if none is given.
+    #[command(about = "Sets the topic of a room, clearing it if none is given")]
+    SetTopic {
+        room_name: String,
+        topic: Option<String>,
+    },
+    /// Manages the members of a room.
+    #[command(about = "Manages the members of a room")]
+    Member {
+        #[command(subcommand)]
+        action: RoomMemberArgAction,
+    },
+    /// Manages the retention policies of the rooms.
+    #[command(about = "Manages the retention policies of the rooms")]
+    Retention {
+        #[command(subcommand)]
+        action: RoomRetentionArgAction,
+    },
+}
+
+/// The actions that can be performed on the members of a room.
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum RoomMemberArgAction {
+    /// Lists the members of a room, along with the users invited.
+    #[command(about = "Lists the members of a room, along with the users invited")]
+    List { room_name: String },
+    /// Invites a user to become a member of a room.
+    #[command(about = "Invites a user to become a member of a room")]
+    Invite {
+        room_name: String,
+        #[command(subcommand)]
+        user_identifier: UserIdentifier,
+    },
+    /// Removes a member from a room, or cancels their invitation.
+    #[command(about = "Removes a member from a room, or cancels their invitation")]
+    Remove {
+        room_name: String,
+        #[command(subcommand)]
+        user_identifier: UserIdentifier,
+    },
+    /// Transfers the ownership of a room to a user.
+    #[command(about = "Transfers the ownership of a room to a user")]
+    Transfer {
+        room_name: String,
+        #[command(subcommand)]
+        user_identifier: UserIdentifier,
+    },
+}
+
+/// The actions that can be performed on the retention policies of the rooms.
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum RoomRetentionArgAction {
+    /// Prints the retention policy of a room, or the default one if no room is given.
+    #[command(
+        about = "Prints the retention policy of a room, or the default one if no room is given"
+    )]
+    Show { room_name: Option<String> },
+    /// Sets the retention policy of a room, or the default one if no room is given.
+    #[command(
+        about = "Sets the retention policy of a room, or the default one if no room is given"
+    )]
+    Set {
+        #[arg(long, help = "The room to set the policy of.")]
+        room: Option<String>,
+        #[command(subcommand)]
+        policy: RetentionPolicyArg,
+    },
+}
+
+/// The retention policies that can be set.
+#[derive(Debug, Clone, Copy, clap::Subcommand)]
+pub enum RetentionPolicyArg {
+    /// Keeps the given number of latest messages.
+    #[command(about = "Keeps the given number of latest messages")]
+    Count { messages: i32 },
+    /// Keeps the messages posted during the given number of days.
+    #[command(about = "Keeps the messages posted during the given number of days")]
+    Age { days: i32 },
+    /// Keeps every message.
+    #[command(about = "Keeps every message")]
+    Unlimited,
+    /// Makes the room follow the default policy.
+    #[command(about = "Makes the room follow the default policy")]
+    Default,
+}
+
+impl From<RetentionPolicyArg> for Option<RetentionPolicy> {
+    fn from(value: RetentionPolicyArg) -> Self {
+        match value {
+            RetentionPolicyArg::Count { messages } => Some(RetentionPolicy::MessageCount(messages)),
+            RetentionPolicyArg::Age { days } => Some(RetentionPolicy::Age(days)),
+            RetentionPolicyArg::Unlimited => Some(RetentionPolicy::Unlimited),
+            RetentionPolicyArg::Default => None,
+        }
+    }
 }
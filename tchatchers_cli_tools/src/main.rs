@@ -15,7 +15,11 @@ mod errors;
 use std::process::{ExitCode, Termination};
 
 use actions::{env::EnvAction, message::MessageAction, room::RoomAction};
-use args::{message::MessageArgAction, CliArgs};
+use args::{
+    message::MessageArgAction,
+    room::{RoomMemberArgAction, RoomRetentionArgAction},
+    CliArgs,
+};
 use clap::{CommandFactory, Parser};
 use errors::CliError;
 use tokio::signal::unix::SignalKind;
@@ -25,6 +29,7 @@ use crate::actions::{queue::QueueArgAction, report::ReportAction, user::UserActi
 #[macro_use]
 extern crate derive_more;
 use log::{debug, info};
+use tchatchers_core::room::InsertableRoom;
 
 #[tokio::main]
 async fn main() -> ExitCode {
@@ -102,6 +107,10 @@ async fn run_main() -> Result<(), CliError> {
                 info!("Deleting messages with UUIDs: {:?}", messages_uuid);
                 MessageAction::delete_messages(messages_uuid).await?
             }
+            MessageArgAction::History { message_uuid } => {
+                info!("Getting the history of message {}...", message_uuid);
+                MessageAction::history(message_uuid).await?
+            }
         },
         args::CliEntityArg::Env { action } => match action {
             args::env::EnvArgAction::Create(output_stream) => {
@@ -132,6 +141,72 @@ async fn run_main() -> Result<(), CliError> {
                 info!("Getting the activity...");
                 RoomAction::get_activity().await?
             }
+            args::room::RoomArgAction::Create {
+                room_name,
+                topic,
+                description,
+                visibility,
+            } => {
+                info!("Creating room {}...", room_name);
+                RoomAction::create(InsertableRoom {
+                    name: room_name,
+                    topic,
+                    description,
+                    visibility,
+                })
+                .await?
+            }
+            args::room::RoomArgAction::Info { room_name } => {
+                info!("Getting the metadata of room {}...", room_name);
+                RoomAction::info(&room_name).await?
+            }
+            args::room::RoomArgAction::SetTopic { room_name, topic } => {
+                info!("Setting the topic of room {}...", room_name);
+                RoomAction::set_topic(&room_name, topic.as_deref()).await?
+            }
+            args::room::RoomArgAction::Member { action } => match action {
+                RoomMemberArgAction::List { room_name } => {
+                    info!("Listing the members of room {}...", room_name);
+                    RoomAction::list_members(&room_name).await?
+                }
+                RoomMemberArgAction::Invite {
+                    room_name,
+                    user_identifier,
+                } => {
+                    info!("Inviting user {} to room {}...", user_identifier, room_name);
+                    RoomAction::invite_member(&room_name, user_identifier).await?
+                }
+                RoomMemberArgAction::Remove {
+                    room_name,
+                    user_identifier,
+                } => {
+                    info!(
+                        "Removing user {} from room {}...",
+                        user_identifier, room_name
+                    );
+                    RoomAction::remove_member(&room_name, user_identifier).await?
+                }
+                RoomMemberArgAction::Transfer {
+                    room_name,
+                    user_identifier,
+                } => {
+                    info!(
+                        "Transferring room {} to user {}...",
+                        room_name, user_identifier
+                    );
+                    RoomAction::transfer_ownership(&room_name, user_identifier).await?
+                }
+            },
+            args::room::RoomArgAction::Retention { action } => match action {
+                RoomRetentionArgAction::Show { room_name } => {
+                    info!("Getting the retention policy...");
+                    RoomAction::show_retention(room_name.as_deref()).await?
+                }
+                RoomRetentionArgAction::Set { room, policy } => {
+                    info!("Setting the retention policy...");
+                    RoomAction::set_retention(room.as_deref(), policy.into()).await?
+                }
+            },
         },
         args::CliEntityArg::Report { action } => match action {
             args::report::ReportArgs::Check => {
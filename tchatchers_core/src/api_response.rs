@@ -87,11 +87,8 @@ pub enum ApiGenericResponse {
     #[cfg_attr(feature = "back", from_err(axum::extract::rejection::BytesRejection))]
     ByteRejection(String),
     #[response(status=BAD_REQUEST, error("serialization_error"))]
-    #[from_err(bincode::Error)]
-    #[cfg_attr(
-        feature = "back",
-        from_err(jsonwebtoken::errors::Error, serde_json::Error)
-    )]
+    #[from_err(bincode::Error, serde_json::Error, crate::ws_codec::WsCodecError)]
+    #[cfg_attr(feature = "back", from_err(jsonwebtoken::errors::Error))]
     SerializationError(String),
     #[response(status=BAD_REQUEST, errors("validation_error"))]
     ValidationError(Vec<String>),
@@ -102,6 +99,34 @@ pub enum ApiGenericResponse {
     IoError(String),
     #[response(status=TOO_MANY_REQUESTS, simple("max_conns_reached"))]
     TooManyRequests,
+    #[response(status=SERVICE_UNAVAILABLE, simple("too_many_rooms"))]
+    TooManyRooms,
+    #[response(status=BAD_REQUEST, error("invalid_cursor"))]
+    InvalidCursor(String),
+    #[response(status=OK, simple("notifications_read"))]
+    NotificationsRead,
+    #[response(status=CREATED, simple("room_created"))]
+    RoomCreated,
+    #[response(status=CONFLICT, simple("room_already_exists"))]
+    RoomAlreadyExists,
+    #[response(status=NOT_FOUND, simple("room_not_found"))]
+    RoomNotFound,
+    #[response(status=OK, simple("room_updated"))]
+    RoomUpdated,
+    #[response(status=OK, simple("user_invited"))]
+    UserInvited,
+    #[response(status=CONFLICT, simple("user_already_invited"))]
+    UserAlreadyInvited,
+    #[response(status=OK, simple("member_removed"))]
+    MemberRemoved,
+    #[response(status=NOT_FOUND, simple("member_not_found"))]
+    MemberNotFound,
+    #[response(status=OK, simple("ownership_transferred"))]
+    OwnershipTransferred,
+    #[response(status=OK, simple("invitation_accepted"))]
+    InvitationAccepted,
+    #[response(status=NOT_FOUND, simple("invitation_not_found"))]
+    InvitationNotFound,
     #[response(status=BAD_REQUEST, error("multipart_error"))]
     #[cfg_attr(feature = "back", from_err(axum::extract::multipart::MultipartError))]
     MultipartError(String),
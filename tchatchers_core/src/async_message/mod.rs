@@ -15,7 +15,10 @@ use sqlx::PgPool;
 pub mod async_payload;
 pub mod processor;
 
-use crate::{user::PartialUser, ws_message::WsMessageContent};
+use crate::{
+    message_read::MessageRead, message_revision::MessageEdition, reaction::ReactionUpdate,
+    user::PartialUser, ws_message::WsMessageContent,
+};
 
 use self::async_payload::AsyncPayload;
 
@@ -26,15 +29,21 @@ static NOT_BLOCKING_OPTIONS: OnceLock<StreamReadOptions> = OnceLock::new();
 #[derive(Debug, Clone, Serialize, Deserialize, Display)]
 pub enum AsyncMessage {
     LoggedUser(i32),
-    MessageSeen(uuid::Uuid),
+    MessageSeen(MessageRead),
     PersistMessage(WsMessageContent),
+    /// Deprecated, the rooms being pruned by a scheduled sweep instead.
+    ///
+    /// Kept so that the variants after it keep their index, the payloads being
+    /// identified by their index once queued.
     CleanRoom(String),
     RemoveUserData(PartialUser),
+    DeleteMessage(uuid::Uuid),
+    EditMessage(MessageEdition),
+    PersistReaction(ReactionUpdate),
 }
 
 /// Represents a queue report containing information about the latest executed processes for a queue.
-#[derive(Debug, Clone, sqlx::FromRow, Display)]
-#[display("[{process_id}#{id}] ({successfull_records}/{records_processed}) on {passed_at}")]
+#[derive(Debug, Clone, sqlx::FromRow)]
 pub struct QueueReport {
     pub id: i32,
     pub process_id: AsyncQueue,
@@ -42,6 +51,26 @@ pub struct QueueReport {
     pub successfull_records: i32,
     pub records_processed: i32,
     pub passed_at: chrono::DateTime<Utc>,
+    /// The room the process has been run on, if it is run per room.
+    pub room: Option<String>,
+}
+
+impl std::fmt::Display for QueueReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}#{}] ({}/{}) on {}",
+            self.process_id,
+            self.id,
+            self.successfull_records,
+            self.records_processed,
+            self.passed_at
+        )?;
+        if let Some(room) = &self.room {
+            write!(f, " in room '{room}'")?;
+        }
+        Ok(())
+    }
 }
 
 impl QueueReport {
@@ -77,6 +106,9 @@ pub enum AsyncQueue {
     PersistMessage = 3,
     CleanRoom = 4,
     RemoveUserData = 5,
+    DeleteMessage = 6,
+    EditMessage = 7,
+    PersistReaction = 8,
 }
 
 impl AsyncQueue {
@@ -175,6 +207,11 @@ impl AsyncQueue {
         .await
     }
 
+    /// Whether the queue is processed on a schedule rather than from its events.
+    pub fn is_scheduled(&self) -> bool {
+        matches!(self, Self::CleanRoom)
+    }
+
     /// Returns an iterator over all the async queue types.
     pub fn iter() -> impl Iterator<Item = Self> {
         [
@@ -183,6 +220,9 @@ impl AsyncQueue {
             Self::PersistMessage,
             Self::CleanRoom,
             Self::RemoveUserData,
+            Self::DeleteMessage,
+            Self::EditMessage,
+            Self::PersistReaction,
         ]
         .iter()
         .cloned()
@@ -206,6 +246,9 @@ impl AsyncMessage {
             AsyncMessage::PersistMessage(_) => AsyncQueue::PersistMessage,
             AsyncMessage::CleanRoom(_) => AsyncQueue::CleanRoom,
             AsyncMessage::RemoveUserData(_) => AsyncQueue::RemoveUserData,
+            AsyncMessage::DeleteMessage(_) => AsyncQueue::DeleteMessage,
+            AsyncMessage::EditMessage(_) => AsyncQueue::EditMessage,
+            AsyncMessage::PersistReaction(_) => AsyncQueue::PersistReaction,
         }
     }
 
@@ -15,12 +15,28 @@ use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::{
+    message_read::MessageRead,
+    message_revision::MessageEdition,
+    reaction::ReactionUpdate,
+    room_retention::RetentionPolicy,
     user::{PartialUser, User},
     ws_message::WsMessageContent,
 };
 
 use super::{async_payload::AsyncPayload, AsyncMessage, AsyncOperationPGType, AsyncQueue};
 
+/// How long the reactions on messages that aren't persisted yet are retried.
+const REACTION_RETRY_DELAY: chrono::Duration = chrono::Duration::minutes(1);
+
+/// How long the read receipts of messages that aren't persisted yet are
+/// retried.
+const MESSAGE_SEEN_RETRY_DELAY: chrono::Duration = chrono::Duration::minutes(1);
+
+/// The future of a processor, resolving to the IDs of the payloads kept in
+/// the queue for a later run.
+type ProcessorFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<HashSet<String>, sqlx::Error>> + Send + 'a>>;
+
 /// Processes messages related to logged users.
 ///
 /// This function takes a vector of `AsyncPayload` messages and a PostgreSQL pool.
@@ -61,36 +77,59 @@ async fn process_logged_users(
 /// Processes messages related to seen messages.
 ///
 /// This function takes a vector of `AsyncPayload` messages and a PostgreSQL pool.
-/// It extracts the seen message UUIDs from the messages and updates the corresponding entities
-/// in the database as "seen". Any messages that don't match the expected format are skipped.
+/// It extracts the read receipts from the messages and persists them, along with
+/// the read positions of their users. Any messages that don't match the expected
+/// format are skipped, and the receipts of messages that aren't persisted yet are
+/// kept for a later run.
 ///
 /// # Arguments
 ///
 /// * `payloads` - A vector of `AsyncPayload` messages to process.
 /// * `pool` - A reference to the PostgreSQL pool for database operations.
-async fn messages_seen(payloads: &Vec<AsyncPayload>, pool: &PgPool) -> Result<(), sqlx::Error> {
-    let mut entities_to_update: HashMap<Uuid, AsyncOperationPGType<Uuid>> =
+async fn messages_seen(
+    payloads: &Vec<AsyncPayload>,
+    pool: &PgPool,
+) -> Result<HashSet<String>, sqlx::Error> {
+    let mut entities_to_update: HashMap<(Uuid, i32), MessageRead> =
         HashMap::with_capacity(payloads.capacity());
 
     for payload in payloads {
-        let AsyncMessage::MessageSeen(id) = payload.entity else {
+        let AsyncMessage::MessageSeen(read) = &payload.entity else {
             warn!("Entity {:?} isn't matching the expected format", payload.id);
             continue;
         };
 
-        if let Some(payload_id) = &payload.id {
-            entities_to_update.insert(
-                id,
-                AsyncOperationPGType {
-                    entity_id: id,
-                    queue_id: payload_id.clone(),
-                    timestamp: payload.timestamp,
-                },
-            );
+        // Only the first read of a message by a user is kept.
+        entities_to_update
+            .entry((read.message_uuid, read.user_id))
+            .or_insert_with(|| read.clone());
+    }
+
+    let pending_messages =
+        MessageRead::persist_async(entities_to_update.into_values().collect(), pool).await?;
+
+    // The receipts of messages that aren't persisted yet are retried, unless
+    // the message has been deleted or lost in the meantime.
+    let retry_deadline = chrono::Utc::now() - MESSAGE_SEEN_RETRY_DELAY;
+    let mut kept_ids: HashSet<String> = HashSet::new();
+    for payload in payloads {
+        let AsyncMessage::MessageSeen(read) = &payload.entity else {
+            continue;
+        };
+        if !pending_messages.contains(&read.message_uuid) {
+            continue;
+        }
+        match (&payload.id, payload.timestamp > retry_deadline) {
+            (Some(id), true) => {
+                kept_ids.insert(id.clone());
+            }
+            _ => warn!(
+                "The read receipt {read} is dropped as its message hasn't been persisted in time"
+            ),
         }
     }
 
-    WsMessageContent::mark_as_seen_async(entities_to_update.into_values().collect(), pool).await
+    Ok(kept_ids)
 }
 
 async fn persist_messages(payloads: &Vec<AsyncPayload>, pool: &PgPool) -> Result<(), sqlx::Error> {
@@ -109,32 +148,142 @@ async fn persist_messages(payloads: &Vec<AsyncPayload>, pool: &PgPool) -> Result
     WsMessageContent::persist_async(entities_to_update.into_values().collect(), pool).await
 }
 
-async fn clean_rooms(payloads: &Vec<AsyncPayload>, pool: &PgPool) -> Result<(), sqlx::Error> {
-    let mut entities_to_delete: HashSet<&str> = HashSet::with_capacity(payloads.capacity());
+/// Prunes the messages of the rooms past their retention policy.
+///
+/// # Arguments
+///
+/// * `pool` - A reference to the PostgreSQL pool for database operations.
+async fn sweep_rooms(pool: &PgPool) -> Result<(), sqlx::Error> {
+    RetentionPolicy::sweep(pool).await
+}
+
+async fn clear_user_data(payloads: &Vec<AsyncPayload>, pool: &PgPool) -> Result<(), sqlx::Error> {
+    let mut entities_to_clear: HashSet<&PartialUser> = HashSet::with_capacity(payloads.capacity());
 
     for payload in payloads {
-        let AsyncMessage::CleanRoom(entity) = &payload.entity else {
+        let AsyncMessage::RemoveUserData(entity) = &payload.entity else {
             warn!("Entity {:?} isn't matching the expected format", payload.id);
             continue;
         };
 
-        entities_to_delete.insert(entity);
+        entities_to_clear.insert(entity);
+    }
+    PartialUser::clear_data(entities_to_clear, pool).await
+}
+
+/// Processes messages related to deleted messages.
+///
+/// This function takes a vector of `AsyncPayload` messages and a PostgreSQL pool.
+/// It extracts the deleted message UUIDs from the messages and removes the corresponding entities
+/// from the database. Any messages that don't match the expected format are skipped.
+///
+/// # Arguments
+///
+/// * `payloads` - A vector of `AsyncPayload` messages to process.
+/// * `pool` - A reference to the PostgreSQL pool for database operations.
+async fn delete_messages(payloads: &Vec<AsyncPayload>, pool: &PgPool) -> Result<(), sqlx::Error> {
+    let mut entities_to_delete: HashMap<Uuid, AsyncOperationPGType<Uuid>> =
+        HashMap::with_capacity(payloads.capacity());
+
+    for payload in payloads {
+        let AsyncMessage::DeleteMessage(id) = payload.entity else {
+            warn!("Entity {:?} isn't matching the expected format", payload.id);
+            continue;
+        };
+
+        if let Some(payload_id) = &payload.id {
+            entities_to_delete.insert(
+                id,
+                AsyncOperationPGType {
+                    entity_id: id,
+                    queue_id: payload_id.clone(),
+                    timestamp: payload.timestamp,
+                },
+            );
+        }
     }
 
-    WsMessageContent::clean_rooms(entities_to_delete, pool).await
+    WsMessageContent::delete_messages_async(entities_to_delete.into_values().collect(), pool).await
 }
-async fn clear_user_data(payloads: &Vec<AsyncPayload>, pool: &PgPool) -> Result<(), sqlx::Error> {
-    let mut entities_to_clear: HashSet<&PartialUser> = HashSet::with_capacity(payloads.capacity());
+
+/// Processes messages related to edited messages.
+///
+/// This function takes a vector of `AsyncPayload` messages and a PostgreSQL pool.
+/// It extracts the editions from the messages and applies them in the database,
+/// keeping the former versions of the messages as revisions. Any messages that
+/// don't match the expected format are skipped.
+///
+/// # Arguments
+///
+/// * `payloads` - A vector of `AsyncPayload` messages to process.
+/// * `pool` - A reference to the PostgreSQL pool for database operations.
+async fn edit_messages(payloads: &Vec<AsyncPayload>, pool: &PgPool) -> Result<(), sqlx::Error> {
+    let mut editions: Vec<MessageEdition> = Vec::with_capacity(payloads.capacity());
 
     for payload in payloads {
-        let AsyncMessage::RemoveUserData(entity) = &payload.entity else {
+        let AsyncMessage::EditMessage(edition) = payload.clone().entity else {
             warn!("Entity {:?} isn't matching the expected format", payload.id);
             continue;
         };
 
-        entities_to_clear.insert(entity);
+        editions.push(edition);
     }
-    PartialUser::clear_data(entities_to_clear, pool).await
+
+    WsMessageContent::edit_messages_async(editions, pool).await
+}
+
+/// Processes messages related to reactions.
+///
+/// This function takes a vector of `AsyncPayload` messages and a PostgreSQL pool.
+/// It extracts the reaction updates from the messages and persists them in the order
+/// they have been emitted. Any messages that don't match the expected format are skipped.
+///
+/// # Arguments
+///
+/// * `payloads` - A vector of `AsyncPayload` messages to process.
+/// * `pool` - A reference to the PostgreSQL pool for database operations.
+async fn persist_reactions(
+    payloads: &Vec<AsyncPayload>,
+    pool: &PgPool,
+) -> Result<HashSet<String>, sqlx::Error> {
+    let mut updates: Vec<ReactionUpdate> = Vec::with_capacity(payloads.capacity());
+
+    for payload in payloads {
+        let AsyncMessage::PersistReaction(update) = payload.clone().entity else {
+            warn!("Entity {:?} isn't matching the expected format", payload.id);
+            continue;
+        };
+
+        updates.push(update);
+    }
+
+    let pending_messages = ReactionUpdate::persist_async(updates, pool).await?;
+
+    // The reactions on messages that aren't persisted yet are retried, unless
+    // the message has been deleted or lost in the meantime.
+    let retry_deadline = chrono::Utc::now() - REACTION_RETRY_DELAY;
+    let mut kept_ids: HashSet<String> = HashSet::new();
+    for payload in payloads {
+        let AsyncMessage::PersistReaction(
+            ReactionUpdate::Add(reaction) | ReactionUpdate::Remove(reaction),
+        ) = &payload.entity
+        else {
+            continue;
+        };
+        if !pending_messages.contains(&reaction.message_uuid) {
+            continue;
+        }
+        match (&payload.id, payload.timestamp > retry_deadline) {
+            (Some(id), true) => {
+                kept_ids.insert(id.clone());
+            }
+            _ => warn!(
+                "The reaction {reaction} is dropped as its message hasn't been persisted in time"
+            ),
+        }
+    }
+
+    Ok(kept_ids)
 }
 
 /// Returns the appropriate processor for the given queue.
@@ -152,16 +301,30 @@ fn get_processor<'a>(
     queue: AsyncQueue,
     payloads: &'a Vec<AsyncPayload>,
     pool: &'a PgPool,
-) -> Pin<Box<dyn Future<Output = Result<(), sqlx::Error>> + Send + 'a>> {
+) -> ProcessorFuture<'a> {
     match queue {
-        AsyncQueue::LoggedUsers => Box::pin(process_logged_users(payloads, pool)),
+        AsyncQueue::LoggedUsers => processes_all(process_logged_users(payloads, pool)),
         AsyncQueue::MessagesSeen => Box::pin(messages_seen(payloads, pool)),
-        AsyncQueue::PersistMessage => Box::pin(persist_messages(payloads, pool)),
-        AsyncQueue::CleanRoom => Box::pin(clean_rooms(payloads, pool)),
-        AsyncQueue::RemoveUserData => Box::pin(clear_user_data(payloads, pool)),
+        AsyncQueue::PersistMessage => processes_all(persist_messages(payloads, pool)),
+        AsyncQueue::CleanRoom => processes_all(sweep_rooms(pool)),
+        AsyncQueue::RemoveUserData => processes_all(clear_user_data(payloads, pool)),
+        AsyncQueue::DeleteMessage => processes_all(delete_messages(payloads, pool)),
+        AsyncQueue::EditMessage => processes_all(edit_messages(payloads, pool)),
+        AsyncQueue::PersistReaction => Box::pin(persist_reactions(payloads, pool)),
     }
 }
 
+/// Boxes a processor that processes all of its payloads at once.
+///
+/// # Arguments
+///
+/// * `processor` - The future of the processor.
+fn processes_all<'a>(
+    processor: impl Future<Output = Result<(), sqlx::Error>> + Send + 'a,
+) -> ProcessorFuture<'a> {
+    Box::pin(async move { processor.await.map(|_| HashSet::new()) })
+}
+
 /// Processes messages in the specified queue.
 ///
 /// This function takes a queue, a list of messages, a PostgreSQL pool, and a Redis connection.
@@ -180,11 +343,18 @@ pub async fn process(
     pg_pool: &PgPool,
     redis_conn: &mut redis::aio::MultiplexedConnection,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let number_of_messages = messages.len();
     let processor = get_processor(queue, &messages, pg_pool);
-    processor.await?;
+    let kept_ids = processor.await?;
+    let number_of_messages = messages.len() - kept_ids.len();
     info!("[{queue}] {number_of_messages} messages passed");
-    let id_list: Vec<String> = messages.into_iter().filter_map(|li| li.id).collect();
+    if !kept_ids.is_empty() {
+        info!("[{queue}] {} messages kept for a later run", kept_ids.len());
+    }
+    let id_list: Vec<String> = messages
+        .into_iter()
+        .filter_map(|li| li.id)
+        .filter(|id| !kept_ids.contains(id))
+        .collect();
     debug!("[{queue}] Deleting old messages...");
     let number_of_id_deleted = queue.delete(id_list, redis_conn).await?;
     if number_of_id_deleted != number_of_messages {
@@ -194,3 +364,28 @@ pub async fn process(
     }
     Ok(())
 }
+
+/// Runs the process of a scheduled queue.
+///
+/// The events of the queue, which can only have been left by former versions,
+/// are discarded beforehand.
+///
+/// # Arguments
+///
+/// * `queue` - The scheduled queue to run the process of.
+/// * `pg_pool` - A reference to the PostgreSQL pool for database operations.
+/// * `redis_conn` - A mutable reference to the Redis connection for queue operations.
+pub async fn run_scheduled(
+    queue: AsyncQueue,
+    pg_pool: &PgPool,
+    redis_conn: &mut redis::aio::MultiplexedConnection,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match redis::AsyncCommands::del::<_, usize>(redis_conn, queue.to_string()).await {
+        Ok(0) => {}
+        Ok(_) => info!("[{queue}] Events left by former versions discarded"),
+        Err(e) => warn!("[{queue}] The events left in the queue couldn't be discarded : {e}"),
+    }
+    get_processor(queue, &Vec::new(), pg_pool).await?;
+    info!("[{queue}] Scheduled process passed");
+    Ok(())
+}
@@ -12,6 +12,7 @@ use crate::common::AUTHORIZATION_TOKEN_EXPIRACY_TIME;
 use crate::serializable_token::SerializableToken;
 use crate::{profile::Profile, user::User};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 /// The JWT structure, holding the data that is shared between the front and
 /// the back.
@@ -23,10 +24,22 @@ pub struct AuthorizationToken {
     pub user_profile: Profile,
     /// The expiracy time on which the JWT expires.
     pub exp: i64,
+    /// The token family of the refresh token the JWT has been issued with,
+    /// identifying the user's session.
+    ///
+    /// Missing from the JWT issued by former versions.
+    #[serde(default)]
+    pub token_family: Option<Uuid>,
 }
 
-impl From<User> for AuthorizationToken {
-    fn from(user: User) -> AuthorizationToken {
+impl AuthorizationToken {
+    /// Creates a new JWT for the given user.
+    ///
+    /// # Arguments
+    ///
+    /// - user : The user authenticated.
+    /// - token_family : The token family of the user's refresh token.
+    pub fn new(user: User, token_family: Uuid) -> AuthorizationToken {
         AuthorizationToken {
             user_id: user.id,
             user_profile: user.profile,
@@ -34,6 +47,7 @@ impl From<User> for AuthorizationToken {
                 + *AUTHORIZATION_TOKEN_EXPIRACY_TIME
                     .get_or_init(|| chrono::Duration::try_weeks(1).unwrap()))
             .timestamp(),
+            token_family: Some(token_family),
         }
     }
 }
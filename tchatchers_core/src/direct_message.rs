@@ -0,0 +1,225 @@
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+//! Direct conversations are private rooms between two users.
+//!
+//! Their messages are stored as the ones of any room, under a room name built
+//! from the IDs of both users, such as `dm:3:12`. As the public room names
+//! can't contain any colon, a direct conversation can't be joined as a public
+//! room.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{user::PartialUser, ws_message::WsMessageContent};
+
+/// The prefix of the room names of the direct conversations.
+const DM_ROOM_PREFIX: &str = "dm";
+
+/// A direct conversation between two users.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DirectConversation {
+    /// The IDs of the two users, the lowest first.
+    users: (i32, i32),
+}
+
+impl DirectConversation {
+    /// Returns the conversation between two users.
+    ///
+    /// # Arguments
+    ///
+    /// - user_id : The ID of one of the users.
+    /// - other_id : The ID of the other user.
+    pub fn new(user_id: i32, other_id: i32) -> Self {
+        Self {
+            users: (user_id.min(other_id), user_id.max(other_id)),
+        }
+    }
+
+    /// Returns the conversation stored under a room name, if it is the one of
+    /// a direct conversation.
+    ///
+    /// # Arguments
+    ///
+    /// - room : The room name.
+    pub fn from_room(room: &str) -> Option<Self> {
+        let mut parts = room.split(':');
+        let (Some(DM_ROOM_PREFIX), Some(first), Some(second), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return None;
+        };
+        let conversation = Self::new(first.parse().ok()?, second.parse().ok()?);
+        // Only the canonical name of a conversation designates it.
+        (conversation.room() == room).then_some(conversation)
+    }
+
+    /// The room name under which the messages of the conversation are stored.
+    pub fn room(&self) -> String {
+        format!("{DM_ROOM_PREFIX}:{}:{}", self.users.0, self.users.1)
+    }
+
+    /// Whether a user takes part in the conversation.
+    ///
+    /// # Arguments
+    ///
+    /// - user_id : The ID of the user.
+    pub fn includes(&self, user_id: i32) -> bool {
+        self.users.0 == user_id || self.users.1 == user_id
+    }
+
+    /// Returns the user the given one is talking with.
+    ///
+    /// # Arguments
+    ///
+    /// - user_id : The ID of one of the users of the conversation.
+    pub fn other(&self, user_id: i32) -> i32 {
+        if self.users.0 == user_id {
+            self.users.1
+        } else {
+            self.users.0
+        }
+    }
+}
+
+/// Whether a user can join a room.
+///
+/// Direct conversations can only be joined by their two users.
+///
+/// # Arguments
+///
+/// - room : The room name.
+/// - user_id : The ID of the user.
+pub fn can_join(room: &str, user_id: i32) -> bool {
+    DirectConversation::from_room(room).is_none_or(|dm| dm.includes(user_id))
+}
+
+/// A direct conversation of a user, as listed in their inbox.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectConversationSummary {
+    /// The user talked with.
+    pub user: PartialUser,
+    /// The latest message of the conversation.
+    pub last_message: WsMessageContent,
+    /// The number of messages of the other user not read yet.
+    pub unread: i64,
+}
+
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+impl DirectConversationSummary {
+    /// Returns the direct conversations of a user, from the latest active to
+    /// the oldest.
+    ///
+    /// Only the conversations with at least one message are listed.
+    ///
+    /// # Arguments
+    ///
+    /// - user_id : The ID of the user.
+    /// - pool : The connection pool.
+    pub async fn get_for_user(user_id: i32, pool: &sqlx::PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        let mut last_messages: Vec<WsMessageContent> = sqlx::query_as(
+            "
+            SELECT DISTINCT ON (m.room) * FROM MESSAGE m INNER JOIN CHATTER c ON m.author = c.id
+            WHERE m.room LIKE 'dm:%'
+                AND $1 IN (split_part(m.room, ':', 2), split_part(m.room, ':', 3))
+            ORDER BY m.room, m.timestamp DESC
+            ",
+        )
+        .bind(user_id.to_string())
+        .fetch_all(pool)
+        .await?;
+        last_messages.sort_by_key(|m| std::cmp::Reverse(m.timestamp));
+        let rooms: Vec<String> = last_messages.iter().map(|m| m.room.clone()).collect();
+        let unread: Vec<(String, i64)> = sqlx::query_as(
+            "
+            SELECT m.room, COUNT(*) FROM MESSAGE m
+            LEFT JOIN ROOM_READ_POSITION p ON p.room = m.room AND p.user_id = $1
+            WHERE m.room = ANY($2)
+                AND m.author <> $1
+                AND NOT m.is_deleted
+                AND (p.last_read_at IS NULL OR m.timestamp > p.last_read_at)
+            GROUP BY m.room
+            ",
+        )
+        .bind(user_id)
+        .bind(&rooms)
+        .fetch_all(pool)
+        .await?;
+        let others: Vec<i32> = rooms
+            .iter()
+            .filter_map(|room| DirectConversation::from_room(room))
+            .map(|dm| dm.other(user_id))
+            .collect();
+        let users: Vec<PartialUser> = sqlx::query_as("SELECT * FROM CHATTER WHERE id = ANY($1)")
+            .bind(&others)
+            .fetch_all(pool)
+            .await?;
+        Ok(last_messages
+            .into_iter()
+            .filter_map(|last_message| {
+                let other = DirectConversation::from_room(&last_message.room)?.other(user_id);
+                let user = users.iter().find(|user| user.id == other)?.clone();
+                let unread = unread
+                    .iter()
+                    .find(|(room, _)| *room == last_message.room)
+                    .map_or(0, |(_, count)| *count);
+                Some(Self {
+                    user,
+                    last_message,
+                    unread,
+                })
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn room_round_trip() {
+        let conversation = DirectConversation::new(12, 3);
+        assert_eq!(conversation, DirectConversation::new(3, 12));
+        assert_eq!(conversation.room(), "dm:3:12");
+        assert_eq!(
+            DirectConversation::from_room(&conversation.room()),
+            Some(conversation)
+        );
+    }
+
+    #[test]
+    fn non_canonical_rooms_are_rejected() {
+        for room in [
+            "dm:12:3",
+            "dm:03:12",
+            "dm:+3:12",
+            "dm:3:12:",
+            "dm:3:12:5",
+            "dm:3",
+            "dm::12",
+            "DM:3:12",
+            "dm:a:12",
+            "general",
+        ] {
+            assert_eq!(DirectConversation::from_room(room), None, "{room}");
+        }
+    }
+
+    #[test]
+    fn other_user() {
+        let conversation = DirectConversation::new(3, 12);
+        assert!(conversation.includes(3) && conversation.includes(12));
+        assert!(!conversation.includes(4));
+        assert_eq!(conversation.other(3), 12);
+        assert_eq!(conversation.other(12), 3);
+    }
+
+    #[test]
+    fn only_the_users_of_a_conversation_can_join_it() {
+        assert!(can_join("dm:3:12", 3));
+        assert!(can_join("dm:3:12", 12));
+        assert!(!can_join("dm:3:12", 4));
+        assert!(can_join("general", 4));
+    }
+}
@@ -6,19 +6,30 @@ pub mod api_response;
 pub mod async_message;
 pub mod authorization_token;
 pub(crate) mod common;
+pub mod direct_message;
 pub mod locale;
+pub mod message_read;
+pub mod message_revision;
 #[cfg(feature = "front")]
 pub mod navlink;
+pub mod notification;
 #[cfg(any(feature = "back", feature = "cli", feature = "async"))]
 pub mod pool;
 pub mod profile;
+pub mod reaction;
 pub mod refresh_token;
 pub mod report;
 pub mod room;
+pub mod room_member;
+pub mod room_pin;
+pub mod room_retention;
 pub mod serializable_token;
 pub mod user;
 pub mod validation_error_message;
+pub mod ws_codec;
 pub mod ws_message;
+#[cfg(feature = "back")]
+pub mod ws_ticket;
 
 #[macro_use]
 extern crate log;
@@ -0,0 +1,255 @@
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+//! Read receipts record which users read each message, so that its author can
+//! know who has seen it.
+//!
+//! Every user also has a read position per room, the timestamp of the latest
+//! message they read in it, from which their unread messages are counted.
+
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::user::PartialUser;
+
+/// A message read by a user, to be persisted asynchronously.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Display)]
+#[display("[{message_uuid}:{user_id}]")]
+pub struct MessageRead {
+    /// The UUID of the message read.
+    pub message_uuid: Uuid,
+    /// The ID of the user that read the message.
+    pub user_id: i32,
+    /// When the message has been read.
+    pub read_at: DateTime<Utc>,
+}
+
+impl MessageRead {
+    /// Creates a read receipt, the message being read now.
+    ///
+    /// # Arguments
+    ///
+    /// - message_uuid : The UUID of the message read.
+    /// - user_id : The ID of the user that read the message.
+    pub fn new(message_uuid: Uuid, user_id: i32) -> Self {
+        Self {
+            message_uuid,
+            user_id,
+            read_at: Utc::now(),
+        }
+    }
+}
+
+/// A user that read a message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(
+    any(feature = "back", feature = "cli", feature = "async"),
+    derive(sqlx::FromRow)
+)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageReader {
+    /// The user that read the message.
+    #[cfg_attr(
+        any(feature = "back", feature = "cli", feature = "async"),
+        sqlx(flatten)
+    )]
+    pub user: PartialUser,
+    /// When the user read the message for the first time.
+    pub read_at: DateTime<Utc>,
+}
+
+/// The number of messages of a room a user hasn't read yet.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(
+    any(feature = "back", feature = "cli", feature = "async"),
+    derive(sqlx::FromRow)
+)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomUnread {
+    /// The room name.
+    pub room: String,
+    /// The number of messages posted by the other users since the latest one
+    /// the user read.
+    pub unread: i64,
+}
+
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+impl MessageRead {
+    /// Persists a list of read receipts, moving forward the read positions of
+    /// their users.
+    ///
+    /// The receipts of messages read by their own author are ignored.
+    ///
+    /// Returns the UUIDs of the messages that aren't persisted yet, whose
+    /// receipts are left aside to be persisted later on.
+    ///
+    /// # Arguments
+    ///
+    /// - reads : The receipts to persist.
+    /// - pool : The connection pool.
+    pub async fn persist_async(
+        reads: Vec<Self>,
+        pool: &sqlx::PgPool,
+    ) -> Result<std::collections::HashSet<Uuid>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let messages_uuid: Vec<Uuid> = reads.iter().map(|r| r.message_uuid).collect();
+        let persisted_messages: std::collections::HashSet<Uuid> =
+            sqlx::query_scalar("SELECT uuid FROM MESSAGE WHERE uuid = ANY($1)")
+                .bind(&messages_uuid)
+                .fetch_all(&mut *tx)
+                .await?
+                .into_iter()
+                .collect();
+        let (reads, pending_reads): (Vec<Self>, Vec<Self>) = reads
+            .into_iter()
+            .partition(|r| persisted_messages.contains(&r.message_uuid));
+        let pending_messages = pending_reads.into_iter().map(|r| r.message_uuid).collect();
+
+        let messages_uuid: Vec<Uuid> = reads.iter().map(|r| r.message_uuid).collect();
+        let users_id: Vec<i32> = reads.iter().map(|r| r.user_id).collect();
+        let reads_at: Vec<DateTime<Utc>> = reads.iter().map(|r| r.read_at).collect();
+
+        let updated_records: u64 = sqlx::query(
+            "
+            INSERT INTO MESSAGE_READ(message_uuid, user_id, read_at)
+            SELECT r.message_uuid, r.user_id, r.read_at
+            FROM UNNEST($1::UUID[], $2::INTEGER[], $3::TIMESTAMPTZ[]) AS r(message_uuid, user_id, read_at)
+            INNER JOIN MESSAGE m ON m.uuid = r.message_uuid AND m.author <> r.user_id
+            ON CONFLICT DO NOTHING
+            ",
+        )
+        .bind(&messages_uuid)
+        .bind(&users_id)
+        .bind(&reads_at)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        // The reception status of a message tells whether anyone read it.
+        sqlx::query(
+            "
+            UPDATE MESSAGE SET reception_status = $2
+            WHERE uuid IN (SELECT message_uuid FROM MESSAGE_READ WHERE message_uuid = ANY($1))
+            ",
+        )
+        .bind(&messages_uuid)
+        .bind(crate::ws_message::WsReceptionStatus::Seen)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "
+            INSERT INTO ROOM_READ_POSITION(user_id, room, last_read_at)
+            SELECT r.user_id, m.room, MAX(m.timestamp)
+            FROM UNNEST($1::UUID[], $2::INTEGER[]) AS r(message_uuid, user_id)
+            INNER JOIN MESSAGE m ON m.uuid = r.message_uuid
+            GROUP BY r.user_id, m.room
+            ON CONFLICT (user_id, room) DO UPDATE
+            SET last_read_at = GREATEST(ROOM_READ_POSITION.last_read_at, EXCLUDED.last_read_at)
+            ",
+        )
+        .bind(&messages_uuid)
+        .bind(&users_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let failed_records = reads.len() - updated_records as usize;
+
+        sqlx::query(
+            "
+        INSERT INTO PROCESS_REPORT(process_id, successfull_records, failed_records)
+        VALUES($1, $2, $3)
+        ",
+        )
+        .bind(crate::async_message::AsyncQueue::MessagesSeen)
+        .bind(updated_records as i64)
+        .bind(failed_records as i64)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(pending_messages)
+    }
+
+    /// Returns the read position of a user in a room, the timestamp of the
+    /// latest message they read in it, if any.
+    ///
+    /// # Arguments
+    ///
+    /// - user_id : The ID of the user.
+    /// - room : The room name.
+    /// - pool : The connection pool.
+    pub async fn get_read_position(
+        user_id: i32,
+        room: &str,
+        pool: &sqlx::PgPool,
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT last_read_at FROM ROOM_READ_POSITION WHERE user_id = $1 AND room = $2",
+        )
+        .bind(user_id)
+        .bind(room)
+        .fetch_optional(pool)
+        .await
+    }
+}
+
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+impl MessageReader {
+    /// Returns the users that read a message, from the first to the latest.
+    ///
+    /// # Arguments
+    ///
+    /// - message_uuid : The UUID of the message.
+    /// - pool : The connection pool.
+    pub async fn get_for_message(
+        message_uuid: &Uuid,
+        pool: &sqlx::PgPool,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as(
+            "
+            SELECT c.*, r.read_at FROM MESSAGE_READ r INNER JOIN CHATTER c ON r.user_id = c.id
+            WHERE r.message_uuid = $1
+            ORDER BY r.read_at ASC
+            ",
+        )
+        .bind(message_uuid)
+        .fetch_all(pool)
+        .await
+    }
+}
+
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+impl RoomUnread {
+    /// Returns the rooms in which a user has unread messages.
+    ///
+    /// Only the rooms in which the user already read a message and that they
+    /// can still access are counted, the direct conversations being listed
+    /// along with their own unread count.
+    ///
+    /// # Arguments
+    ///
+    /// - user_id : The ID of the user.
+    /// - pool : The connection pool.
+    pub async fn get_for_user(user_id: i32, pool: &sqlx::PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as(
+            "
+            SELECT p.room, COUNT(*) AS unread FROM ROOM_READ_POSITION p
+            INNER JOIN MESSAGE m ON m.room = p.room
+                AND m.timestamp > p.last_read_at
+                AND m.author <> p.user_id
+                AND NOT m.is_deleted
+            WHERE p.user_id = $1 AND p.room NOT LIKE 'dm:%' AND can_access_room(p.room, p.user_id)
+            GROUP BY p.room
+            ORDER BY p.room
+            ",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+    }
+}
@@ -0,0 +1,79 @@
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+//! A message revision is a former version of a message, kept whenever its
+//! author edits it.
+//!
+//! The revisions of a message constitute its edit history, which can be
+//! consulted by the moderators.
+
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A former version of a message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(
+    any(feature = "back", feature = "cli", feature = "async"),
+    derive(sqlx::FromRow)
+)]
+pub struct MessageRevision {
+    /// The ID of the revision.
+    pub id: i32,
+    /// The UUID of the edited message.
+    pub message_uuid: Uuid,
+    /// The content of the message before it got edited.
+    pub content: String,
+    /// Since when the message had this content.
+    pub revised_at: DateTime<Utc>,
+}
+
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+impl MessageRevision {
+    /// Returns the revisions of a message, from the oldest to the latest.
+    ///
+    /// # Arguments
+    ///
+    /// - message_uuid : The UUID of the message.
+    /// - pool : The connection pool.
+    pub async fn get_for_message(
+        message_uuid: &Uuid,
+        pool: &sqlx::PgPool,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT * FROM MESSAGE_REVISION WHERE message_uuid = $1 ORDER BY revised_at ASC, id ASC",
+        )
+        .bind(message_uuid)
+        .fetch_all(pool)
+        .await
+    }
+}
+
+/// An edition of a message, to be applied asynchronously.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Display)]
+#[display("[{uuid}]")]
+pub struct MessageEdition {
+    /// The UUID of the edited message.
+    pub uuid: Uuid,
+    /// The new content of the message.
+    pub content: String,
+    /// When the message has been edited.
+    pub edited_at: DateTime<Utc>,
+}
+
+impl MessageEdition {
+    /// Creates a new edition of a message, happening now.
+    ///
+    /// # Arguments
+    ///
+    /// - uuid : The UUID of the edited message.
+    /// - content : The new content of the message.
+    pub fn new(uuid: Uuid, content: String) -> Self {
+        Self {
+            uuid,
+            content,
+            edited_at: Utc::now(),
+        }
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+//! Notifications are kept in the inbox of a user whenever another user
+//! mentions them in a message, with `@login`.
+//!
+//! They are pushed to the user's live sockets when created, and stay unread
+//! until the user marks them as read.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::user::PartialUser;
+
+/// The maximum number of users that can be mentioned by a single message.
+const MAX_MENTIONS: usize = 10;
+
+/// The number of notifications returned by the inbox.
+pub const INBOX_SIZE: i64 = 50;
+
+/// A notification of a user mentioned in a message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(
+    any(feature = "back", feature = "cli", feature = "async"),
+    derive(sqlx::FromRow)
+)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    /// The ID of the notification.
+    #[cfg_attr(
+        any(feature = "back", feature = "cli", feature = "async"),
+        sqlx(rename = "notification_id")
+    )]
+    pub id: i32,
+    /// The ID of the user notified.
+    pub user_id: i32,
+    /// The UUID of the message mentioning the user.
+    pub message_uuid: Uuid,
+    /// The room of the message.
+    pub room: String,
+    /// The content of the message when the user got mentioned.
+    pub content: String,
+    /// The author of the message.
+    #[cfg_attr(
+        any(feature = "back", feature = "cli", feature = "async"),
+        sqlx(flatten)
+    )]
+    pub author: PartialUser,
+    /// When the user got mentioned.
+    pub created_at: DateTime<Utc>,
+    /// When the user read the notification, if they did.
+    pub read_at: Option<DateTime<Utc>>,
+}
+
+/// The latest notifications of a user.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationInbox {
+    /// The latest notifications, from the latest to the oldest.
+    pub notifications: Vec<Notification>,
+    /// The number of notifications the user hasn't read yet.
+    pub unread: i64,
+}
+
+/// Returns the logins mentioned in the content of a message.
+///
+/// A mention is an `@` followed by a login, that isn't preceded by a word
+/// character, so that email addresses aren't considered as mentions.
+///
+/// # Arguments
+///
+/// - content : The content of the message.
+pub fn mentioned_logins(content: &str) -> Vec<String> {
+    let is_login_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut logins: Vec<String> = Vec::new();
+    let mut previous: Option<char> = None;
+    for (i, c) in content.char_indices() {
+        if c == '@' && !previous.is_some_and(is_login_char) {
+            let login: String = content[i + 1..]
+                .chars()
+                .take_while(|c| is_login_char(*c))
+                .collect();
+            if (3..=32).contains(&login.len()) && !logins.contains(&login) {
+                logins.push(login);
+            }
+        }
+        previous = Some(c);
+    }
+    logins.truncate(MAX_MENTIONS);
+    logins
+}
+
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+impl Notification {
+    /// Notifies the users mentioned in a message.
+    ///
+    /// Only the existing and authorized users that can access the room are
+    /// notified, the author of the message being never notified of their own
+    /// mentions.
+    ///
+    /// Returns the notifications created.
+    ///
+    /// # Arguments
+    ///
+    /// - logins : The logins mentioned.
+    /// - message_uuid : The UUID of the message.
+    /// - room : The room of the message.
+    /// - content : The content of the message.
+    /// - author_id : The ID of the message's author.
+    /// - pool : The connection pool.
+    pub async fn notify_mentions(
+        logins: &[String],
+        message_uuid: &Uuid,
+        room: &str,
+        content: &str,
+        author_id: i32,
+        pool: &sqlx::PgPool,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as(
+            "
+            WITH inserted AS (
+                INSERT INTO NOTIFICATION(user_id, author_id, message_uuid, room, content)
+                SELECT c.id, $2, $3, $4, $5 FROM CHATTER c
+                WHERE c.login = ANY($1) AND c.id <> $2 AND c.is_authorized
+                    AND can_access_room($4, c.id)
+                ON CONFLICT DO NOTHING
+                RETURNING *
+            )
+            SELECT i.id AS notification_id, i.user_id, i.message_uuid, i.room, i.content,
+                i.created_at, i.read_at, c.*
+            FROM inserted i INNER JOIN CHATTER c ON i.author_id = c.id
+            ",
+        )
+        .bind(logins)
+        .bind(author_id)
+        .bind(message_uuid)
+        .bind(room)
+        .bind(content)
+        .fetch_all(pool)
+        .await
+    }
+}
+
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+impl NotificationInbox {
+    /// Returns the latest notifications of a user, along with their number of
+    /// unread ones.
+    ///
+    /// # Arguments
+    ///
+    /// - user_id : The ID of the user.
+    /// - pool : The connection pool.
+    pub async fn get_for_user(user_id: i32, pool: &sqlx::PgPool) -> Result<Self, sqlx::Error> {
+        let notifications: Vec<Notification> = sqlx::query_as(
+            "
+            SELECT n.id AS notification_id, n.user_id, n.message_uuid, n.room, n.content,
+                n.created_at, n.read_at, c.*
+            FROM NOTIFICATION n INNER JOIN CHATTER c ON n.author_id = c.id
+            WHERE n.user_id = $1
+            ORDER BY n.created_at DESC, n.id DESC
+            LIMIT $2
+            ",
+        )
+        .bind(user_id)
+        .bind(INBOX_SIZE)
+        .fetch_all(pool)
+        .await?;
+        let (unread,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM NOTIFICATION WHERE user_id = $1 AND read_at IS NULL",
+        )
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+        Ok(Self {
+            notifications,
+            unread,
+        })
+    }
+
+    /// Marks the notifications of a user as read.
+    ///
+    /// Returns the number of notifications that were unread.
+    ///
+    /// # Arguments
+    ///
+    /// - user_id : The ID of the user.
+    /// - notification_id : The notification to mark as read, or none to mark
+    ///   them all.
+    /// - pool : The connection pool.
+    pub async fn mark_as_read(
+        user_id: i32,
+        notification_id: Option<i32>,
+        pool: &sqlx::PgPool,
+    ) -> Result<u64, sqlx::Error> {
+        Ok(sqlx::query(
+            "
+            UPDATE NOTIFICATION SET read_at = CURRENT_TIMESTAMP
+            WHERE user_id = $1 AND ($2::INTEGER IS NULL OR id = $2) AND read_at IS NULL
+            ",
+        )
+        .bind(user_id)
+        .bind(notification_id)
+        .execute(pool)
+        .await?
+        .rows_affected())
+    }
+}
@@ -55,3 +55,13 @@ pub async fn get_async_pool() -> Result<redis::aio::MultiplexedConnection, redis
     let client = redis::Client::open(format!("redis://{redis_host}:{redis_port}/2"))?;
     client.get_multiplexed_async_connection().await
 }
+
+#[cfg(feature = "back")]
+/// Returns a Redis connection used to listen to the messages published on
+/// Redis channels.
+pub async fn get_pubsub() -> Result<redis::aio::PubSub, redis::RedisError> {
+    let redis_host = std::env::var("REDIS_HOST").expect("No redis host defined in .env");
+    let redis_port = std::env::var("REDIS_PORT").expect("No redis port defined in .env");
+    let client = redis::Client::open(format!("redis://{redis_host}:{redis_port}"))?;
+    client.get_async_pubsub().await
+}
@@ -0,0 +1,207 @@
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+//! Reactions are emojis that users can add under the messages of a room.
+//!
+//! A user can react once with each emoji on a given message, and the reactions
+//! are sent to the clients aggregated by emoji.
+
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// The maximum number of characters of a reaction.
+///
+/// Some emojis are made of several characters, such as flags or skin tones.
+const MAX_EMOJI_CHARS: usize = 8;
+
+/// A reaction of a user on a message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Display)]
+#[display("[{message_uuid}:{user_id}:{emoji}]")]
+pub struct Reaction {
+    /// The UUID of the message reacted to.
+    pub message_uuid: Uuid,
+    /// The ID of the user reacting.
+    ///
+    /// Filled server side.
+    pub user_id: i32,
+    /// The emoji of the reaction.
+    pub emoji: String,
+}
+
+impl Reaction {
+    /// Whether the emoji of the reaction is acceptable.
+    ///
+    /// Only short and non alphanumeric contents are accepted.
+    pub fn is_valid(&self) -> bool {
+        let chars = self.emoji.chars().count();
+        (1..=MAX_EMOJI_CHARS).contains(&chars)
+            && !self
+                .emoji
+                .chars()
+                .any(|c| c.is_ascii() || c.is_alphanumeric() || c.is_whitespace())
+    }
+}
+
+/// A change on the reactions of a message, to be persisted asynchronously.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Display)]
+pub enum ReactionUpdate {
+    /// The reaction has been added.
+    Add(Reaction),
+    /// The reaction has been removed.
+    Remove(Reaction),
+}
+
+/// The reactions of a message with a given emoji.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ReactionCount {
+    /// The emoji of the reactions.
+    pub emoji: String,
+    /// The number of users that reacted with this emoji.
+    pub count: i64,
+    /// The IDs of the users that reacted with this emoji.
+    pub user_ids: Vec<i32>,
+}
+
+/// The aggregated reactions of a message, as read from the database.
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct MessageReactionCount {
+    /// The UUID of the message.
+    pub message_uuid: Uuid,
+    /// The emoji of the reactions.
+    pub emoji: String,
+    /// The number of users that reacted with this emoji.
+    pub count: i64,
+    /// The IDs of the users that reacted with this emoji.
+    pub user_ids: Vec<i32>,
+}
+
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+impl MessageReactionCount {
+    /// Returns the aggregated reactions of the given messages.
+    ///
+    /// # Arguments
+    ///
+    /// - messages_uuid : The UUIDs of the messages.
+    /// - pool : The connection pool.
+    pub async fn get_for_messages(
+        messages_uuid: &[Uuid],
+        pool: &sqlx::PgPool,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as(
+            "
+            SELECT message_uuid, emoji, COUNT(*) AS count, ARRAY_AGG(user_id ORDER BY created_at) AS user_ids
+            FROM MESSAGE_REACTION
+            WHERE message_uuid = ANY($1)
+            GROUP BY message_uuid, emoji
+            ORDER BY MIN(created_at)
+            ",
+        )
+        .bind(messages_uuid)
+        .fetch_all(pool)
+        .await
+    }
+}
+
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+impl From<MessageReactionCount> for ReactionCount {
+    fn from(value: MessageReactionCount) -> Self {
+        Self {
+            emoji: value.emoji,
+            count: value.count,
+            user_ids: value.user_ids,
+        }
+    }
+}
+
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+impl ReactionUpdate {
+    /// Persists a list of reaction updates, in the order they have been emitted.
+    ///
+    /// The updates on messages that aren't persisted yet are left aside, the
+    /// UUIDs of these messages being returned so that their updates can be
+    /// persisted later on.
+    ///
+    /// # Arguments
+    ///
+    /// - updates : The updates to persist.
+    /// - pool : The connection pool.
+    pub async fn persist_async(
+        updates: Vec<Self>,
+        pool: &sqlx::PgPool,
+    ) -> Result<std::collections::HashSet<Uuid>, sqlx::Error> {
+        let mut successfull_records = 0i64;
+        let mut failed_records = 0i64;
+
+        let mut tx = pool.begin().await?;
+
+        let messages_uuid: Vec<Uuid> = updates
+            .iter()
+            .map(
+                |(ReactionUpdate::Add(reaction) | ReactionUpdate::Remove(reaction))| {
+                    reaction.message_uuid
+                },
+            )
+            .collect();
+        let persisted_messages: std::collections::HashSet<Uuid> =
+            sqlx::query_scalar("SELECT uuid FROM MESSAGE WHERE uuid = ANY($1)")
+                .bind(&messages_uuid)
+                .fetch_all(&mut *tx)
+                .await?
+                .into_iter()
+                .collect();
+        let mut pending_messages = std::collections::HashSet::new();
+
+        for update in updates {
+            let (ReactionUpdate::Add(reaction) | ReactionUpdate::Remove(reaction)) = &update;
+            if !persisted_messages.contains(&reaction.message_uuid) {
+                pending_messages.insert(reaction.message_uuid);
+                continue;
+            }
+            let query = match &update {
+                // Reactions on messages that have been deleted in the meantime are ignored.
+                ReactionUpdate::Add(_) => {
+                    "
+                    INSERT INTO MESSAGE_REACTION(message_uuid, user_id, emoji)
+                    SELECT $1, $2, $3 WHERE EXISTS (SELECT 1 FROM MESSAGE WHERE uuid = $1)
+                    ON CONFLICT DO NOTHING
+                    "
+                }
+                ReactionUpdate::Remove(_) => {
+                    "DELETE FROM MESSAGE_REACTION WHERE message_uuid = $1 AND user_id = $2 AND emoji = $3"
+                }
+            };
+            let (ReactionUpdate::Add(reaction) | ReactionUpdate::Remove(reaction)) = update;
+            let updated_records = sqlx::query(query)
+                .bind(reaction.message_uuid)
+                .bind(reaction.user_id)
+                .bind(reaction.emoji)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+            if updated_records == 0 {
+                failed_records += 1;
+            } else {
+                successfull_records += 1;
+            }
+        }
+
+        sqlx::query(
+            "
+        INSERT INTO PROCESS_REPORT(process_id, successfull_records, failed_records) 
+        VALUES($1, $2, $3)
+        ",
+        )
+        .bind(crate::async_message::AsyncQueue::PersistReaction)
+        .bind(successfull_records)
+        .bind(failed_records)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(pending_messages)
+    }
+}
@@ -3,12 +3,23 @@
 
 //! Rooms are where user chats with each others.
 //!
-//! They are persisted within redis as "room_name=redis_key", so that any user
-//! that reconnects retieve the messages sent before he joined.
+//! They are stored in the `ROOM` table along with their metadata, such as
+//! their owner and topic. A room is only registered once created, the rooms
+//! in which messages had been posted beforehand being registered without any
+//! owner.
+//!
+//! Private rooms can only be accessed by their owner and members, while the
+//! unlisted ones can be joined by anyone knowing their name.
 
 use crate::common::limited_chars_checker;
+use crate::profile::Profile;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use validator::Validate;
 
+/// The maximum number of rooms returned when listing them.
+pub const ROOM_LIST_SIZE: i64 = 20;
+
 #[derive(Debug, Validate)]
 pub struct RoomNameValidator {
     #[validate(
@@ -23,3 +34,374 @@ impl From<String> for RoomNameValidator {
         Self { name: value }
     }
 }
+
+/// The activity of a room on a server instance.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomActivity {
+    /// The room name.
+    pub name: String,
+    /// The number of sockets connected to the room.
+    pub sockets: usize,
+    /// The number of sockets subscribed to the room's messages.
+    pub subscribers: usize,
+    /// The number of distinct users connected to the room.
+    pub members: usize,
+}
+
+/// Who can find and access a room.
+#[derive(
+    Debug, Default, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Hash, derive_more::Display,
+)]
+#[cfg_attr(
+    any(feature = "back", feature = "cli", feature = "async"),
+    derive(sqlx::Type)
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[repr(i32)]
+pub enum RoomVisibility {
+    /// Anyone can find and join the room.
+    #[default]
+    Public = 1,
+    /// Anyone can join the room, but it isn't listed.
+    Unlisted = 2,
+    /// Only the owner and the members of the room can find and join it.
+    Private = 3,
+}
+
+/// A room, along with its metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(
+    any(feature = "back", feature = "cli", feature = "async"),
+    derive(sqlx::FromRow)
+)]
+#[serde(rename_all = "camelCase")]
+pub struct Room {
+    /// The room name.
+    pub name: String,
+    /// The ID of the user that created the room, if any.
+    pub owner_id: Option<i32>,
+    /// The topic currently discussed in the room.
+    pub topic: Option<String>,
+    /// The description of the room.
+    pub description: Option<String>,
+    /// Who can find and access the room.
+    pub visibility: RoomVisibility,
+    /// The number of seconds a user has to wait between two messages, if the
+    /// slow mode is on.
+    pub slow_mode: Option<i32>,
+    /// Whether only the moderators can post in the room.
+    pub is_announcement: bool,
+    /// When the room has been created.
+    pub created_at: DateTime<Utc>,
+}
+
+impl Room {
+    /// Whether a user can update the metadata of the room.
+    ///
+    /// Only the owner of a room and the moderators can update it.
+    ///
+    /// # Arguments
+    ///
+    /// - user_id : The ID of the user.
+    /// - profile : The profile of the user.
+    pub fn can_be_updated_by(&self, user_id: i32, profile: Profile) -> bool {
+        profile != Profile::User || self.owner_id == Some(user_id)
+    }
+
+    /// Whether a user can post in the room, only the moderators being
+    /// allowed to post in the announcement rooms.
+    ///
+    /// # Arguments
+    ///
+    /// - profile : The profile of the user.
+    pub fn can_post(&self, profile: Profile) -> bool {
+        !self.is_announcement || profile >= Profile::Moderator
+    }
+
+    /// Returns the number of seconds a user has to wait between two messages,
+    /// if the slow mode applies to them.
+    ///
+    /// The moderators aren't subject to the slow mode.
+    ///
+    /// # Arguments
+    ///
+    /// - profile : The profile of the user.
+    pub fn slow_mode_for(&self, profile: Profile) -> Option<i32> {
+        self.slow_mode.filter(|_| profile < Profile::Moderator)
+    }
+}
+
+/// The rules restricting who can post in a room, and how often.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomPostingRules {
+    /// The number of seconds a user has to wait between two messages, none
+    /// turning the slow mode off.
+    #[validate(range(min = 1, max = 21600))]
+    pub slow_mode: Option<i32>,
+    /// Whether only the moderators can post in the room.
+    pub is_announcement: bool,
+}
+
+/// Structure used only to create new rooms.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct InsertableRoom {
+    /// The room name.
+    #[validate(
+        length(min = 1, max = 128),
+        custom(function = "limited_chars_checker", code = "limited_chars")
+    )]
+    pub name: String,
+    /// The topic of the room.
+    #[validate(length(max = 256))]
+    pub topic: Option<String>,
+    /// The description of the room.
+    #[validate(length(max = 1024))]
+    pub description: Option<String>,
+    /// Who can find and access the room.
+    #[serde(default)]
+    pub visibility: RoomVisibility,
+}
+
+/// The updatable metadata of a room.
+///
+/// The topic and the description are replaced, an empty or missing one
+/// clearing it, while a missing visibility is left unchanged.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdatableRoom {
+    /// The topic of the room.
+    #[validate(length(max = 256))]
+    pub topic: Option<String>,
+    /// The description of the room.
+    #[validate(length(max = 1024))]
+    pub description: Option<String>,
+    /// Who can find and access the room, left unchanged if none is given.
+    #[serde(default)]
+    pub visibility: Option<RoomVisibility>,
+}
+
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+impl Room {
+    /// Returns a room from its name.
+    ///
+    /// # Arguments
+    ///
+    /// - name : The room name.
+    /// - pool : The connection pool.
+    pub async fn find_by_name(
+        name: &str,
+        pool: &sqlx::PgPool,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM ROOM WHERE name = $1")
+            .bind(name)
+            .fetch_optional(pool)
+            .await
+    }
+
+    /// Whether a user can access a room.
+    ///
+    /// The rooms that aren't registered yet can be accessed by anyone.
+    ///
+    /// # Arguments
+    ///
+    /// - name : The room name.
+    /// - user_id : The ID of the user.
+    /// - pool : The connection pool.
+    pub async fn can_access(
+        name: &str,
+        user_id: i32,
+        pool: &sqlx::PgPool,
+    ) -> Result<bool, sqlx::Error> {
+        let (can_access,): (bool,) = sqlx::query_as("SELECT can_access_room($1, $2)")
+            .bind(name)
+            .bind(user_id)
+            .fetch_one(pool)
+            .await?;
+        Ok(can_access)
+    }
+
+    /// Returns the rooms listed to a user whose name starts with the given
+    /// prefix, whatever the case, in the alphabetical order.
+    ///
+    /// Besides the public rooms, a user is listed the rooms they own or are a
+    /// member of.
+    ///
+    /// # Arguments
+    ///
+    /// - prefix : The beginning of the room names.
+    /// - user_id : The ID of the user.
+    /// - limit : The maximum number of rooms returned.
+    /// - pool : The connection pool.
+    pub async fn search(
+        prefix: &str,
+        user_id: i32,
+        limit: i64,
+        pool: &sqlx::PgPool,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as(
+            "
+            SELECT * FROM ROOM r
+            WHERE left(lower(r.name), length($1)) = lower($1)
+                AND (r.visibility = $2 OR r.owner_id = $3 OR EXISTS (
+                    SELECT 1 FROM ROOM_MEMBER m
+                    WHERE m.room = r.name AND m.user_id = $3 AND m.joined_at IS NOT NULL
+                ))
+            ORDER BY r.name
+            LIMIT $4
+            ",
+        )
+        .bind(prefix)
+        .bind(RoomVisibility::Public)
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Updates the metadata of a room.
+    ///
+    /// Returns the room updated, if it exists.
+    ///
+    /// # Arguments
+    ///
+    /// - name : The room name.
+    /// - room : The new metadata of the room.
+    /// - pool : The connection pool.
+    pub async fn update(
+        name: &str,
+        room: &UpdatableRoom,
+        pool: &sqlx::PgPool,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as(
+            "
+            UPDATE ROOM SET topic = NULLIF($2, ''), description = NULLIF($3, ''),
+                visibility = COALESCE($4, visibility)
+            WHERE name = $1
+            RETURNING *
+            ",
+        )
+        .bind(name)
+        .bind(&room.topic)
+        .bind(&room.description)
+        .bind(room.visibility)
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Transfers the ownership of a room to another user.
+    ///
+    /// The former owner stays a member of the room.
+    ///
+    /// Returns the room updated, if it exists.
+    ///
+    /// # Arguments
+    ///
+    /// - name : The room name.
+    /// - owner_id : The ID of the new owner.
+    /// - pool : The connection pool.
+    pub async fn transfer_ownership(
+        name: &str,
+        owner_id: i32,
+        pool: &sqlx::PgPool,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        sqlx::query(
+            "
+            INSERT INTO ROOM_MEMBER(room, user_id, joined_at)
+            SELECT name, owner_id, CURRENT_TIMESTAMP FROM ROOM
+            WHERE name = $1 AND owner_id IS NOT NULL AND owner_id <> $2
+            ON CONFLICT (room, user_id) DO UPDATE
+            SET joined_at = COALESCE(ROOM_MEMBER.joined_at, EXCLUDED.joined_at)
+            ",
+        )
+        .bind(name)
+        .bind(owner_id)
+        .execute(&mut *tx)
+        .await?;
+        let room = sqlx::query_as("UPDATE ROOM SET owner_id = $2 WHERE name = $1 RETURNING *")
+            .bind(name)
+            .bind(owner_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(room)
+    }
+
+    /// Sets the topic of a room, leaving its other metadata unchanged.
+    ///
+    /// Returns the room updated, if it exists.
+    ///
+    /// # Arguments
+    ///
+    /// - name : The room name.
+    /// - topic : The new topic, none or an empty one clearing it.
+    /// - pool : The connection pool.
+    pub async fn set_topic(
+        name: &str,
+        topic: Option<&str>,
+        pool: &sqlx::PgPool,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as("UPDATE ROOM SET topic = NULLIF($2, '') WHERE name = $1 RETURNING *")
+            .bind(name)
+            .bind(topic)
+            .fetch_optional(pool)
+            .await
+    }
+
+    /// Sets the posting rules of a room.
+    ///
+    /// Returns the room updated, if it exists.
+    ///
+    /// # Arguments
+    ///
+    /// - name : The room name.
+    /// - rules : The new posting rules.
+    /// - pool : The connection pool.
+    pub async fn set_posting_rules(
+        name: &str,
+        rules: &RoomPostingRules,
+        pool: &sqlx::PgPool,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as(
+            "UPDATE ROOM SET slow_mode = $2, is_announcement = $3 WHERE name = $1 RETURNING *",
+        )
+        .bind(name)
+        .bind(rules.slow_mode)
+        .bind(rules.is_announcement)
+        .fetch_optional(pool)
+        .await
+    }
+}
+
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+impl InsertableRoom {
+    /// Inserts the room in the database.
+    ///
+    /// # Arguments
+    ///
+    /// - owner_id : The user creating the room, if any.
+    /// - pool : The connection pool.
+    pub async fn insert(
+        &self,
+        owner_id: Option<i32>,
+        pool: &sqlx::PgPool,
+    ) -> Result<Room, sqlx::Error> {
+        sqlx::query_as(
+            "
+            INSERT INTO ROOM(name, owner_id, topic, description, visibility)
+            VALUES ($1, $2, NULLIF($3, ''), NULLIF($4, ''), $5)
+            RETURNING *
+            ",
+        )
+        .bind(&self.name)
+        .bind(owner_id)
+        .bind(&self.topic)
+        .bind(&self.description)
+        .bind(self.visibility)
+        .fetch_one(pool)
+        .await
+    }
+}
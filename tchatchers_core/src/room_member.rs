@@ -0,0 +1,178 @@
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+//! The members of a room are the users allowed to access it when it is
+//! private.
+//!
+//! Users become members by accepting the invitations of the room's owner,
+//! the invitations being kept as members that haven't joined yet.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::user::PartialUser;
+
+/// A member of a room, or a user invited to become one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(
+    any(feature = "back", feature = "cli", feature = "async"),
+    derive(sqlx::FromRow)
+)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomMember {
+    /// The member.
+    #[cfg_attr(
+        any(feature = "back", feature = "cli", feature = "async"),
+        sqlx(flatten)
+    )]
+    pub user: PartialUser,
+    /// The ID of the user that invited the member, if any.
+    pub invited_by: Option<i32>,
+    /// When the member has been invited.
+    pub invited_at: DateTime<Utc>,
+    /// When the member accepted the invitation, if they did.
+    pub joined_at: Option<DateTime<Utc>>,
+}
+
+/// An invitation to become a member of a room.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(
+    any(feature = "back", feature = "cli", feature = "async"),
+    derive(sqlx::FromRow)
+)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomInvitation {
+    /// The room name.
+    pub room: String,
+    /// The ID of the user that sent the invitation, if any.
+    pub invited_by: Option<i32>,
+    /// When the invitation has been sent.
+    pub invited_at: DateTime<Utc>,
+}
+
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+impl RoomMember {
+    /// Returns the members of a room along with the users invited, from the
+    /// oldest member to the latest invited.
+    ///
+    /// # Arguments
+    ///
+    /// - room : The room name.
+    /// - pool : The connection pool.
+    pub async fn get_for_room(room: &str, pool: &sqlx::PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as(
+            "
+            SELECT c.*, m.invited_by, m.invited_at, m.joined_at
+            FROM ROOM_MEMBER m INNER JOIN CHATTER c ON m.user_id = c.id
+            WHERE m.room = $1
+            ORDER BY m.joined_at ASC NULLS LAST, m.invited_at ASC
+            ",
+        )
+        .bind(room)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Invites a user to become a member of a room.
+    ///
+    /// Returns whether the user has been invited, which isn't the case if
+    /// they already are a member or have already been invited.
+    ///
+    /// # Arguments
+    ///
+    /// - room : The room name.
+    /// - user_id : The ID of the user invited.
+    /// - invited_by : The ID of the user sending the invitation, if any.
+    /// - pool : The connection pool.
+    pub async fn invite(
+        room: &str,
+        user_id: i32,
+        invited_by: Option<i32>,
+        pool: &sqlx::PgPool,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "
+            INSERT INTO ROOM_MEMBER(room, user_id, invited_by) VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING
+            ",
+        )
+        .bind(room)
+        .bind(user_id)
+        .bind(invited_by)
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Accepts the invitation of a user to a room.
+    ///
+    /// Returns whether the user had a pending invitation.
+    ///
+    /// # Arguments
+    ///
+    /// - room : The room name.
+    /// - user_id : The ID of the user invited.
+    /// - pool : The connection pool.
+    pub async fn accept(
+        room: &str,
+        user_id: i32,
+        pool: &sqlx::PgPool,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "
+            UPDATE ROOM_MEMBER SET joined_at = CURRENT_TIMESTAMP
+            WHERE room = $1 AND user_id = $2 AND joined_at IS NULL
+            ",
+        )
+        .bind(room)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Removes a member from a room, or declines their invitation.
+    ///
+    /// Returns whether the user was a member or invited.
+    ///
+    /// # Arguments
+    ///
+    /// - room : The room name.
+    /// - user_id : The ID of the user removed.
+    /// - pool : The connection pool.
+    pub async fn remove(
+        room: &str,
+        user_id: i32,
+        pool: &sqlx::PgPool,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM ROOM_MEMBER WHERE room = $1 AND user_id = $2")
+            .bind(room)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+impl RoomInvitation {
+    /// Returns the pending invitations of a user, from the latest to the
+    /// oldest.
+    ///
+    /// # Arguments
+    ///
+    /// - user_id : The ID of the user.
+    /// - pool : The connection pool.
+    pub async fn get_for_user(user_id: i32, pool: &sqlx::PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as(
+            "
+            SELECT room, invited_by, invited_at FROM ROOM_MEMBER
+            WHERE user_id = $1 AND joined_at IS NULL
+            ORDER BY invited_at DESC
+            ",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+    }
+}
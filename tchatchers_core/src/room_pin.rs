@@ -0,0 +1,118 @@
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+//! Pinned messages are kept at the top of their room.
+//!
+//! Messages can be pinned by the owner of their room and the moderators, and
+//! aren't pruned by the retention policy of the room as long as they are
+//! pinned.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::ws_message::WsMessageContent;
+
+/// A message pinned in its room.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(
+    any(feature = "back", feature = "cli", feature = "async"),
+    derive(sqlx::FromRow)
+)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomPin {
+    /// The message pinned.
+    #[cfg_attr(
+        any(feature = "back", feature = "cli", feature = "async"),
+        sqlx(flatten)
+    )]
+    pub message: WsMessageContent,
+    /// The ID of the user that pinned the message, if any.
+    pub pinned_by: Option<i32>,
+    /// When the message has been pinned.
+    pub pinned_at: DateTime<Utc>,
+}
+
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+impl RoomPin {
+    /// Returns the messages pinned in a room, from the latest pinned to the
+    /// oldest.
+    ///
+    /// # Arguments
+    ///
+    /// - room : The room name.
+    /// - pool : The connection pool.
+    pub async fn get_for_room(room: &str, pool: &sqlx::PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as(
+            "
+            SELECT m.*, c.*, p.pinned_by, p.pinned_at FROM ROOM_PIN p
+            INNER JOIN MESSAGE m ON m.uuid = p.message_uuid
+            INNER JOIN CHATTER c ON m.author = c.id
+            WHERE p.room = $1
+            ORDER BY p.pinned_at DESC
+            ",
+        )
+        .bind(room)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Pins a message of a room.
+    ///
+    /// Returns the pin, unless the message isn't found in the room, has been
+    /// deleted or is already pinned.
+    ///
+    /// # Arguments
+    ///
+    /// - room : The room name.
+    /// - message_uuid : The UUID of the message.
+    /// - pinned_by : The ID of the user pinning the message.
+    /// - pool : The connection pool.
+    pub async fn pin(
+        room: &str,
+        message_uuid: &uuid::Uuid,
+        pinned_by: i32,
+        pool: &sqlx::PgPool,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as(
+            "
+            WITH inserted AS (
+                INSERT INTO ROOM_PIN(message_uuid, room, pinned_by)
+                SELECT uuid, room, $3 FROM MESSAGE
+                WHERE uuid = $2 AND room = $1 AND NOT is_deleted
+                ON CONFLICT DO NOTHING
+                RETURNING *
+            )
+            SELECT m.*, c.*, p.pinned_by, p.pinned_at FROM inserted p
+            INNER JOIN MESSAGE m ON m.uuid = p.message_uuid
+            INNER JOIN CHATTER c ON m.author = c.id
+            ",
+        )
+        .bind(room)
+        .bind(message_uuid)
+        .bind(pinned_by)
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Unpins a message of a room.
+    ///
+    /// Returns whether the message was pinned.
+    ///
+    /// # Arguments
+    ///
+    /// - room : The room name.
+    /// - message_uuid : The UUID of the message.
+    /// - pool : The connection pool.
+    pub async fn unpin(
+        room: &str,
+        message_uuid: &uuid::Uuid,
+        pool: &sqlx::PgPool,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM ROOM_PIN WHERE room = $1 AND message_uuid = $2")
+            .bind(room)
+            .bind(message_uuid)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+}
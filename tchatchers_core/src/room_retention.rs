@@ -0,0 +1,218 @@
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+//! The retention policies define how long the messages of a room are kept.
+//!
+//! Each room can have its own policy, the other ones following the default
+//! policy. The messages past the retention of their room are pruned by a
+//! scheduled sweep, which reports the number of messages pruned per room.
+
+use serde::{Deserialize, Serialize};
+
+/// How long the messages of a room are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RetentionPolicy {
+    /// Only the given number of latest messages are kept.
+    MessageCount(i32),
+    /// Only the messages posted during the given number of days are kept.
+    Age(i32),
+    /// The messages are never pruned.
+    Unlimited,
+}
+
+impl std::fmt::Display for RetentionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MessageCount(count) => write!(f, "the {count} latest messages"),
+            Self::Age(days) => write!(f, "the messages of the last {days} days"),
+            Self::Unlimited => write!(f, "every message"),
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// Returns the policy stored in the given columns, if any.
+    ///
+    /// # Arguments
+    ///
+    /// - policy : The kind of policy.
+    /// - value : The value of the policy, if any.
+    fn from_columns(policy: Option<i32>, value: Option<i32>) -> Option<Self> {
+        match (policy?, value) {
+            (1, Some(count)) => Some(Self::MessageCount(count)),
+            (2, Some(days)) => Some(Self::Age(days)),
+            (3, _) => Some(Self::Unlimited),
+            _ => None,
+        }
+    }
+
+    /// Returns the columns under which the policy is stored.
+    fn columns(&self) -> (i32, Option<i32>) {
+        match self {
+            Self::MessageCount(count) => (1, Some(*count)),
+            Self::Age(days) => (2, Some(*days)),
+            Self::Unlimited => (3, None),
+        }
+    }
+
+    /// Whether the policy can be applied, which isn't the case when it
+    /// doesn't keep any message.
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Self::MessageCount(value) | Self::Age(value) => *value > 0,
+            Self::Unlimited => true,
+        }
+    }
+}
+
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+impl RetentionPolicy {
+    /// Returns the policy of the rooms without any.
+    ///
+    /// # Arguments
+    ///
+    /// - pool : The connection pool.
+    pub async fn get_default(pool: &sqlx::PgPool) -> Result<Self, sqlx::Error> {
+        let (policy, value): (i32, Option<i32>) =
+            sqlx::query_as("SELECT policy, value FROM DEFAULT_ROOM_RETENTION")
+                .fetch_one(pool)
+                .await?;
+        Self::from_columns(Some(policy), value).ok_or(sqlx::Error::RowNotFound)
+    }
+
+    /// Sets the policy of the rooms without any.
+    ///
+    /// # Arguments
+    ///
+    /// - pool : The connection pool.
+    pub async fn set_default(&self, pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        let (policy, value) = self.columns();
+        sqlx::query(
+            "
+            INSERT INTO DEFAULT_ROOM_RETENTION(policy, value) VALUES ($1, $2)
+            ON CONFLICT (id) DO UPDATE SET policy = EXCLUDED.policy, value = EXCLUDED.value
+            ",
+        )
+        .bind(policy)
+        .bind(value)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns the policy of a room, none meaning that it follows the default
+    /// policy.
+    ///
+    /// # Arguments
+    ///
+    /// - room : The room name.
+    /// - pool : The connection pool.
+    pub async fn get_for_room(
+        room: &str,
+        pool: &sqlx::PgPool,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let columns: Option<(Option<i32>, Option<i32>)> =
+            sqlx::query_as("SELECT retention_policy, retention_value FROM ROOM WHERE name = $1")
+                .bind(room)
+                .fetch_optional(pool)
+                .await?;
+        Ok(columns.and_then(|(policy, value)| Self::from_columns(policy, value)))
+    }
+
+    /// Sets the policy of a room, none making it follow the default policy.
+    ///
+    /// Returns whether the room exists.
+    ///
+    /// # Arguments
+    ///
+    /// - room : The room name.
+    /// - policy : The policy of the room, if any.
+    /// - pool : The connection pool.
+    pub async fn set_for_room(
+        room: &str,
+        policy: Option<Self>,
+        pool: &sqlx::PgPool,
+    ) -> Result<bool, sqlx::Error> {
+        let (policy, value) = policy.map_or((None, None), |policy| {
+            let (policy, value) = policy.columns();
+            (Some(policy), value)
+        });
+        let result = sqlx::query(
+            "UPDATE ROOM SET retention_policy = $2, retention_value = $3 WHERE name = $1",
+        )
+        .bind(room)
+        .bind(policy)
+        .bind(value)
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Prunes the messages of every room past its retention policy.
+    ///
+    /// The rooms that aren't registered, such as the direct conversations,
+    /// follow the default policy. The number of messages pruned is reported
+    /// per room, the sweeps pruning nothing being reported once.
+    ///
+    /// # Arguments
+    ///
+    /// - pool : The connection pool.
+    pub async fn sweep(pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        // The tombstones are left out as they are only kept for their replies,
+        // and the pinned messages are kept until they are unpinned.
+        let expired: Vec<(String, Vec<uuid::Uuid>)> = sqlx::query_as(
+            "
+            WITH policy AS (
+                SELECT m.room,
+                    COALESCE(r.retention_policy, d.policy) AS policy,
+                    CASE WHEN r.retention_policy IS NULL THEN d.value ELSE r.retention_value END AS value
+                FROM (SELECT DISTINCT room FROM MESSAGE) m
+                LEFT JOIN ROOM r ON r.name = m.room
+                CROSS JOIN DEFAULT_ROOM_RETENTION d
+            ), ranked AS (
+                SELECT uuid, room, timestamp,
+                    ROW_NUMBER() OVER (PARTITION BY room ORDER BY timestamp DESC) AS rank
+                FROM MESSAGE WHERE NOT is_deleted
+            )
+            SELECT m.room, ARRAY_AGG(m.uuid) FROM ranked m INNER JOIN policy p ON p.room = m.room
+            WHERE ((p.policy = 1 AND m.rank > p.value)
+                OR (p.policy = 2 AND m.timestamp < CURRENT_TIMESTAMP - make_interval(days => p.value)))
+                AND NOT EXISTS (SELECT 1 FROM ROOM_PIN pin WHERE pin.message_uuid = m.uuid)
+            GROUP BY m.room
+            ",
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let mut reports: Vec<(Option<String>, i64)> = Vec::with_capacity(expired.len());
+        for (room, messages_uuid) in expired {
+            let pruned_records =
+                crate::ws_message::WsMessageContent::delete_or_tombstone(&messages_uuid, &mut tx)
+                    .await?;
+            reports.push((Some(room), pruned_records as i64));
+        }
+        if reports.is_empty() {
+            reports.push((None, 0));
+        }
+
+        for (room, pruned_records) in reports {
+            sqlx::query(
+                "
+            INSERT INTO PROCESS_REPORT(process_id, successfull_records, failed_records, room)
+            VALUES($1, $2, 0, $3)
+            ",
+            )
+            .bind(crate::async_message::AsyncQueue::CleanRoom)
+            .bind(pruned_records)
+            .bind(room)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
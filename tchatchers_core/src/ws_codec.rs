@@ -0,0 +1,280 @@
+//! The codecs used to encode the WS messages exchanged between the client and
+//! the server.
+//!
+//! The codec of a socket is negotiated through the `Sec-WebSocket-Protocol`
+//! header, a socket that doesn't ask for any using JSON.
+
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+use crate::ws_message::WsMessage;
+use derive_more::Display;
+use std::sync::OnceLock;
+
+/// An error met while encoding or decoding a WS message.
+#[derive(Debug, Clone, Display, PartialEq, Eq)]
+pub struct WsCodecError(String);
+
+impl std::error::Error for WsCodecError {}
+
+impl From<serde_json::Error> for WsCodecError {
+    fn from(value: serde_json::Error) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl From<bincode::Error> for WsCodecError {
+    fn from(value: bincode::Error) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl From<rmp_serde::encode::Error> for WsCodecError {
+    fn from(value: rmp_serde::encode::Error) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl From<rmp_serde::decode::Error> for WsCodecError {
+    fn from(value: rmp_serde::decode::Error) -> Self {
+        Self(value.to_string())
+    }
+}
+
+/// A format in which the WS messages can be exchanged.
+pub trait WsCodec {
+    /// The name of the subprotocol identifying the codec.
+    fn protocol(&self) -> &'static str;
+
+    /// Encodes a message into a frame.
+    ///
+    /// # Arguments
+    ///
+    /// - msg : The message to encode.
+    fn encode(&self, msg: &WsMessage) -> Result<Vec<u8>, WsCodecError>;
+
+    /// Decodes a message from a frame.
+    ///
+    /// # Arguments
+    ///
+    /// - frame : The frame received.
+    fn decode(&self, frame: &[u8]) -> Result<WsMessage, WsCodecError>;
+}
+
+/// Messages encoded as JSON.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+impl WsCodec for JsonCodec {
+    fn protocol(&self) -> &'static str {
+        "tchatchers.json.v1"
+    }
+
+    fn encode(&self, msg: &WsMessage) -> Result<Vec<u8>, WsCodecError> {
+        Ok(serde_json::to_vec(msg)?)
+    }
+
+    fn decode(&self, frame: &[u8]) -> Result<WsMessage, WsCodecError> {
+        Ok(serde_json::from_slice(frame)?)
+    }
+}
+
+/// Messages encoded with bincode, as the HTTP API does.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BincodeCodec;
+
+impl WsCodec for BincodeCodec {
+    fn protocol(&self) -> &'static str {
+        "tchatchers.bincode.v1"
+    }
+
+    fn encode(&self, msg: &WsMessage) -> Result<Vec<u8>, WsCodecError> {
+        Ok(bincode::serialize(msg)?)
+    }
+
+    fn decode(&self, frame: &[u8]) -> Result<WsMessage, WsCodecError> {
+        Ok(bincode::deserialize(frame)?)
+    }
+}
+
+/// Messages encoded as MessagePack, with their fields named.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MsgPackCodec;
+
+impl WsCodec for MsgPackCodec {
+    fn protocol(&self) -> &'static str {
+        "tchatchers.msgpack.v1"
+    }
+
+    fn encode(&self, msg: &WsMessage) -> Result<Vec<u8>, WsCodecError> {
+        Ok(rmp_serde::to_vec_named(msg)?)
+    }
+
+    fn decode(&self, frame: &[u8]) -> Result<WsMessage, WsCodecError> {
+        Ok(rmp_serde::from_slice(frame)?)
+    }
+}
+
+/// The codecs that can be negotiated for a socket.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum WireFormat {
+    /// See [JsonCodec].
+    #[default]
+    Json,
+    /// See [BincodeCodec].
+    Bincode,
+    /// See [MsgPackCodec].
+    MsgPack,
+}
+
+impl WireFormat {
+    /// The codecs supported, from the most to the least preferred.
+    pub const ALL: [WireFormat; 3] = [Self::Bincode, Self::MsgPack, Self::Json];
+
+    /// Returns the codec identified by a subprotocol, if supported.
+    ///
+    /// # Arguments
+    ///
+    /// - protocol : The subprotocol negotiated.
+    pub fn from_protocol(protocol: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.protocol() == protocol)
+    }
+
+    /// Selects the preferred codec among the ones offered by a client.
+    ///
+    /// # Arguments
+    ///
+    /// - offered : The value of the `Sec-WebSocket-Protocol` header, listing
+    ///   the subprotocols supported by the client separated by commas.
+    pub fn negotiate(offered: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| {
+            offered
+                .split(',')
+                .any(|protocol| protocol.trim() == format.protocol())
+        })
+    }
+
+    /// The codec used to encode and decode the messages.
+    fn codec(&self) -> &'static dyn WsCodec {
+        match self {
+            Self::Json => &JsonCodec,
+            Self::Bincode => &BincodeCodec,
+            Self::MsgPack => &MsgPackCodec,
+        }
+    }
+}
+
+impl WsCodec for WireFormat {
+    fn protocol(&self) -> &'static str {
+        self.codec().protocol()
+    }
+
+    fn encode(&self, msg: &WsMessage) -> Result<Vec<u8>, WsCodecError> {
+        self.codec().encode(msg)
+    }
+
+    fn decode(&self, frame: &[u8]) -> Result<WsMessage, WsCodecError> {
+        self.codec().decode(frame)
+    }
+}
+
+/// A message shared by several sockets, encoded at most once per codec.
+#[derive(Debug)]
+pub struct EncodedMessage {
+    /// The message shared.
+    message: WsMessage,
+    /// The frames already encoded, indexed by codec.
+    frames: [OnceLock<Vec<u8>>; WireFormat::ALL.len()],
+}
+
+impl EncodedMessage {
+    /// The message shared.
+    pub fn message(&self) -> &WsMessage {
+        &self.message
+    }
+
+    /// Returns the frame of the message in the given format, encoding it if
+    /// it hasn't been yet.
+    ///
+    /// # Arguments
+    ///
+    /// - format : The codec of the socket.
+    pub fn encode(&self, format: WireFormat) -> Result<&[u8], WsCodecError> {
+        let frame = &self.frames[format as usize];
+        if let Some(frame) = frame.get() {
+            return Ok(frame);
+        }
+        let encoded = format.encode(&self.message)?;
+        Ok(frame.get_or_init(|| encoded))
+    }
+}
+
+impl From<WsMessage> for EncodedMessage {
+    fn from(message: WsMessage) -> Self {
+        Self {
+            message,
+            frames: Default::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiate_prefers_the_most_compact_codec() {
+        assert_eq!(
+            WireFormat::negotiate("tchatchers.json.v1, tchatchers.msgpack.v1"),
+            Some(WireFormat::MsgPack)
+        );
+        assert_eq!(
+            WireFormat::negotiate("tchatchers.json.v1,tchatchers.bincode.v1,tchatchers.msgpack.v1"),
+            Some(WireFormat::Bincode)
+        );
+        assert_eq!(
+            WireFormat::negotiate(" tchatchers.json.v1 "),
+            Some(WireFormat::Json)
+        );
+    }
+
+    #[test]
+    fn negotiate_ignores_unknown_codecs() {
+        assert_eq!(WireFormat::negotiate(""), None);
+        assert_eq!(WireFormat::negotiate("tchatchers.json.v2, chat"), None);
+        assert_eq!(WireFormat::negotiate("tchatchers.json.v1.extra"), None);
+    }
+
+    #[test]
+    fn protocol_round_trip() {
+        for format in WireFormat::ALL {
+            assert_eq!(WireFormat::from_protocol(format.protocol()), Some(format));
+        }
+        assert_eq!(WireFormat::from_protocol("tchatchers.xml.v1"), None);
+    }
+
+    #[test]
+    fn codecs_round_trip() {
+        let msg = WsMessage::Seen(vec![uuid::Uuid::new_v4(), uuid::Uuid::new_v4()]);
+        for format in WireFormat::ALL {
+            let frame = format.encode(&msg).unwrap();
+            assert_eq!(format.decode(&frame).unwrap(), msg, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn encoded_message_matches_its_codec() {
+        let encoded = EncodedMessage::from(WsMessage::RateLimited { retry_after: 3 });
+        for format in WireFormat::ALL {
+            let frame = encoded.encode(format).unwrap().to_vec();
+            assert_eq!(
+                frame,
+                format.encode(encoded.message()).unwrap(),
+                "{format:?}"
+            );
+            assert_eq!(encoded.encode(format).unwrap(), frame.as_slice());
+        }
+    }
+}
@@ -8,11 +8,41 @@
 
 #[cfg(any(feature = "back", feature = "cli", feature = "async"))]
 use crate::async_message::{AsyncOperationPGType, AsyncQueue};
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+use crate::message_revision::MessageEdition;
+use crate::notification::Notification;
+use crate::profile::Profile;
+#[cfg(any(feature = "back", feature = "cli", feature = "async"))]
+use crate::reaction::MessageReactionCount;
+use crate::reaction::{Reaction, ReactionCount};
+use crate::room::Room;
+use crate::room_pin::RoomPin;
 use crate::user::PartialUser;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SecondsFormat, Utc};
 use derive_more::Display;
+use std::str::FromStr;
 use uuid::Uuid;
 
+/// The number of messages returned by a page of history when none is asked.
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+
+/// The maximum number of messages that can be returned by a page of history.
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+/// The version of the WS protocol spoken by this build.
+///
+/// It has to be bumped whenever a change of [WsMessage] prevents the clients
+/// of the previous version from working, such as removing or reordering a
+/// variant.
+pub const WS_PROTOCOL_VERSION: u32 = 6;
+
+/// The oldest version of the WS protocol still accepted by the server.
+pub const WS_MIN_PROTOCOL_VERSION: u32 = 6;
+
+/// The close code sent to the clients whose protocol version isn't supported,
+/// or that didn't start the connection with a [WsMessage::Hello].
+pub const WS_INCOMPATIBLE_VERSION_CLOSE_CODE: u16 = 4001;
+
 /// The types of messages shared between users.
 ///
 /// Some WS messages are containing data that have to be transmitted to everyone
@@ -35,9 +65,18 @@ pub enum WsMessage {
     MessagesRetrieved {
         messages: Vec<WsMessageContent>,
         session_id: Uuid,
+        /// The messages pinned in the room, whether they are part of the
+        /// messages retrieved or not.
+        pins: Vec<RoomPin>,
+        /// The timestamp of the latest message of the room read by the user,
+        /// if any.
+        last_read_at: Option<DateTime<Utc>>,
+    },
+    /// Indicates that a user has seen the messages.
+    MessagesSeen {
+        reader: PartialUser,
+        messages: Vec<Uuid>,
     },
-    /// Indicates that the user has seeen the messages.
-    MessagesSeen(Vec<Uuid>),
     /// Responds to Ping !
     Pong,
     /// Service !
@@ -46,6 +85,122 @@ pub enum WsMessage {
     Close,
     /// Keep alive to not close the connection.
     ClientKeepAlive,
+    /// Inform that one has seen the messages.
+    Seen(Vec<Uuid>),
+    /// Deletes a message
+    Delete(Uuid),
+    /// Sends an authentication request.
+    Authenticate(String),
+    /// Indicates that the user is attempting to access an access to a WS requiring authentication.
+    AuthenticationRequired,
+    /// Authentication has been validated by backend.
+    AuthenticationValidated,
+    /// Authentication is expired.
+    AuthenticationExpired,
+    /// The message sent has been rejected by the server, as either its author
+    /// or its room doesn't match the ones of the connection, or as the user
+    /// isn't allowed to edit or delete it.
+    MessageRejected(Uuid),
+    /// Edits the content of a message.
+    Edit { uuid: Uuid, content: String },
+    /// Informs that a message has been edited by its author.
+    MessageEdited {
+        uuid: Uuid,
+        content: String,
+        edited_at: DateTime<Utc>,
+    },
+    /// Informs that a user is typing a message.
+    ///
+    /// The user is filled server side before being relayed to the room.
+    Typing(PartialUser),
+    /// Informs that a user stopped typing.
+    ///
+    /// The user is filled server side before being relayed to the room.
+    StoppedTyping(PartialUser),
+    /// The users connected to the room, sent to a socket once it joined it.
+    PresenceSnapshot(Vec<PartialUser>),
+    /// Informs that a user joined the room.
+    UserJoined(PartialUser),
+    /// Informs that a user left the room.
+    UserLeft(PartialUser),
+    /// Adds a reaction on a message.
+    ///
+    /// The user is filled server side before being relayed to the room.
+    React(Reaction),
+    /// Removes a reaction from a message.
+    ///
+    /// The user is filled server side before being relayed to the room.
+    Unreact(Reaction),
+    /// Action sent by a client to retrieve a message and all the replies of
+    /// its thread.
+    RetrieveThread(Uuid),
+    /// The messages of a thread, from its root message to its latest reply.
+    ThreadRetrieved {
+        root: Uuid,
+        messages: Vec<WsMessageContent>,
+    },
+    /// Action sent by a client to retrieve the messages of the room older
+    /// than the given cursor.
+    RetrieveMessagesBefore { cursor: MessageCursor, limit: i64 },
+    /// A page of messages older than the cursor requested.
+    MessagesBeforeRetrieved(MessagePage),
+    /// Informs the client that it has been too slow to receive the messages of
+    /// the room, with the number of messages it missed.
+    ///
+    /// The client is expected to retrieve the messages of the room again.
+    Resync(u64),
+    /// Informs the client that its frame has been dropped because it sent too
+    /// many of them, with the number of seconds to wait before retrying.
+    RateLimited { retry_after: u64 },
+    /// The first message sent by a client once connected, announcing the
+    /// version of the protocol it speaks and the optional features it
+    /// supports.
+    Hello {
+        protocol_version: u32,
+        capabilities: Vec<WsCapability>,
+    },
+    /// The answer of the server to a compatible [WsMessage::Hello].
+    Welcome {
+        /// The git revision the server has been built from.
+        server_version: String,
+        capabilities: Vec<WsCapability>,
+        limits: WsLimits,
+    },
+    /// Action sent by a client once reconnected to retrieve the messages of
+    /// the room posted after the last one it received.
+    Resume { since_uuid: Uuid },
+    /// The messages posted after the one a client resumed from, from the
+    /// oldest to the latest.
+    ///
+    /// The replay isn't complete if the message resumed from is unknown or if
+    /// too many messages have been missed, in which case the client is
+    /// expected to retrieve the messages of the room again.
+    MessagesResumed {
+        messages: Vec<WsMessageContent>,
+        is_complete: bool,
+    },
+    /// Informs a user that they have been mentioned in a message, whatever
+    /// the room their socket is connected to.
+    Mentioned(Notification),
+    /// The metadata of the room, sent once joined and whenever they change.
+    RoomUpdated(Room),
+    /// Informs the sockets of a user that they can't access a room anymore,
+    /// the ones connected to it being closed.
+    RoomAccessRevoked(String),
+    /// The message sent hasn't been posted as the posting rules of the room
+    /// don't allow its author to post right away.
+    PostRejected { uuid: Uuid, reason: PostRejection },
+    /// Action sent by a client to pin a message of the room.
+    Pin(Uuid),
+    /// Action sent by a client to unpin a message of the room.
+    Unpin(Uuid),
+    /// A message has been pinned in the room.
+    Pinned(RoomPin),
+    /// A message has been unpinned from the room.
+    Unpinned(Uuid),
+    // The variants below are only used within the client, and are kept last
+    // so that the binary codecs, which identify the variants by their index,
+    // agree on the shared ones.
     /// Action to inform the server that the client reconnected.
     #[cfg(feature = "front")]
     ClientReconnected,
@@ -62,18 +217,90 @@ pub enum WsMessage {
     SerializationError,
     #[cfg(feature = "front")]
     ReactorExited,
-    /// Inform that one has seen the messages.
-    Seen(Vec<Uuid>),
-    /// Deletes a message
-    Delete(Uuid),
-    /// Sends an authentication request.
-    Authenticate(String),
-    /// Indicates that the user is attempting to access an access to a WS requiring authentication.
-    AuthenticationRequired,
-    /// Authentication has been validated by backend.
-    AuthenticationValidated,
-    /// Authentication is expired.
-    AuthenticationExpired,
+    /// The server closed the connection as the client is outdated, and has to
+    /// be reloaded.
+    #[cfg(feature = "front")]
+    IncompatibleVersion,
+}
+
+/// Why a message hasn't been posted in its room.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum PostRejection {
+    /// Only the moderators can post in the room.
+    AnnouncementOnly,
+    /// The slow mode of the room is on, with the number of seconds to wait
+    /// before posting again.
+    SlowMode { retry_after: u64 },
+}
+
+/// The optional features of the WS protocol.
+///
+/// A client only receives the messages of the features it announced.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum WsCapability {
+    /// Typing indicators.
+    Typing,
+    /// Members joining and leaving the room.
+    Presence,
+    /// Reactions on the messages.
+    Reactions,
+    /// Notifications of the mentions of the user.
+    Mentions,
+    /// The metadata of the room, such as its topic.
+    RoomMetadata,
+    /// The messages pinned in the room.
+    Pins,
+    /// A feature announced by a more recent party.
+    #[serde(other)]
+    Unknown,
+}
+
+impl WsCapability {
+    /// All the features known by this build.
+    pub const ALL: [WsCapability; 6] = [
+        Self::Typing,
+        Self::Presence,
+        Self::Reactions,
+        Self::Mentions,
+        Self::RoomMetadata,
+        Self::Pins,
+    ];
+
+    /// Returns the feature a client has to support to be sent a message, if
+    /// any.
+    ///
+    /// # Arguments
+    ///
+    /// - msg : The message to send.
+    pub fn required_by(msg: &WsMessage) -> Option<Self> {
+        match msg {
+            WsMessage::Typing(_) | WsMessage::StoppedTyping(_) => Some(Self::Typing),
+            WsMessage::PresenceSnapshot(_) | WsMessage::UserJoined(_) | WsMessage::UserLeft(_) => {
+                Some(Self::Presence)
+            }
+            WsMessage::React(_) | WsMessage::Unreact(_) => Some(Self::Reactions),
+            WsMessage::Mentioned(_) => Some(Self::Mentions),
+            WsMessage::RoomUpdated(_) => Some(Self::RoomMetadata),
+            WsMessage::Pinned(_) | WsMessage::Unpinned(_) => Some(Self::Pins),
+            _ => None,
+        }
+    }
+}
+
+/// The limits applied by the server on a connection.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WsLimits {
+    /// The maximum number of messages of a page of history.
+    pub max_page_size: i64,
+    /// The number of seconds the socket has to authenticate.
+    pub authentication_timeout: u64,
+    /// The number of messages that can be sent at once.
+    pub send_burst: u32,
+    /// The number of seconds after which as many messages can be sent again.
+    pub send_window: u32,
 }
 
 #[derive(
@@ -141,6 +368,142 @@ pub struct WsMessageContent {
     pub room: String,
     /// Whether a message has been received or not.
     pub reception_status: WsReceptionStatus,
+    /// When the message has been edited for the last time, if ever.
+    pub edited_at: Option<DateTime<Utc>>,
+    /// The reactions of the message, aggregated by emoji.
+    #[cfg_attr(any(feature = "back", feature = "cli", feature = "async"), sqlx(skip))]
+    pub reactions: Vec<ReactionCount>,
+    /// The message this message replies to, if any.
+    pub reply_to: Option<Uuid>,
+    /// Whether the message has been deleted while it had replies.
+    ///
+    /// Such a message is kept as a tombstone, without any content, so that
+    /// its replies aren't orphaned.
+    pub is_deleted: bool,
+}
+
+impl WsMessageContent {
+    /// Whether the given user is allowed to delete the message.
+    ///
+    /// Users can only delete their own messages, while moderators and admins
+    /// can delete any of them.
+    ///
+    /// # Arguments
+    ///
+    /// - user_id : The ID of the user attempting to delete the message.
+    /// - profile : The profile of this user.
+    pub fn can_be_deleted_by(&self, user_id: i32, profile: Profile) -> bool {
+        profile != Profile::User || self.author.id == user_id
+    }
+
+    /// Adds a reaction of a user on the message, if not already present.
+    ///
+    /// # Arguments
+    ///
+    /// - user_id : The ID of the user reacting.
+    /// - emoji : The emoji of the reaction.
+    pub fn add_reaction(&mut self, user_id: i32, emoji: &str) {
+        match self.reactions.iter_mut().find(|r| r.emoji == emoji) {
+            Some(reaction) if reaction.user_ids.contains(&user_id) => {}
+            Some(reaction) => {
+                reaction.user_ids.push(user_id);
+                reaction.count += 1;
+            }
+            None => self.reactions.push(ReactionCount {
+                emoji: emoji.to_string(),
+                count: 1,
+                user_ids: vec![user_id],
+            }),
+        }
+    }
+
+    /// Removes the reaction of a user on the message, if present.
+    ///
+    /// # Arguments
+    ///
+    /// - user_id : The ID of the user that reacted.
+    /// - emoji : The emoji of the reaction.
+    pub fn remove_reaction(&mut self, user_id: i32, emoji: &str) {
+        if let Some(reaction) = self
+            .reactions
+            .iter_mut()
+            .find(|r| r.emoji == emoji && r.user_ids.contains(&user_id))
+        {
+            reaction.user_ids.retain(|id| *id != user_id);
+            reaction.count -= 1;
+        }
+        self.reactions.retain(|r| r.count > 0);
+    }
+
+    /// Returns the cursor pointing at this message in its room's history.
+    pub fn cursor(&self) -> MessageCursor {
+        MessageCursor {
+            timestamp: self.timestamp,
+            uuid: self.uuid,
+        }
+    }
+
+    /// Turns the message into a tombstone, removing its content.
+    pub fn tombstone(&mut self) {
+        self.content.clear();
+        self.edited_at = None;
+        self.reactions.clear();
+        self.is_deleted = true;
+    }
+}
+
+/// A position in the history of a room, used to paginate its messages.
+///
+/// Messages are ordered by timestamp, and by UUID for the ones emitted at the
+/// same time.
+#[derive(
+    Debug, Clone, Copy, serde::Serialize, serde::Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageCursor {
+    /// The timestamp of the message pointed at.
+    pub timestamp: DateTime<Utc>,
+    /// The UUID of the message pointed at.
+    pub uuid: Uuid,
+}
+
+impl std::fmt::Display for MessageCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}_{}",
+            self.timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            self.uuid
+        )
+    }
+}
+
+impl FromStr for MessageCursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (timestamp, uuid) = s
+            .split_once('_')
+            .ok_or_else(|| format!("The cursor {s} isn't formatted as timestamp_uuid"))?;
+        Ok(Self {
+            timestamp: DateTime::parse_from_rfc3339(timestamp)
+                .map_err(|e| e.to_string())?
+                .with_timezone(&Utc),
+            uuid: Uuid::parse_str(uuid).map_err(|e| e.to_string())?,
+        })
+    }
+}
+
+/// A page of the history of a room, from the latest message to the oldest.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MessagePage {
+    /// The messages of the page.
+    pub messages: Vec<WsMessageContent>,
+    /// Whether older messages exist before this page.
+    pub has_more: bool,
+    /// The cursor to use to retrieve the next page, if any.
+    pub next_cursor: Option<MessageCursor>,
 }
 
 #[cfg(any(feature = "back", feature = "cli", feature = "async"))]
@@ -168,10 +531,153 @@ impl WsMessageContent {
         room_name: &str,
         pool: &sqlx::PgPool,
     ) -> Result<Vec<Self>, sqlx::Error> {
-        sqlx::query_as("SELECT * FROM MESSAGE m INNER JOIN CHATTER c ON m.author = c.id WHERE room=$1 ORDER BY timestamp DESC LIMIT 100 ")
+        let mut messages: Vec<Self> = sqlx::query_as("SELECT * FROM MESSAGE m INNER JOIN CHATTER c ON m.author = c.id WHERE room=$1 ORDER BY timestamp DESC LIMIT 100 ")
             .bind(room_name)
             .fetch_all(pool)
-            .await
+            .await?;
+        Self::fill_reactions(&mut messages, pool).await?;
+        Ok(messages)
+    }
+
+    /// Returns a page of the messages of a room, from the latest to the oldest.
+    ///
+    /// # Arguments
+    ///
+    /// - room_name : The room the query is made for.
+    /// - before : The cursor before which the messages are returned, or none to
+    ///   start from the latest message.
+    /// - limit : The maximum number of messages returned, bounded by
+    ///   [MAX_PAGE_SIZE].
+    /// - pool : The connection pool.
+    pub async fn query_page_for_room(
+        room_name: &str,
+        before: Option<&MessageCursor>,
+        limit: i64,
+        pool: &sqlx::PgPool,
+    ) -> Result<MessagePage, sqlx::Error> {
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+        // One more message is fetched to know whether older ones exist.
+        let mut messages: Vec<Self> = sqlx::query_as(
+            "
+            SELECT * FROM MESSAGE m INNER JOIN CHATTER c ON m.author = c.id
+            WHERE room = $1 AND ($2::TIMESTAMPTZ IS NULL OR (m.timestamp, m.uuid) < ($2, $3))
+            ORDER BY m.timestamp DESC, m.uuid DESC
+            LIMIT $4
+            ",
+        )
+        .bind(room_name)
+        .bind(before.map(|cursor| cursor.timestamp))
+        .bind(before.map(|cursor| cursor.uuid))
+        .bind(limit + 1)
+        .fetch_all(pool)
+        .await?;
+        let has_more = messages.len() as i64 > limit;
+        messages.truncate(limit as usize);
+        Self::fill_reactions(&mut messages, pool).await?;
+        Ok(MessagePage {
+            next_cursor: has_more
+                .then(|| messages.last().map(Self::cursor))
+                .flatten(),
+            messages,
+            has_more,
+        })
+    }
+
+    /// Returns the messages of a room posted after the given cursor, from the
+    /// oldest to the latest, along with whether more recent ones exist beyond
+    /// the limit.
+    ///
+    /// # Arguments
+    ///
+    /// - room_name : The room the query is made for.
+    /// - since : The cursor after which the messages are returned.
+    /// - limit : The maximum number of messages returned, bounded by
+    ///   [MAX_PAGE_SIZE].
+    /// - pool : The connection pool.
+    pub async fn query_since_for_room(
+        room_name: &str,
+        since: &MessageCursor,
+        limit: i64,
+        pool: &sqlx::PgPool,
+    ) -> Result<(Vec<Self>, bool), sqlx::Error> {
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+        // One more message is fetched to know whether more recent ones exist.
+        let mut messages: Vec<Self> = sqlx::query_as(
+            "
+            SELECT * FROM MESSAGE m INNER JOIN CHATTER c ON m.author = c.id
+            WHERE room = $1 AND (m.timestamp, m.uuid) > ($2, $3)
+            ORDER BY m.timestamp ASC, m.uuid ASC
+            LIMIT $4
+            ",
+        )
+        .bind(room_name)
+        .bind(since.timestamp)
+        .bind(since.uuid)
+        .bind(limit + 1)
+        .fetch_all(pool)
+        .await?;
+        let has_more = messages.len() as i64 > limit;
+        messages.truncate(limit as usize);
+        Self::fill_reactions(&mut messages, pool).await?;
+        Ok((messages, has_more))
+    }
+
+    /// Returns a message and all the replies of its thread, from the oldest to
+    /// the latest.
+    ///
+    /// Nothing is returned if the message doesn't belong to the given room.
+    ///
+    /// # Arguments
+    ///
+    /// - uuid : The UUID of the thread's root message.
+    /// - room_name : The room the query is made for.
+    /// - pool : The connection pool.
+    pub async fn query_thread(
+        uuid: &Uuid,
+        room_name: &str,
+        pool: &sqlx::PgPool,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let mut messages: Vec<Self> = sqlx::query_as(
+            "
+            WITH RECURSIVE thread AS (
+                SELECT uuid FROM MESSAGE WHERE uuid = $1 AND room = $2
+                UNION
+                SELECT r.uuid FROM MESSAGE r INNER JOIN thread t ON r.reply_to = t.uuid
+            )
+            SELECT * FROM MESSAGE m INNER JOIN CHATTER c ON m.author = c.id
+            WHERE m.uuid IN (SELECT uuid FROM thread)
+            ORDER BY timestamp ASC
+            ",
+        )
+        .bind(uuid)
+        .bind(room_name)
+        .fetch_all(pool)
+        .await?;
+        Self::fill_reactions(&mut messages, pool).await?;
+        Ok(messages)
+    }
+
+    /// Fills the reactions of the given messages from the database.
+    ///
+    /// # Arguments
+    ///
+    /// - messages : The messages to fill the reactions of.
+    /// - pool : The connection pool.
+    pub async fn fill_reactions(
+        messages: &mut [Self],
+        pool: &sqlx::PgPool,
+    ) -> Result<(), sqlx::Error> {
+        let messages_uuid: Vec<Uuid> = messages.iter().map(|m| m.uuid).collect();
+        let reactions = MessageReactionCount::get_for_messages(&messages_uuid, pool).await?;
+        for reaction in reactions {
+            if let Some(message) = messages
+                .iter_mut()
+                .find(|m| m.uuid == reaction.message_uuid)
+            {
+                message.reactions.push(reaction.into());
+            }
+        }
+        Ok(())
     }
 
     /// Insert the message in the database.
@@ -185,7 +691,7 @@ impl WsMessageContent {
         let mut tx = pool.begin().await?;
 
         let mut query_builder: sqlx::QueryBuilder<sqlx::Postgres> = sqlx::QueryBuilder::new(
-            "INSERT INTO MESSAGE(uuid, content, author, timestamp, room, reception_status)",
+            "INSERT INTO MESSAGE(uuid, content, author, timestamp, room, reception_status, reply_to)",
         );
         query_builder.push_values(values, |mut b, value| {
             b.push_bind(value.uuid)
@@ -193,7 +699,8 @@ impl WsMessageContent {
                 .push_bind(value.author.id)
                 .push_bind(value.timestamp)
                 .push_bind(value.room)
-                .push_bind(value.reception_status);
+                .push_bind(value.reception_status)
+                .push_bind(value.reply_to);
         });
 
         let res = query_builder.build().execute(&mut *tx).await?;
@@ -219,27 +726,51 @@ impl WsMessageContent {
         Ok(())
     }
 
-    /// Mark a list of existing messages as seen.
+    /// Edits a list of existing messages, keeping their former content as
+    /// revisions.
     ///
     /// # Arguments
     ///
-    /// - messages_uuid : the list of messages seen.
+    /// - editions : the list of editions to apply.
     /// - pool : the connection pool.
-    pub async fn mark_as_seen_async(
-        messages: Vec<AsyncOperationPGType<Uuid>>,
+    pub async fn edit_messages_async(
+        mut editions: Vec<MessageEdition>,
         pool: &sqlx::PgPool,
     ) -> Result<(), sqlx::Error> {
+        let mut successfull_records = 0i64;
+        let mut failed_records = 0i64;
+
+        // Editions are applied chronologically so that revisions keep the right order.
+        editions.sort_by_key(|edition| edition.edited_at);
+
         let mut tx = pool.begin().await?;
 
-        let updated_records: u64 =
-            sqlx::query("UPDATE MESSAGE m SET RECEPTION_STATUS = $1 WHERE uuid = ANY($2)")
-                .bind(WsReceptionStatus::Seen)
-                .bind(messages.iter().map(|m| m.entity_id).collect::<Vec<Uuid>>())
-                .execute(&mut *tx)
-                .await?
-                .rows_affected();
+        for edition in editions {
+            sqlx::query(
+                "
+            INSERT INTO MESSAGE_REVISION(message_uuid, content, revised_at)
+            SELECT uuid, content, COALESCE(edited_at, timestamp) FROM MESSAGE WHERE uuid = $1
+            ",
+            )
+            .bind(edition.uuid)
+            .execute(&mut *tx)
+            .await?;
 
-        let failed_records = messages.len() - updated_records as usize;
+            let updated_records =
+                sqlx::query("UPDATE MESSAGE SET content = $1, edited_at = $2 WHERE uuid = $3")
+                    .bind(edition.content)
+                    .bind(edition.edited_at)
+                    .bind(edition.uuid)
+                    .execute(&mut *tx)
+                    .await?
+                    .rows_affected();
+
+            if updated_records == 0 {
+                failed_records += 1;
+            } else {
+                successfull_records += 1;
+            }
+        }
 
         sqlx::query(
             "
@@ -247,9 +778,9 @@ impl WsMessageContent {
         VALUES($1, $2, $3)
         ",
         )
-        .bind(AsyncQueue::MessagesSeen)
-        .bind(updated_records as i64)
-        .bind(failed_records as i64)
+        .bind(AsyncQueue::EditMessage)
+        .bind(successfull_records)
+        .bind(failed_records)
         .execute(&mut *tx)
         .await?;
 
@@ -258,54 +789,72 @@ impl WsMessageContent {
         Ok(())
     }
 
-    pub async fn clean_rooms(
-        room_names: std::collections::HashSet<&str>,
-        pool: &sqlx::PgPool,
-    ) -> Result<(), sqlx::Error> {
-        let mut failed_records = 0i64;
-
-        let mut tx = pool.begin().await?;
-
-        let mut uuid_to_delete: Vec<Uuid> = Vec::new();
-
-        for room in room_names {
-            let Ok(uuid): Result<Vec<(Uuid,)>, sqlx::Error> =
-                sqlx::query_as("SELECT uuid FROM MESSAGE WHERE room=$1 OFFSET 100")
-                    .bind(room)
-                    .fetch_all(&mut *tx)
-                    .await
-            else {
-                failed_records += 1;
-                continue;
-            };
-            uuid_to_delete.append(&mut uuid.iter().map(|u| u.0).collect());
-        }
-
-        let successfull_records = if !uuid_to_delete.is_empty() {
-            sqlx::query("DELETE FROM MESSAGE WHERE UUID = ANY($1)")
-                .bind(uuid_to_delete)
-                .execute(&mut *tx)
-                .await?
-                .rows_affected() as i64
-        } else {
-            0i64
-        };
-
-        sqlx::query(
+    /// Deletes the given messages, keeping the ones that still have replies as
+    /// tombstones.
+    ///
+    /// Tombstones left without any reply are deleted as well.
+    ///
+    /// Returns the number of messages either deleted or tombstoned.
+    ///
+    /// # Arguments
+    ///
+    /// - messages_uuid : The messages to delete.
+    /// - con : The connection to run the queries on.
+    pub(crate) async fn delete_or_tombstone(
+        messages_uuid: &[Uuid],
+        con: &mut sqlx::PgConnection,
+    ) -> Result<u64, sqlx::Error> {
+        let parents: Vec<Option<Uuid>> = sqlx::query_scalar(
             "
-        INSERT INTO PROCESS_REPORT(process_id, successfull_records, failed_records) 
-        VALUES($1, $2, $3)
-        ",
+            DELETE FROM MESSAGE m WHERE uuid = ANY($1)
+            AND NOT EXISTS (SELECT 1 FROM MESSAGE r WHERE r.reply_to = m.uuid)
+            RETURNING reply_to
+            ",
         )
-        .bind(AsyncQueue::CleanRoom)
-        .bind(successfull_records)
-        .bind(failed_records)
-        .execute(&mut *tx)
+        .bind(messages_uuid)
+        .fetch_all(&mut *con)
         .await?;
+        let deleted_records = parents.len() as u64;
 
-        tx.commit().await?;
+        sqlx::query("DELETE FROM MESSAGE_REACTION WHERE message_uuid = ANY($1)")
+            .bind(messages_uuid)
+            .execute(&mut *con)
+            .await?;
+        sqlx::query("DELETE FROM MESSAGE_REVISION WHERE message_uuid = ANY($1)")
+            .bind(messages_uuid)
+            .execute(&mut *con)
+            .await?;
+        sqlx::query("DELETE FROM ROOM_PIN WHERE message_uuid = ANY($1)")
+            .bind(messages_uuid)
+            .execute(&mut *con)
+            .await?;
 
-        Ok(())
+        let tombstoned_records = sqlx::query(
+            "UPDATE MESSAGE SET content = '', edited_at = NULL, is_deleted = TRUE WHERE uuid = ANY($1)",
+        )
+        .bind(messages_uuid)
+        .execute(&mut *con)
+        .await?
+        .rows_affected();
+
+        // Deleting a reply can leave its tombstoned parent without any reply,
+        // and so on up the chain of the replies deleted.
+        let mut parents_uuid: Vec<Uuid> = parents.into_iter().flatten().collect();
+        while !parents_uuid.is_empty() {
+            let parents: Vec<Option<Uuid>> = sqlx::query_scalar(
+                "
+                DELETE FROM MESSAGE m WHERE uuid = ANY($1) AND is_deleted
+                AND NOT EXISTS (SELECT 1 FROM MESSAGE r WHERE r.reply_to = m.uuid)
+                RETURNING reply_to
+                ",
+            )
+            .bind(&parents_uuid)
+            .fetch_all(&mut *con)
+            .await?;
+            parents_uuid = parents.into_iter().flatten().collect();
+        }
+
+        Ok(deleted_records + tombstoned_records)
     }
 
     /// Deletes the message present in a room.
@@ -323,14 +872,58 @@ impl WsMessageContent {
             .await
     }
 
+    /// Deletes a list of existing messages.
+    ///
+    /// # Arguments
+    ///
+    /// - messages : the list of messages to delete.
+    /// - pool : the connection pool.
+    pub async fn delete_messages_async(
+        messages: Vec<AsyncOperationPGType<Uuid>>,
+        pool: &sqlx::PgPool,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let deleted_records: u64 = Self::delete_or_tombstone(
+            &messages.iter().map(|m| m.entity_id).collect::<Vec<Uuid>>(),
+            &mut tx,
+        )
+        .await?;
+
+        let failed_records = messages.len() - deleted_records as usize;
+
+        sqlx::query(
+            "
+        INSERT INTO PROCESS_REPORT(process_id, successfull_records, failed_records) 
+        VALUES($1, $2, $3)
+        ",
+        )
+        .bind(AsyncQueue::DeleteMessage)
+        .bind(deleted_records as i64)
+        .bind(failed_records as i64)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Deletes a list of existing messages, keeping the ones with replies as
+    /// tombstones.
+    ///
+    /// # Arguments
+    ///
+    /// - messages_uuid : the list of messages to delete.
+    /// - pool : the connection pool.
     pub async fn delete_messages(
-        messages_uuid: &Vec<Uuid>,
+        messages_uuid: &[Uuid],
         pool: &sqlx::PgPool,
-    ) -> Result<sqlx::postgres::PgQueryResult, sqlx::Error> {
-        sqlx::query("DELETE FROM MESSAGE WHERE uuid = ANY($1)")
-            .bind(messages_uuid)
-            .execute(pool)
-            .await
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let deleted_records = Self::delete_or_tombstone(messages_uuid, &mut tx).await?;
+        tx.commit().await?;
+        Ok(deleted_records)
     }
 }
 
@@ -357,3 +950,84 @@ impl WsMessageStats {
             .await
     }
 }
+
+#[cfg(all(test, any(feature = "back", feature = "cli", feature = "async")))]
+mod tests {
+    use super::*;
+
+    /// Inserts a message, replying to the given one if any.
+    async fn insert_message(
+        author: i32,
+        reply_to: Option<Uuid>,
+        is_deleted: bool,
+        con: &mut sqlx::PgConnection,
+    ) -> Uuid {
+        let uuid = Uuid::new_v4();
+        sqlx::query(
+            "
+            INSERT INTO MESSAGE(uuid, content, author, room, reception_status, reply_to, is_deleted)
+            VALUES ($1, 'content', $2, 'delete_or_tombstone_test', $3, $4, $5)
+            ",
+        )
+        .bind(uuid)
+        .bind(author)
+        .bind(WsReceptionStatus::Sent)
+        .bind(reply_to)
+        .bind(is_deleted)
+        .execute(con)
+        .await
+        .unwrap();
+        uuid
+    }
+
+    /// Returns whether a message exists, and whether it is a tombstone.
+    async fn find_message(uuid: Uuid, con: &mut sqlx::PgConnection) -> Option<bool> {
+        sqlx::query_scalar("SELECT is_deleted FROM MESSAGE WHERE uuid = $1")
+            .bind(uuid)
+            .fetch_optional(con)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a PostgreSQL database configured by the user env"]
+    async fn delete_or_tombstone_cleans_up_the_parents_only() {
+        let pool = crate::pool::get_pg_pool().await.unwrap();
+        // Nothing is committed, the transaction being rolled back on drop.
+        let mut tx = pool.begin().await.unwrap();
+        let author: i32 = sqlx::query_scalar(
+            "INSERT INTO CHATTER(login, password, name) VALUES ($1, '', 'test') RETURNING id",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .fetch_one(&mut *tx)
+        .await
+        .unwrap();
+        let root = insert_message(author, None, false, &mut tx).await;
+        let reply = insert_message(author, Some(root), false, &mut tx).await;
+        let nested_reply = insert_message(author, Some(reply), false, &mut tx).await;
+        let orphan_tombstone = insert_message(author, None, true, &mut tx).await;
+
+        // A message with replies is kept as a tombstone.
+        let deleted = WsMessageContent::delete_or_tombstone(&[root], &mut tx)
+            .await
+            .unwrap();
+        assert_eq!(deleted, 1);
+        assert_eq!(find_message(root, &mut tx).await, Some(true));
+
+        // A parent that isn't a tombstone is kept once its replies are deleted.
+        WsMessageContent::delete_or_tombstone(&[nested_reply], &mut tx)
+            .await
+            .unwrap();
+        assert_eq!(find_message(nested_reply, &mut tx).await, None);
+        assert_eq!(find_message(reply, &mut tx).await, Some(false));
+
+        // A tombstone left without any reply is deleted up the chain, while
+        // the unrelated tombstones are kept.
+        WsMessageContent::delete_or_tombstone(&[reply], &mut tx)
+            .await
+            .unwrap();
+        assert_eq!(find_message(reply, &mut tx).await, None);
+        assert_eq!(find_message(root, &mut tx).await, None);
+        assert_eq!(find_message(orphan_tombstone, &mut tx).await, Some(true));
+    }
+}
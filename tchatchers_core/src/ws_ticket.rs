@@ -0,0 +1,98 @@
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+//! A WS ticket is a short-lived and single-use token used to authenticate a
+//! websocket connection when it is upgraded.
+//!
+//! It avoids sending the bearer token within the websocket, as a ticket can't
+//! be reused once it has been consumed or once it expired.
+
+use redis::AsyncCommands;
+use uuid::Uuid;
+
+/// The number of seconds during which a ticket can be consumed.
+const WS_TICKET_EXPIRACY_TIME: u64 = 30;
+
+/// A ticket that can be exchanged once against an authenticated websocket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WsTicket {
+    /// The ticket value, shared with the client.
+    pub ticket: Uuid,
+    /// The ID of the user the ticket has been issued for.
+    pub user_id: i32,
+    /// The expiracy time of the authorization token the ticket has been
+    /// issued against, after which the socket is closed.
+    pub exp: i64,
+    /// The token family of the user's session, if known.
+    pub token_family: Option<Uuid>,
+}
+
+impl WsTicket {
+    /// Creates a new ticket for the given user.
+    ///
+    /// # Arguments
+    ///
+    /// - `user_id`: The ID of the user the ticket is issued for.
+    /// - `exp`: The expiracy time of the user's authorization token.
+    /// - `token_family`: The token family of the user's session, if known.
+    pub fn new(user_id: i32, exp: i64, token_family: Option<Uuid>) -> Self {
+        Self {
+            ticket: Uuid::new_v4(),
+            user_id,
+            exp,
+            token_family,
+        }
+    }
+
+    /// Returns the Redis key under which a ticket is stored.
+    fn key(ticket: &Uuid) -> String {
+        format!("ws_ticket:{ticket}")
+    }
+
+    /// Stores the ticket in Redis until it expires.
+    ///
+    /// # Arguments
+    ///
+    /// * `con` - A mutable reference to a Redis connection to execute the Redis command.
+    pub async fn store(
+        &self,
+        con: &mut redis::aio::MultiplexedConnection,
+    ) -> Result<(), redis::RedisError> {
+        con.set_ex(
+            Self::key(&self.ticket),
+            format!(
+                "{}:{}:{}",
+                self.user_id,
+                self.exp,
+                self.token_family.map(|f| f.to_string()).unwrap_or_default()
+            ),
+            WS_TICKET_EXPIRACY_TIME,
+        )
+        .await
+    }
+
+    /// Consumes a ticket, returning it if it was still valid.
+    ///
+    /// The ticket is deleted from Redis on read, so that it can only be used once.
+    ///
+    /// # Arguments
+    ///
+    /// * `ticket` - The ticket sent by the client.
+    /// * `con` - A mutable reference to a Redis connection to execute the Redis command.
+    pub async fn consume(
+        ticket: &Uuid,
+        con: &mut redis::aio::MultiplexedConnection,
+    ) -> Result<Option<Self>, redis::RedisError> {
+        let value: Option<String> = con.get_del(Self::key(ticket)).await?;
+        Ok(value.and_then(|value| {
+            let mut parts = value.splitn(3, ':');
+            let (user_id, exp) = (parts.next()?, parts.next()?);
+            Some(Self {
+                ticket: *ticket,
+                user_id: user_id.parse().ok()?,
+                exp: exp.parse().ok()?,
+                token_family: parts.next().and_then(|f| f.parse().ok()),
+            })
+        }))
+    }
+}
@@ -7,110 +7,202 @@ use futures::{SinkExt, StreamExt};
 use gloo_console::error;
 use gloo_net::websocket::{futures::WebSocket, Message, WebSocketError};
 use serde::{Deserialize, Serialize};
-use tchatchers_core::ws_message::WsMessage;
+use tchatchers_core::ws_codec::{WireFormat, WsCodec};
+use tchatchers_core::ws_message::{
+    WsCapability, WsMessage, WS_INCOMPATIBLE_VERSION_CLOSE_CODE, WS_PROTOCOL_VERSION,
+};
 use tokio::pin;
 use yew_agent::reactor::{reactor, ReactorScope};
 
+/// The codec asked to the server when opening the socket.
+const WIRE_FORMAT: WireFormat = WireFormat::Bincode;
+
 #[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum WebSocketReactorControl {
     Open(String),
     Send(WsMessage),
-    Reconnect,
+    /// Closes the current socket, if any, and opens a new one at the given
+    /// address.
+    Reconnect(String),
     Close,
 }
 
+/// How the reactor goes on once a socket is over.
+enum SocketEnd {
+    /// A new socket is opened at the given address.
+    Reconnect(String),
+    /// The socket has been lost, the reactor waiting to be asked to reconnect.
+    Lost,
+    /// The reactor exits.
+    Exit,
+}
+
 #[reactor(ChatReactor)]
 pub async fn websocket_reactor(mut scope: ReactorScope<WebSocketReactorControl, WsMessage>) {
     gloo_console::log!("ChatReactor started");
     let _ = scope.flush().await;
     let (mut sender, mut reader) = scope.split();
 
-    if let Some(m) = reader.next().await {
-        gloo_console::log!("Reactor message received");
-        let WebSocketReactorControl::Open(address) = m else {
-            panic!("Opening message not received");
-        };
-        let Ok(websocket) = WebSocket::open(&address) else {
-            gloo_console::error!("An error has been met while trying to open WS connection");
-            std::mem::drop(sender.send(WsMessage::Close));
-            return;
-        };
+    let Some(m) = reader.next().await else {
+        gloo_console::log!("No message received, skipped");
+        return;
+    };
+    gloo_console::log!("Reactor message received");
+    let WebSocketReactorControl::Open(mut address) = m else {
+        panic!("Opening message not received");
+    };
 
-        let (mut write, mut read) = websocket.split();
-        let (in_tx, mut in_rx) = tokio::sync::broadcast::channel::<WsMessage>(2);
+    loop {
+        let mut end = match WebSocket::open_with_protocol(&address, WIRE_FORMAT.protocol()) {
+            Ok(websocket) => {
+                let (mut write, mut read) = websocket.split();
+                let end = {
+                    let (in_tx, mut in_rx) = tokio::sync::broadcast::channel::<WsMessage>(2);
 
-        let write_tsk = async move {
-            while let Ok(s) = in_rx.recv().await {
-                write
-                    .send(Message::Bytes(serde_json::to_vec(&s).unwrap()))
-                    .await
-                    .unwrap();
-            }
-        };
-
-        let read_tsk = async move {
-            while let Some(msg) = read.next().await {
-                match msg {
-                    Ok(Message::Text(data)) => {
-                        let Ok(msg): Result<WsMessage, _> = serde_json::from_slice(data.as_bytes())
-                        else {
-                            let _ = sender.send(WsMessage::SerializationError).await;
-                            break;
-                        };
-                        let _ = sender.send(msg).await;
-                    }
-                    Ok(Message::Bytes(b)) => {
-                        let Ok(msg): Result<WsMessage, _> = serde_json::from_slice(&b) else {
-                            let _ = sender.send(WsMessage::SerializationError).await;
-                            break;
+                    let write_tsk = async {
+                        // The server expects each connection to start with the handshake.
+                        let hello = WsMessage::Hello {
+                            protocol_version: WS_PROTOCOL_VERSION,
+                            capabilities: WsCapability::ALL.to_vec(),
                         };
-                        let _ = sender.send(msg).await;
-                    }
-                    Err(e) => match e {
-                        WebSocketError::ConnectionError => {
-                            error!("Error on connection");
-                            let _ = sender.send(WsMessage::ClientDisconnected).await;
+                        if write
+                            .send(Message::Bytes(WIRE_FORMAT.encode(&hello).unwrap()))
+                            .await
+                            .is_err()
+                        {
+                            return;
                         }
-                        WebSocketError::ConnectionClose(e) => {
-                            error!("The connection has been closed :", e.code);
-                            error!("Error :", e.reason);
-                            let _ = sender.send(WsMessage::ConnectionClosed).await;
+                        while let Ok(s) = in_rx.recv().await {
+                            if write
+                                .send(Message::Bytes(WIRE_FORMAT.encode(&s).unwrap()))
+                                .await
+                                .is_err()
+                            {
+                                return;
+                            }
                         }
-                        WebSocketError::MessageSendError(e) => {
-                            error!("Error while sending message", e.to_string());
-                            let _ = sender.send(WsMessage::ErrorOnMessage(e.to_string())).await;
+                    };
+
+                    let read_tsk = async {
+                        while let Some(msg) = read.next().await {
+                            match msg {
+                                Ok(Message::Text(data)) => {
+                                    let Ok(msg) = WIRE_FORMAT.decode(data.as_bytes()) else {
+                                        let _ = sender.send(WsMessage::SerializationError).await;
+                                        break;
+                                    };
+                                    let _ = sender.send(msg).await;
+                                }
+                                Ok(Message::Bytes(b)) => {
+                                    let Ok(msg) = WIRE_FORMAT.decode(&b) else {
+                                        let _ = sender.send(WsMessage::SerializationError).await;
+                                        break;
+                                    };
+                                    let _ = sender.send(msg).await;
+                                }
+                                Err(e) => match e {
+                                    WebSocketError::ConnectionError => {
+                                        error!("Error on connection");
+                                        let _ = sender.send(WsMessage::ClientDisconnected).await;
+                                    }
+                                    WebSocketError::ConnectionClose(e)
+                                        if e.code == WS_INCOMPATIBLE_VERSION_CLOSE_CODE =>
+                                    {
+                                        error!(
+                                            "The server doesn't support this client anymore :",
+                                            e.reason
+                                        );
+                                        let _ = sender.send(WsMessage::IncompatibleVersion).await;
+                                    }
+                                    WebSocketError::ConnectionClose(e) => {
+                                        error!("The connection has been closed :", e.code);
+                                        error!("Error :", e.reason);
+                                        let _ = sender.send(WsMessage::ConnectionClosed).await;
+                                    }
+                                    WebSocketError::MessageSendError(e) => {
+                                        error!("Error while sending message", e.to_string());
+                                        let _ = sender
+                                            .send(WsMessage::ErrorOnMessage(e.to_string()))
+                                            .await;
+                                    }
+                                    _ => error!(
+                                        "Unexpected error while communicating with distant ws"
+                                    ),
+                                },
+                            }
                         }
-                        _ => error!("Unexpected error while communicating with distant ws"),
-                    },
+                    };
+
+                    let scope_bridge_tsk = async {
+                        while let Some(m) = reader.next().await {
+                            match m {
+                                WebSocketReactorControl::Send(m) => {
+                                    let _ = in_tx.send(m);
+                                }
+                                WebSocketReactorControl::Open(address)
+                                | WebSocketReactorControl::Reconnect(address) => {
+                                    return SocketEnd::Reconnect(address)
+                                }
+                                WebSocketReactorControl::Close => return SocketEnd::Exit,
+                            }
+                        }
+                        SocketEnd::Exit
+                    };
+
+                    pin!(read_tsk, write_tsk, scope_bridge_tsk);
+
+                    tokio::select! {
+                        _ = (&mut read_tsk) => {
+                            gloo_console::log!("Read aborted");
+                            SocketEnd::Lost
+                        },
+                        _ = (&mut write_tsk) => {
+                            gloo_console::log!("Write aborted");
+                            SocketEnd::Lost
+                        },
+                        end = (&mut scope_bridge_tsk) => {
+                            gloo_console::log!("End of scope bridge");
+                            end
+                        }
+                    }
+                };
+                // The socket isn't closed when dropped.
+                if let Ok(websocket) = read.reunite(write) {
+                    let _ = websocket.close(None, None);
                 }
+                end
+            }
+            Err(_) => {
+                error!("An error has been met while trying to open WS connection");
+                let _ = sender.send(WsMessage::ClientDisconnected).await;
+                SocketEnd::Lost
             }
         };
 
-        let scope_bridge_tsk = async {
+        // Once the socket is lost, the messages sent are dropped until the
+        // reactor is asked to reconnect.
+        if let SocketEnd::Lost = end {
+            end = SocketEnd::Exit;
             while let Some(m) = reader.next().await {
                 match m {
-                    WebSocketReactorControl::Send(m) => {
-                        let _ = in_tx.send(m);
+                    WebSocketReactorControl::Open(address)
+                    | WebSocketReactorControl::Reconnect(address) => {
+                        end = SocketEnd::Reconnect(address);
+                        break;
                     }
-                    WebSocketReactorControl::Reconnect => {}
-                    _ => break,
+                    WebSocketReactorControl::Send(_) => continue,
+                    WebSocketReactorControl::Close => break,
                 }
             }
-        };
-
-        pin!(read_tsk, write_tsk, scope_bridge_tsk);
+        }
 
-        tokio::select! {
-            _ = (&mut read_tsk) => {
-                gloo_console::log!("Read aborted");
-            },
-            _ = (&mut write_tsk) => {
-                gloo_console::log!("Write aborted");
-            },
-            _ = (&mut scope_bridge_tsk) => gloo_console::log!("End of scope bridge")
+        match end {
+            SocketEnd::Reconnect(next_address) => {
+                gloo_console::log!("Reconnecting");
+                address = next_address;
+            }
+            SocketEnd::Lost | SocketEnd::Exit => break,
         }
-    } else {
-        gloo_console::log!("No message received, skipped");
     }
     gloo_console::log!("Exiting");
 }
@@ -16,6 +16,26 @@ pub struct ProfileRMenuProps {
 pub struct MessageRMenuProps {
     pub message_id: Uuid,
     pub is_self: bool,
+    pub content: String,
+    pub is_pinned: bool,
+    pub can_pin: bool,
+}
+
+/// An action on a message chosen from its right menu, run by the room's feed.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub enum MessageAction {
+    /// Replaces the content of the message.
+    Edit(Uuid, String),
+    /// Reacts on the message with the given emoji.
+    React(Uuid, String),
+    /// Replies to the message.
+    Reply(Uuid),
+    /// Opens the thread of the message.
+    OpenThread(Uuid),
+    /// Pins the message in the room.
+    Pin(Uuid),
+    /// Unpins the message from the room.
+    Unpin(Uuid),
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -32,6 +52,7 @@ pub struct RMenuBus {
 pub enum RMenusBusEvents {
     OpenRMenu(i32, i32, RMenuKind),
     CloseRMenu,
+    MessageAction(MessageAction),
 }
 
 impl Worker for RMenuBus {
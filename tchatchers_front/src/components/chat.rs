@@ -1,13 +1,19 @@
 // Copyright ⓒ 2022 LABEYE Loïc
 // This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+use std::rc::Rc;
+
+use crate::components::common::I18N;
 use chrono::{DateTime, Datelike, Duration, Timelike, Utc};
 use rmenu_service::{MessageRMenuProps, ProfileRMenuProps, RMenuBus, RMenuKind, RMenusBusEvents};
+use tchatchers_core::locale::TranslationMap;
+use tchatchers_core::reaction::{Reaction, ReactionCount};
 use tchatchers_core::user::PartialUser;
-use tchatchers_core::ws_message::{WsMessageContent, WsReceptionStatus};
+use tchatchers_core::ws_message::{WsMessage, WsMessageContent, WsReceptionStatus};
 use uuid::Uuid;
 use web_sys::MouseEvent;
 use yew::{
-    classes, function_component, html, use_state, AttrValue, Component, Context, Html, Properties,
+    classes, function_component, html, use_state, AttrValue, Callback, Component, Context, Html,
+    Properties,
 };
 use yew_agent::worker::use_worker_subscription;
 
@@ -52,20 +58,88 @@ struct MessageProperties {
     #[prop_or_default]
     pub is_user: bool,
     pub reception_status: WsReceptionStatus,
+    #[prop_or_default]
+    pub edited_at: Option<DateTime<Utc>>,
+    #[prop_or_default]
+    pub reactions: Vec<ReactionCount>,
+    pub user_id: i32,
+    pub pass_message_to_ws: Callback<WsMessage>,
+    #[prop_or_default]
+    pub is_deleted: bool,
+    #[prop_or_default]
+    pub reply_to: Option<Uuid>,
+    /// The message replied to, if loaded.
+    #[prop_or_default]
+    pub parent: Option<WsMessageContent>,
+    pub translation: Rc<TranslationMap>,
+    pub on_open_thread: Callback<Uuid>,
+    #[prop_or_default]
+    pub is_pinned: bool,
+    #[prop_or_default]
+    pub can_pin: bool,
+}
+
+#[derive(Properties, PartialEq)]
+struct ReactionsProperties {
+    pub message_uuid: Uuid,
+    pub reactions: Vec<ReactionCount>,
+    pub user_id: i32,
+    pub pass_message_to_ws: Callback<WsMessage>,
+}
+
+#[function_component(Reactions)]
+fn reactions(reactions_properties: &ReactionsProperties) -> Html {
+    let user_id = reactions_properties.user_id;
+    let reactions = reactions_properties.reactions.iter().map(|reaction_count| {
+        let has_reacted = reaction_count.user_ids.contains(&user_id);
+        let reaction = Reaction {
+            message_uuid: reactions_properties.message_uuid,
+            user_id,
+            emoji: reaction_count.emoji.clone(),
+        };
+        let pass_message_to_ws = reactions_properties.pass_message_to_ws.clone();
+        let onclick = move |_| {
+            let reaction = reaction.clone();
+            pass_message_to_ws.emit(match has_reacted {
+                true => WsMessage::Unreact(reaction),
+                false => WsMessage::React(reaction),
+            })
+        };
+        html! {
+            <button class={classes!("text-xs", "rounded-full", "px-2", "border", "dark:text-white", if has_reacted { "border-blue-500" } else { "border-gray-300" })} {onclick}>
+                {format!("{} {}", reaction_count.emoji, reaction_count.count)}
+            </button>
+        }
+    });
+    html! {
+        <div class="flex flex-wrap gap-1 mx-2">
+            {for reactions}
+        </div>
+    }
 }
 
 #[function_component(Message)]
 fn message(message_properties: &MessageProperties) -> Html {
     let timestamp = &message_properties.timestamp;
-    let title: AttrValue = format!(
+    let mut title = format!(
         "on {:02}/{:02}/{} at {}:{:02}",
         timestamp.day(),
         timestamp.month(),
         timestamp.year(),
         timestamp.hour(),
         timestamp.minute()
-    )
-    .into();
+    );
+    if let Some(edited_at) = &message_properties.edited_at {
+        title.push_str(&format!(
+            ", edited on {:02}/{:02}/{} at {}:{:02}",
+            edited_at.day(),
+            edited_at.month(),
+            edited_at.year(),
+            edited_at.hour(),
+            edited_at.minute()
+        ));
+    }
+    let title: AttrValue = title.into();
     let reception_checkmark = match message_properties.reception_status {
         WsReceptionStatus::Sent if message_properties.is_user => Some("M4.5 12.75l6 6 9-13.5"),
         WsReceptionStatus::Seen if message_properties.is_user => {
@@ -76,6 +150,31 @@ fn message(message_properties: &MessageProperties) -> Html {
 
     let message_id = message_properties.uuid;
     let is_self = message_properties.is_user;
+    let is_deleted = message_properties.is_deleted;
+    let is_pinned = message_properties.is_pinned;
+    let can_pin = message_properties.can_pin;
+    let content = message_properties.content.to_string();
+    let translation = &message_properties.translation;
+
+    let reply_preview = message_properties.reply_to.map(|reply_to| {
+        let preview = match &message_properties.parent {
+            Some(parent) if !parent.is_deleted => {
+                html! { {format!("{} : {}", parent.author.name, parent.content)} }
+            }
+            Some(_) => {
+                html! { <I18N label={"message_tombstone"} default={"This message has been deleted"} {translation} /> }
+            }
+            None => {
+                html! { <I18N label={"message_unavailable"} default={"Original message unavailable"} {translation} /> }
+            }
+        };
+        let on_open_thread = message_properties.on_open_thread.clone();
+        html! {
+            <small class="cursor-pointer italic truncate max-w-xs border-l-2 border-gray-400 pl-2 mx-2 dark:text-gray-300" onclick={move |_| on_open_thread.emit(reply_to)}>
+                {preview}
+            </small>
+        }
+    });
 
     let hide_timestamp = use_state(|| true);
 
@@ -83,13 +182,29 @@ fn message(message_properties: &MessageProperties) -> Html {
     html! {
         <div id={message_id.to_string()} class={classes!("flex", (!message_properties.is_user).then_some("flex-row-reverse"))}>
             <small hidden={*hide_timestamp} class="dark:text-white mx-2">{&title}</small>
+            <div class={classes!("flex", "flex-col", if message_properties.is_user { "items-end" } else { "items-start" })}>
+            if let Some(reply_preview) = reply_preview {
+                {reply_preview}
+            }
             <p {title} class={classes!(if message_properties.is_user { "message-user" } else { "message-other" } )} onclick={move |_me| hide_timestamp.set(!*hide_timestamp)} oncontextmenu={move |me: MouseEvent|
                 {
                     me.prevent_default();
-                    bridge.send(RMenusBusEvents::OpenRMenu(me.client_x(), me.client_y(), RMenuKind::MessageRMenu(MessageRMenuProps{ message_id, is_self })));
+                    if !is_deleted {
+                        bridge.send(RMenusBusEvents::OpenRMenu(me.client_x(), me.client_y(), RMenuKind::MessageRMenu(MessageRMenuProps{ message_id, is_self, content: content.clone(), is_pinned, can_pin })));
+                    }
                 }}
             >
-                {message_properties.content.as_str()}
+                if is_deleted {
+                    <i><I18N label={"message_tombstone"} default={"This message has been deleted"} {translation} /></i>
+                } else {
+                    {message_properties.content.as_str()}
+                }
+                if message_properties.edited_at.is_some() {
+                    <small class="italic ml-1">{"✎"}</small>
+                }
+                if is_pinned {
+                    <small class="ml-1">{"📌"}</small>
+                }
                     <span class="absolute right-0 bottom-0 pb-1 pr-1">
                     if let Some(reception_checkmark) = reception_checkmark {
                         <svg xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 24 24" stroke-width="1.5" stroke="currentColor" class="w-2 h-2">
@@ -98,6 +213,10 @@ fn message(message_properties: &MessageProperties) -> Html {
                     }
                     </span>
             </p>
+            if !message_properties.reactions.is_empty() {
+                <Reactions message_uuid={message_id} reactions={message_properties.reactions.clone()} user_id={message_properties.user_id} pass_message_to_ws={message_properties.pass_message_to_ws.clone()} />
+            }
+            </div>
         </div>
     }
 }
@@ -116,6 +235,24 @@ struct UserChatProperties {
     pub display_pfp: bool,
     pub reception_status: WsReceptionStatus,
     pub author_id: i32,
+    #[prop_or_default]
+    pub edited_at: Option<DateTime<Utc>>,
+    #[prop_or_default]
+    pub reactions: Vec<ReactionCount>,
+    pub user_id: i32,
+    pub pass_message_to_ws: Callback<WsMessage>,
+    #[prop_or_default]
+    pub is_deleted: bool,
+    #[prop_or_default]
+    pub reply_to: Option<Uuid>,
+    #[prop_or_default]
+    pub parent: Option<WsMessageContent>,
+    pub translation: Rc<TranslationMap>,
+    pub on_open_thread: Callback<Uuid>,
+    #[prop_or_default]
+    pub is_pinned: bool,
+    #[prop_or_default]
+    pub can_pin: bool,
 }
 
 #[function_component(UserChat)]
@@ -125,16 +262,23 @@ fn user_chat(user_chat_properties: &UserChatProperties) -> Html {
             if !user_chat_properties.is_user {
                 <ProfilePicture pfp={user_chat_properties.pfp.clone()} author={user_chat_properties.author.clone()} display_pfp={user_chat_properties.display_pfp} author_id={user_chat_properties.author_id} is_self={user_chat_properties.is_user}/>
             }
-            <Message uuid={user_chat_properties.uuid} reception_status={user_chat_properties.reception_status} content={user_chat_properties.content.clone()} is_user={user_chat_properties.is_user} timestamp={user_chat_properties.timestamp} />
+            <Message uuid={user_chat_properties.uuid} reception_status={user_chat_properties.reception_status} content={user_chat_properties.content.clone()} is_user={user_chat_properties.is_user} timestamp={user_chat_properties.timestamp} edited_at={user_chat_properties.edited_at} reactions={user_chat_properties.reactions.clone()} user_id={user_chat_properties.user_id} pass_message_to_ws={user_chat_properties.pass_message_to_ws.clone()} is_deleted={user_chat_properties.is_deleted} reply_to={user_chat_properties.reply_to} parent={user_chat_properties.parent.clone()} translation={user_chat_properties.translation.clone()} on_open_thread={user_chat_properties.on_open_thread.clone()} is_pinned={user_chat_properties.is_pinned} can_pin={user_chat_properties.can_pin} />
         </div>
     }
 }
 
-#[derive(Clone, Eq, PartialEq, Properties)]
+#[derive(Clone, PartialEq, Properties)]
 pub struct Props {
     pub messages: Vec<WsMessageContent>,
     pub room: AttrValue,
     pub user: PartialUser,
+    pub pass_message_to_ws: Callback<WsMessage>,
+    pub translation: Rc<TranslationMap>,
+    pub on_open_thread: Callback<Uuid>,
+    #[prop_or_default]
+    pub pinned: Vec<Uuid>,
+    #[prop_or_default]
+    pub can_pin: bool,
 }
 
 pub struct Chat;
@@ -164,7 +308,15 @@ impl Component for Chat {
                     // so we display the pfp for the first message
                     _ => true,
                 };
-            html_content.push(html! { <UserChat uuid={current_element.uuid} pfp={current_element.author.pfp.clone().unwrap_or_else(|| DEFAULT_PFP.into())} reception_status={current_element.reception_status} content={current_element.content.clone()} author_id={current_element.author.id} author={current_element.author.name.clone()} is_user={current_element.author.id == current_user_id} timestamp={current_element.timestamp + user_offset} {display_pfp}/> });
+            let parent = current_element.reply_to.and_then(|reply_to| {
+                ctx.props()
+                    .messages
+                    .iter()
+                    .find(|message| message.uuid == reply_to)
+                    .cloned()
+            });
+            let is_pinned = ctx.props().pinned.contains(&current_element.uuid);
+            html_content.push(html! { <UserChat uuid={current_element.uuid} pfp={current_element.author.pfp.clone().unwrap_or_else(|| DEFAULT_PFP.into())} reception_status={current_element.reception_status} content={current_element.content.clone()} author_id={current_element.author.id} author={current_element.author.name.clone()} is_user={current_element.author.id == current_user_id} timestamp={current_element.timestamp + user_offset} edited_at={current_element.edited_at.map(|edited_at| edited_at + user_offset)} reactions={current_element.reactions.clone()} user_id={current_user_id} pass_message_to_ws={ctx.props().pass_message_to_ws.clone()} is_deleted={current_element.is_deleted} reply_to={current_element.reply_to} {parent} translation={ctx.props().translation.clone()} on_open_thread={ctx.props().on_open_thread.clone()} {is_pinned} can_pin={ctx.props().can_pin} {display_pfp}/> });
         }
         html_content.into_iter().collect::<Html>()
     }
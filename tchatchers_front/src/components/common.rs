@@ -5,7 +5,7 @@ use std::rc::Rc;
 // This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
 use tchatchers_core::locale::TranslationMap;
 use web_sys::MouseEvent;
-use web_sys::{HtmlInputElement, SubmitEvent};
+use web_sys::{HtmlInputElement, InputEvent, SubmitEvent};
 use yew::html::ChildrenRenderer;
 use yew::virtual_dom::VChild;
 use yew::{
@@ -185,6 +185,11 @@ pub struct FormInputProperties {
     pub value: AttrValue,
     #[prop_or_default]
     pub autofocus: bool,
+    /// The ID of the datalist suggesting values for the input.
+    #[prop_or_default]
+    pub list: Option<AttrValue>,
+    #[prop_or_default]
+    pub oninput: Callback<InputEvent>,
 }
 
 #[function_component(FormInput)]
@@ -198,7 +203,7 @@ pub fn form_input(props: &FormInputProperties) -> Html {
             </label>
             </div>
                 <div class="md:w-2/3">
-                <input autofocus={props.autofocus} class="common-input" type={&props.input_type} required={props.required} minlength={&props.minlength} maxlength={&props.maxlength} ref={&props.attr_ref} disabled={props.disabled} value={&props.value} />
+                <input autofocus={props.autofocus} class="common-input" type={&props.input_type} required={props.required} minlength={&props.minlength} maxlength={&props.maxlength} ref={&props.attr_ref} disabled={props.disabled} value={&props.value} list={&props.list} oninput={&props.oninput} />
             </div>
         </div>
     }
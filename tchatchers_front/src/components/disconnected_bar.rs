@@ -26,9 +26,31 @@ pub fn try_reconnect(props: &TryReconnectProps) -> Html {
     }
 }
 
+#[derive(Properties, PartialEq)]
+pub struct ReloadRequiredProps {
+    translation: Rc<TranslationMap>,
+}
+
+#[function_component(ReloadRequired)]
+pub fn reload_required(props: &ReloadRequiredProps) -> Html {
+    let translation = &props.translation;
+    html! {
+        <div class="flex items-center justify-center gap-2 lg:gap-12 dark:text-gray-200">
+            <span>
+            <I18N  label={"client_outdated"} default={"A new version is available, please reload the page"} {translation} />
+            </span>
+            <button class="common-button" onclick={move |_| {let _ = web_sys::window().unwrap().location().reload();}} >
+            <I18N  label={"reload"} default={"Reload"} {translation} />
+            </button>
+        </div>
+    }
+}
+
 #[derive(Clone, PartialEq, Properties)]
 pub struct Props {
     pub called_back: bool,
+    #[prop_or_default]
+    pub is_outdated: bool,
     pub translation: Rc<TranslationMap>,
 }
 
@@ -46,7 +68,9 @@ impl Component for DisconnectedBar {
         let translation = &ctx.props().translation;
         html! {
             <div class="col-span-6 mb-6">
-                if ctx.props().called_back {
+                if ctx.props().is_outdated {
+                    <ReloadRequired {translation} />
+                } else if ctx.props().called_back {
                     <TryReconnect {translation} />
                 } else {
                     <WaitingForResponse {translation} />
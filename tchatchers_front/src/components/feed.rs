@@ -4,26 +4,47 @@ use std::rc::Rc;
 // This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
 use super::chat::Chat;
 use super::disconnected_bar::DisconnectedBar;
+use super::member_list::MemberList;
+use super::thread_panel::ThreadPanel;
 use super::type_bar::TypeBar;
 use crate::router::Route;
 use crate::utils;
 use crate::utils::client_context::ClientContext;
 use crate::utils::requester::Requester;
 use chat_service::{ChatReactor, WebSocketReactorControl};
+use chrono::{DateTime, Utc};
 use gloo_timers::callback::{Interval, Timeout};
-use tchatchers_core::room::RoomNameValidator;
-use tchatchers_core::ws_message::{WsMessage, WsMessageContent, WsReceptionStatus};
+use rmenu_service::{MessageAction, RMenuBus, RMenusBusEvents};
+use tchatchers_core::api_response::{ApiGenericResponse, ApiResponse};
+use tchatchers_core::direct_message::DirectConversation;
+use tchatchers_core::profile::Profile;
+use tchatchers_core::reaction::Reaction;
+use tchatchers_core::room::{Room, RoomNameValidator};
+use tchatchers_core::room_pin::RoomPin;
+use tchatchers_core::user::PartialUser;
+use tchatchers_core::ws_message::{
+    PostRejection, WsMessage, WsMessageContent, WsReceptionStatus, DEFAULT_PAGE_SIZE,
+};
 use toast_service::{Alert, ToastBus};
 use uuid::Uuid;
 use validator::Validate;
+use web_sys::{Element, Event};
 use yew::{
     function_component, html, use_context, AttrValue, Component, Context, Html, Properties,
-    UseStateHandle,
+    TargetCast, UseStateHandle,
 };
 use yew_agent::reactor::{use_reactor_subscription, UseReactorSubscriptionHandle};
 use yew_agent::worker::{use_worker_subscription, UseWorkerSubscriptionHandle};
 use yew_router::scope_ext::RouterScopeExt;
 
+/// Distance, in pixels, from the top of the feed under which older messages
+/// are loaded.
+const LOAD_OLDER_MESSAGES_THRESHOLD: i32 = 100;
+
+/// The delays, in milliseconds, before the successive attempts to reopen a
+/// lost socket.
+const RECONNECT_DELAYS: [u32; 5] = [1_000, 2_000, 5_000, 10_000, 30_000];
+
 #[derive(Properties, PartialEq, Clone)]
 pub struct FeedHOCProps {
     pub room: AttrValue,
@@ -37,14 +58,25 @@ pub fn feed_hoc(props: &FeedHOCProps) -> Html {
 
     let toaster = use_worker_subscription::<ToastBus>();
 
-    html! { <Feed room={props.room.clone()} {client_context}  {toaster} {reactor} /> }
+    let rmenu_bus = use_worker_subscription::<RMenuBus>();
+
+    html! { <Feed room={props.room.clone()} {client_context}  {toaster} {reactor} {rmenu_bus} /> }
 }
 
 #[derive(Clone)]
 pub enum Msg {
+    OpenWs(Option<String>),
+    Reconnect,
+    ReopenWs(Option<String>),
     CheckWsState,
     CutWs,
     Authenticate,
+    OpenThread(Uuid),
+    CloseThread,
+    CancelReply,
+    LoadOlderMessages,
+    SlowModeElapsed,
+    TogglePinBar,
 }
 
 #[derive(Clone, PartialEq, Properties)]
@@ -53,6 +85,7 @@ pub struct Props {
     pub client_context: Rc<ClientContext>,
     pub reactor: UseReactorSubscriptionHandle<ChatReactor>,
     pub toaster: UseWorkerSubscriptionHandle<ToastBus>,
+    pub rmenu_bus: UseWorkerSubscriptionHandle<RMenuBus>,
 }
 
 pub struct Feed {
@@ -60,12 +93,113 @@ pub struct Feed {
     timeout: Option<Timeout>,
     called_back: bool,
     is_connected: bool,
+    is_authenticated: bool,
     ws_keep_alive: Option<Interval>,
     is_closed: bool,
     session_id: Uuid,
     room_name_checked: bool,
     user_context: ClientContext,
     bearer: UseStateHandle<Option<String>>,
+    typing_users: Vec<PartialUser>,
+    members: Vec<PartialUser>,
+    replying_to: Option<WsMessageContent>,
+    thread: Option<Vec<WsMessageContent>>,
+    has_older_messages: bool,
+    is_loading_older_messages: bool,
+    is_outdated: bool,
+    room_metadata: Option<Room>,
+    /// The delay the user has to wait before posting again under slow mode.
+    slow_mode_cooldown: Option<(u64, Timeout)>,
+    /// The messages pinned in the room, the latest pinned first.
+    pins: Vec<RoomPin>,
+    is_pin_bar_open: bool,
+    /// The number of attempts made to reopen the socket since it was lost.
+    reconnect_attempts: usize,
+    reconnect_timeout: Option<Timeout>,
+    /// The timestamp of the latest message of the room read by the user.
+    last_read_at: Option<DateTime<Utc>>,
+}
+
+impl Feed {
+    /// Requests a ticket authenticating the socket, passing it to the given
+    /// message once received.
+    fn request_ws_ticket(ctx: &Context<Self>, msg: fn(Option<String>) -> Msg) {
+        let mut req = Requester::post("/api/ws_ticket");
+        req.bearer(ctx.props().client_context.bearer.clone());
+        let link = ctx.link().clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let resp = req.send().await;
+            let ticket = match resp.ok() {
+                true => resp.text().await.ok(),
+                false => None,
+            };
+            link.send_message(msg(ticket));
+        });
+    }
+
+    /// Sends a ping once the socket is opened, to check its state.
+    fn check_ws_state(&mut self, ctx: &Context<Self>) {
+        let link = ctx.link().clone();
+        self.timeout = Some(Timeout::new(1, move || {
+            link.send_message(Msg::CheckWsState)
+        }));
+    }
+
+    /// Prevents the user from posting during the given number of seconds.
+    fn start_slow_mode_cooldown(&mut self, ctx: &Context<Self>, seconds: u64) {
+        let link = ctx.link().clone();
+        let timeout = Timeout::new((seconds * 1_000) as u32, move || {
+            link.send_message(Msg::SlowModeElapsed)
+        });
+        self.slow_mode_cooldown = Some((seconds, timeout));
+    }
+
+    /// Applies a change on a message, either displayed in the feed or in the
+    /// opened thread.
+    fn update_message(&mut self, uuid: Uuid, f: impl Fn(&mut WsMessageContent)) {
+        let thread_messages = self.thread.iter_mut().flatten();
+        for message in self
+            .received_messages
+            .iter_mut()
+            .chain(thread_messages)
+            .filter(|message| message.uuid == uuid)
+        {
+            f(message);
+        }
+    }
+
+    /// Returns the UUIDs of the messages the user hasn't read yet, moving their
+    /// read position forward.
+    fn unread_messages(&mut self, messages: &[WsMessageContent]) -> Vec<Uuid> {
+        let user_id = self.user_context.user.as_ref().unwrap().id;
+        let last_read_at = self.last_read_at;
+        let unread: Vec<&WsMessageContent> = messages
+            .iter()
+            .filter(|message| {
+                message.author.id != user_id
+                    && last_read_at.map_or(true, |read_at| message.timestamp > read_at)
+            })
+            .collect();
+        if let Some(latest) = unread.iter().map(|message| message.timestamp).max() {
+            self.last_read_at = Some(latest);
+        }
+        unread.into_iter().map(|message| message.uuid).collect()
+    }
+
+    /// Removes a deleted message, or turns it into a tombstone if it has been
+    /// replied to.
+    fn delete_message(messages: &mut Vec<WsMessageContent>, uuid: Uuid) {
+        if messages
+            .iter()
+            .any(|message| message.reply_to == Some(uuid))
+        {
+            if let Some(message) = messages.iter_mut().find(|message| message.uuid == uuid) {
+                message.tombstone();
+            }
+        } else {
+            messages.retain(|message| message.uuid != uuid);
+        }
+    }
 }
 
 impl Component for Feed {
@@ -73,32 +207,70 @@ impl Component for Feed {
     type Properties = Props;
 
     fn create(ctx: &Context<Self>) -> Self {
-        let reactor = &ctx.props().reactor;
-        reactor.send(WebSocketReactorControl::Open(utils::get_ws_room_address(
-            &ctx.props().room,
-        )));
+        Self::request_ws_ticket(ctx, Msg::OpenWs);
 
         Self {
             received_messages: vec![],
             is_connected: false,
+            is_authenticated: false,
             called_back: false,
             is_closed: false,
+            is_outdated: false,
+            room_metadata: None,
+            slow_mode_cooldown: None,
+            pins: vec![],
+            is_pin_bar_open: false,
+            reconnect_attempts: 0,
+            reconnect_timeout: None,
+            last_read_at: None,
             ws_keep_alive: None,
-            timeout: {
-                let link = ctx.link().clone();
-                Some(Timeout::new(1, move || {
-                    link.send_message(Msg::CheckWsState)
-                }))
-            },
+            timeout: None,
             session_id: Uuid::new_v4(),
             room_name_checked: false,
             user_context: ctx.props().client_context.as_ref().clone(),
             bearer: ctx.props().client_context.bearer.clone(),
+            typing_users: vec![],
+            members: vec![],
+            replying_to: None,
+            thread: None,
+            has_older_messages: true,
+            is_loading_older_messages: false,
         }
     }
 
     fn changed(&mut self, ctx: &Context<Self>, old_props: &Self::Properties) -> bool {
         let reactor = &ctx.props().reactor;
+        let rmenu_bus = &ctx.props().rmenu_bus;
+        let mut should_render = false;
+        if old_props.rmenu_bus.len() < rmenu_bus.len() {
+            if let Some(RMenusBusEvents::MessageAction(action)) =
+                rmenu_bus.last().map(|event| (**event).clone())
+            {
+                let message = match action {
+                    MessageAction::Edit(uuid, content) => Some(WsMessage::Edit { uuid, content }),
+                    MessageAction::React(message_uuid, emoji) => Some(WsMessage::React(Reaction {
+                        message_uuid,
+                        user_id: self.user_context.user.as_ref().unwrap().id,
+                        emoji,
+                    })),
+                    MessageAction::Reply(uuid) => {
+                        self.replying_to = self
+                            .received_messages
+                            .iter()
+                            .find(|message| message.uuid == uuid)
+                            .cloned();
+                        should_render = true;
+                        None
+                    }
+                    MessageAction::OpenThread(uuid) => Some(WsMessage::RetrieveThread(uuid)),
+                    MessageAction::Pin(uuid) => Some(WsMessage::Pin(uuid)),
+                    MessageAction::Unpin(uuid) => Some(WsMessage::Unpin(uuid)),
+                };
+                if let Some(message) = message {
+                    reactor.send(WebSocketReactorControl::Send(message));
+                }
+            }
+        }
         let old_reactor_length = old_props.reactor.len();
         if old_reactor_length < reactor.len() {
             let Some(last_msg) = reactor.last().cloned() else {
@@ -120,9 +292,11 @@ impl Component for Feed {
                             }
                         });
                         if !self.room_name_checked {
-                            if let Err(_e) =
-                                RoomNameValidator::from(ctx.props().room.to_string()).validate()
-                            {
+                            let room = ctx.props().room.to_string();
+                            // The access to direct conversations is checked by the server.
+                            let is_valid_room = DirectConversation::from_room(&room).is_some()
+                                || RoomNameValidator::from(room).validate().is_ok();
+                            if !is_valid_room {
                                 ctx.props().toaster.send(Alert {
                                         is_success: false,
                                         label: "room_name_incorrect".into(),
@@ -135,31 +309,155 @@ impl Component for Feed {
                         }
                         self.ws_keep_alive = None;
                         self.is_connected = false;
+                        self.is_authenticated = false;
+                        self.typing_users.clear();
+                        self.members.clear();
+                        // The socket is reopened, the messages missed being
+                        // replayed once authenticated again.
+                        if self.room_name_checked && self.reconnect_timeout.is_none() {
+                            let delay = RECONNECT_DELAYS
+                                [self.reconnect_attempts.min(RECONNECT_DELAYS.len() - 1)];
+                            let link = ctx.link().clone();
+                            self.reconnect_timeout = Some(Timeout::new(delay, move || {
+                                link.send_message(Msg::Reconnect)
+                            }));
+                        }
                     }
                 }
                 WsMessage::Receive(msg_content) => {
+                    self.typing_users.retain(|u| u.id != msg_content.author.id);
+                    if let Some(thread_messages) = &mut self.thread {
+                        if thread_messages
+                            .iter()
+                            .any(|message| Some(message.uuid) == msg_content.reply_to)
+                        {
+                            thread_messages.push(msg_content.clone());
+                        }
+                    }
                     self.received_messages.insert(0, msg_content.clone());
-                    if msg_content.reception_status == WsReceptionStatus::Sent
-                        && msg_content.author.id != self.user_context.user.as_ref().unwrap().id
-                    {
-                        reactor.send(WebSocketReactorControl::Send(WsMessage::Seen(vec![
-                            msg_content.uuid,
-                        ])));
+                    let user = self.user_context.user.as_ref().unwrap();
+                    if msg_content.author.id == user.id {
+                        if let Some(slow_mode) = self
+                            .room_metadata
+                            .as_ref()
+                            .and_then(|room| room.slow_mode_for(user.profile))
+                        {
+                            self.start_slow_mode_cooldown(ctx, slow_mode as u64);
+                        }
+                    }
+                    let messages_seen = self.unread_messages(&[msg_content]);
+                    if !messages_seen.is_empty() {
+                        reactor.send(WebSocketReactorControl::Send(WsMessage::Seen(
+                            messages_seen,
+                        )));
                     }
                 }
                 WsMessage::MessagesRetrieved {
                     mut messages,
                     session_id,
+                    pins,
+                    last_read_at,
                 } if session_id == self.session_id => {
-                    let messages_seen: Vec<Uuid> = messages
-                        .clone()
+                    self.pins = pins;
+                    // The read position of the server might lag behind the
+                    // messages seen lately, as it is persisted asynchronously.
+                    self.last_read_at = self.last_read_at.max(last_read_at);
+                    let messages_seen = self.unread_messages(&messages);
+                    self.received_messages.append(&mut messages);
+
+                    if !messages_seen.is_empty() {
+                        reactor.send(WebSocketReactorControl::Send(WsMessage::Seen(
+                            messages_seen,
+                        )));
+                    }
+                }
+                WsMessage::Resync(missed) => {
+                    gloo_console::warn!(format!("{missed} messages missed, resyncing"));
+                    self.received_messages.clear();
+                    self.has_older_messages = true;
+                    self.is_loading_older_messages = false;
+                    reactor.send(WebSocketReactorControl::Send(WsMessage::RetrieveMessages(
+                        self.session_id,
+                    )));
+                }
+                WsMessage::MessagesResumed {
+                    messages,
+                    is_complete,
+                } => {
+                    if !is_complete {
+                        gloo_console::warn!("Too many messages missed, resyncing");
+                        self.received_messages.clear();
+                        self.has_older_messages = true;
+                        self.is_loading_older_messages = false;
+                        reactor.send(WebSocketReactorControl::Send(WsMessage::RetrieveMessages(
+                            self.session_id,
+                        )));
+                    } else {
+                        let messages_seen = self.unread_messages(&messages);
+                        // The messages already received while resuming are replaced
+                        // by their latest version.
+                        for message in messages {
+                            match self
+                                .received_messages
+                                .iter_mut()
+                                .find(|m| m.uuid == message.uuid)
+                            {
+                                Some(received) => *received = message,
+                                None => self.received_messages.push(message),
+                            }
+                        }
+                        self.received_messages
+                            .sort_by_key(|message| std::cmp::Reverse(message.cursor()));
+                        if !messages_seen.is_empty() {
+                            reactor.send(WebSocketReactorControl::Send(WsMessage::Seen(
+                                messages_seen,
+                            )));
+                        }
+                    }
+                }
+                WsMessage::Mentioned(notification) => {
+                    ctx.props().toaster.send(Alert {
+                        is_success: true,
+                        label: "mentioned_you".into(),
+                        default: format!(
+                            "{} mentioned you in #{}",
+                            notification.author.name, notification.room
+                        ),
+                    });
+                }
+                WsMessage::Welcome { server_version, .. } => {
+                    gloo_console::debug!(format!("Connected to the server {server_version}"));
+                    self.is_connected = true;
+                }
+                WsMessage::IncompatibleVersion => {
+                    self.is_outdated = true;
+                    self.is_closed = true;
+                    self.is_connected = false;
+                    self.ws_keep_alive = None;
+                }
+                WsMessage::RateLimited { retry_after } => {
+                    gloo_console::warn!(format!("Rate limited, retry after {retry_after}s"));
+                    let api_resp: ApiResponse = ApiGenericResponse::TooManyRequests.into();
+                    ctx.props().toaster.send(Alert {
+                        is_success: false,
+                        label: api_resp.label,
+                        default: api_resp.text.unwrap_or_default(),
+                    });
+                }
+                WsMessage::MessagesBeforeRetrieved(page) => {
+                    self.is_loading_older_messages = false;
+                    self.has_older_messages = page.has_more;
+                    let mut messages: Vec<WsMessageContent> = page
+                        .messages
                         .into_iter()
                         .filter(|message| {
-                            message.reception_status == WsReceptionStatus::Sent
-                                && message.author.id != self.user_context.user.as_ref().unwrap().id
+                            !self
+                                .received_messages
+                                .iter()
+                                .any(|m| m.uuid == message.uuid)
                         })
-                        .map(|m| m.uuid)
                         .collect();
+                    let messages_seen = self.unread_messages(&messages);
                     self.received_messages.append(&mut messages);
 
                     if !messages_seen.is_empty() {
@@ -171,25 +469,131 @@ impl Component for Feed {
                 WsMessage::Pong => {
                     self.is_connected = true;
 
-                    ctx.link().send_message(Msg::Authenticate);
+                    if !self.is_authenticated {
+                        ctx.link().send_message(Msg::Authenticate);
+                    }
                 }
-                WsMessage::MessagesSeen(msgs_uuid) => {
+                WsMessage::MessagesSeen { reader, messages } => {
                     for msg in self.received_messages.iter_mut() {
-                        if msgs_uuid.contains(&msg.uuid) {
+                        if messages.contains(&msg.uuid) && msg.author.id != reader.id {
                             msg.reception_status = WsReceptionStatus::Seen;
                         }
                     }
                 }
                 WsMessage::Delete(msg_uuid) => {
-                    reactor.send(WebSocketReactorControl::Send(message));
-                    self.received_messages.retain(|msg| msg_uuid != msg.uuid);
+                    Self::delete_message(&mut self.received_messages, msg_uuid);
+                    if let Some(thread_messages) = &mut self.thread {
+                        Self::delete_message(thread_messages, msg_uuid);
+                    }
+                    if self.replying_to.as_ref().map(|msg| msg.uuid) == Some(msg_uuid) {
+                        self.replying_to = None;
+                    }
+                    self.pins.retain(|pin| pin.message.uuid != msg_uuid);
+                }
+                WsMessage::MessageEdited {
+                    uuid,
+                    content,
+                    edited_at,
+                } => {
+                    self.update_message(uuid, |msg| {
+                        msg.content = content.clone();
+                        msg.edited_at = Some(edited_at);
+                    });
+                }
+                WsMessage::React(reaction) => {
+                    self.update_message(reaction.message_uuid, |msg| {
+                        msg.add_reaction(reaction.user_id, &reaction.emoji)
+                    });
+                }
+                WsMessage::Unreact(reaction) => {
+                    self.update_message(reaction.message_uuid, |msg| {
+                        msg.remove_reaction(reaction.user_id, &reaction.emoji)
+                    });
+                }
+                WsMessage::ThreadRetrieved { messages, .. } => {
+                    self.thread = Some(messages);
+                }
+                WsMessage::Typing(user) => {
+                    if user.id != self.user_context.user.as_ref().unwrap().id
+                        && !self.typing_users.iter().any(|u| u.id == user.id)
+                    {
+                        self.typing_users.push(user);
+                    }
+                }
+                WsMessage::StoppedTyping(user) => {
+                    self.typing_users.retain(|u| u.id != user.id);
+                }
+                WsMessage::Pinned(pin) => {
+                    self.pins.retain(|p| p.message.uuid != pin.message.uuid);
+                    self.pins.insert(0, pin);
+                }
+                WsMessage::Unpinned(uuid) => {
+                    self.pins.retain(|pin| pin.message.uuid != uuid);
+                }
+                WsMessage::RoomUpdated(room) => {
+                    self.room_metadata = Some(room);
+                }
+                WsMessage::RoomAccessRevoked(_) => {
+                    ctx.props().toaster.send(Alert {
+                        is_success: false,
+                        label: "room_access_revoked".into(),
+                        default: "You can no longer access this room.".into(),
+                    });
+                    ctx.link().navigator().unwrap().push(&Route::JoinRoom);
+                }
+                WsMessage::PresenceSnapshot(members) => {
+                    self.members = members;
+                }
+                WsMessage::UserJoined(user) => {
+                    if !self.members.iter().any(|u| u.id == user.id) {
+                        self.members.push(user);
+                    }
+                }
+                WsMessage::UserLeft(user) => {
+                    self.members.retain(|u| u.id != user.id);
+                    self.typing_users.retain(|u| u.id != user.id);
+                }
+                WsMessage::MessageRejected(_) => {
+                    ctx.props().toaster.send(Alert {
+                        is_success: false,
+                        label: "message_rejected".into(),
+                        default: "Your message has been rejected by the server".into(),
+                    });
+                }
+                WsMessage::PostRejected { reason, .. } => {
+                    let (label, default) = match reason {
+                        PostRejection::AnnouncementOnly => (
+                            "announcement_only",
+                            "Only the moderators can post in this room.",
+                        ),
+                        PostRejection::SlowMode { retry_after } => {
+                            self.start_slow_mode_cooldown(ctx, retry_after);
+                            (
+                                "slow_mode_on",
+                                "Slow mode is on, please wait before posting again.",
+                            )
+                        }
+                    };
+                    ctx.props().toaster.send(Alert {
+                        is_success: false,
+                        label: label.into(),
+                        default: default.into(),
+                    });
                 }
                 WsMessage::AuthenticationRequired => ctx.link().send_message(Msg::Authenticate),
                 WsMessage::AuthenticationValidated => {
-                    if self.received_messages.is_empty() {
-                        let msg = WsMessage::RetrieveMessages(self.session_id);
+                    if !self.is_authenticated {
+                        // The messages missed while disconnected are replayed
+                        // rather than retrieved again.
+                        let msg = match self.received_messages.first() {
+                            Some(latest) => WsMessage::Resume {
+                                since_uuid: latest.uuid,
+                            },
+                            None => WsMessage::RetrieveMessages(self.session_id),
+                        };
 
                         reactor.send(WebSocketReactorControl::Send(msg));
+                        self.reconnect_attempts = 0;
                         self.ws_keep_alive = {
                             let reactor = reactor.clone();
                             Some(Interval::new(30_000, move || {
@@ -199,8 +603,10 @@ impl Component for Feed {
                             }))
                         }
                     }
+                    self.is_authenticated = true;
                 }
                 WsMessage::AuthenticationExpired => {
+                    self.is_authenticated = false;
                     let mut req = Requester::get("/api/validate");
                     req.bearer(self.bearer.clone());
                     let link = ctx.link().clone();
@@ -221,13 +627,39 @@ impl Component for Feed {
             true
         // Other props can't change
         } else {
-            false
+            should_render
         }
     }
 
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         let reactor = &ctx.props().reactor;
         match msg {
+            Msg::OpenWs(ticket) => {
+                reactor.send(WebSocketReactorControl::Open(utils::get_ws_room_address(
+                    &ctx.props().room,
+                    ticket.as_deref(),
+                )));
+                self.check_ws_state(ctx);
+                false
+            }
+            Msg::Reconnect => {
+                if !self.is_closed {
+                    Self::request_ws_ticket(ctx, Msg::ReopenWs);
+                }
+                false
+            }
+            Msg::ReopenWs(ticket) => {
+                self.reconnect_timeout = None;
+                if self.is_closed {
+                    return false;
+                }
+                self.reconnect_attempts += 1;
+                reactor.send(WebSocketReactorControl::Reconnect(
+                    utils::get_ws_room_address(&ctx.props().room, ticket.as_deref()),
+                ));
+                self.check_ws_state(ctx);
+                false
+            }
             Msg::CheckWsState => {
                 reactor.send(WebSocketReactorControl::Send(WsMessage::Ping));
                 if self.timeout.is_some() {
@@ -248,24 +680,151 @@ impl Component for Feed {
                 }
                 false
             }
+            Msg::OpenThread(uuid) => {
+                reactor.send(WebSocketReactorControl::Send(WsMessage::RetrieveThread(
+                    uuid,
+                )));
+                false
+            }
+            Msg::CloseThread => {
+                self.thread = None;
+                true
+            }
+            Msg::CancelReply => {
+                self.replying_to = None;
+                true
+            }
+            Msg::LoadOlderMessages => {
+                if !self.is_authenticated
+                    || !self.has_older_messages
+                    || self.is_loading_older_messages
+                {
+                    return false;
+                }
+                let Some(oldest_message) = self.received_messages.last() else {
+                    return false;
+                };
+                self.is_loading_older_messages = true;
+                reactor.send(WebSocketReactorControl::Send(
+                    WsMessage::RetrieveMessagesBefore {
+                        cursor: oldest_message.cursor(),
+                        limit: DEFAULT_PAGE_SIZE,
+                    },
+                ));
+                false
+            }
+            Msg::SlowModeElapsed => {
+                self.slow_mode_cooldown = None;
+                true
+            }
+            Msg::TogglePinBar => {
+                self.is_pin_bar_open = !self.is_pin_bar_open;
+                true
+            }
         }
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
         let translation = &ctx.props().client_context.translation;
         let reactor = &ctx.props().reactor;
-        html! {
-            <div class="grid grid-rows-11 auto-rows-fr h-full dark:bg-zinc-800">
-                <div class="row-span-10 overflow-auto flex flex-col-reverse max-h-full mt-4" >
-                    <Chat messages={self.received_messages.clone()} room={ctx.props().room.clone()} user={self.user_context.user.as_ref().unwrap().clone()} />
-                </div>
-                <div class="row-span-1 grid grid-cols-6 px-5 gap-4 justify-center content-center block ">
-                    if self.is_connected {
-                        <TypeBar {translation} pass_message_to_ws={{let reactor = reactor.clone(); move |message| reactor.send(WebSocketReactorControl::Send(message))}} user={self.user_context.user.as_ref().unwrap().clone()} room={ctx.props().room.clone()} />
-                    } else {
-                        <DisconnectedBar {translation} called_back={self.called_back}  />
+        let onscroll = ctx.link().batch_callback(|e: Event| {
+            let feed: Element = e.target_unchecked_into();
+            // The feed is displayed in reverse, so it is scrolled up with negative values.
+            let distance_to_top =
+                feed.scroll_height() - feed.client_height() - feed.scroll_top().abs();
+            (distance_to_top < LOAD_OLDER_MESSAGES_THRESHOLD).then_some(Msg::LoadOlderMessages)
+        });
+        let typing_indicator = match self.typing_users.as_slice() {
+            [] => None,
+            [user] => Some(format!(
+                "{} {}",
+                user.name,
+                translation.get_or_default("is_typing", "is typing…")
+            )),
+            users => Some(format!(
+                "{} {}",
+                users
+                    .iter()
+                    .map(|user| user.name.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", "),
+                translation.get_or_default("are_typing", "are typing…")
+            )),
+        };
+        let user = self.user_context.user.as_ref().unwrap();
+        let restriction = match (&self.room_metadata, &self.slow_mode_cooldown) {
+            (Some(room), _) if !room.can_post(user.profile) => {
+                Some(PostRejection::AnnouncementOnly)
+            }
+            (_, Some((retry_after, _))) => Some(PostRejection::SlowMode {
+                retry_after: *retry_after,
+            }),
+            _ => None,
+        };
+        let can_pin = user.profile >= Profile::Moderator
+            || self
+                .room_metadata
+                .as_ref()
+                .is_some_and(|room| room.can_be_updated_by(user.id, user.profile));
+        let pinned: Vec<Uuid> = self.pins.iter().map(|pin| pin.message.uuid).collect();
+        let pin_bar = (!self.pins.is_empty()).then(|| {
+            let pins = self.pins.iter().map(|pin| {
+                let uuid = pin.message.uuid;
+                html! {
+                    <li class="truncate cursor-pointer hover:text-gray-500" onclick={ctx.link().callback(move |_| Msg::OpenThread(uuid))}>
+                        {format!("{} : {}", pin.message.author.name, pin.message.content)}
+                    </li>
+                }
+            });
+            html! {
+                <div class="px-5 py-1 text-sm dark:text-gray-300">
+                    <button class="font-semibold" onclick={ctx.link().callback(|_| Msg::TogglePinBar)}>
+                        {format!("📌 {} ({}) {}", translation.get_or_default("pinned_messages", "Pinned messages"), self.pins.len(), if self.is_pin_bar_open { "▴" } else { "▾" })}
+                    </button>
+                    if self.is_pin_bar_open {
+                        <ul class="max-h-40 overflow-auto">
+                            {for pins}
+                        </ul>
                     }
                 </div>
+            }
+        });
+        let topic = self
+            .room_metadata
+            .as_ref()
+            .and_then(|room| room.topic.as_ref());
+        html! {
+            <div class="flex h-full dark:bg-zinc-800">
+                <div class="grid grid-rows-11 auto-rows-fr h-full flex-grow">
+                    <div class="row-span-10 overflow-auto flex flex-col-reverse max-h-full mt-4" {onscroll}>
+                        if let Some(typing_indicator) = typing_indicator {
+                            <small class="italic px-5 dark:text-gray-300">{typing_indicator}</small>
+                        }
+                        <Chat messages={self.received_messages.clone()} room={ctx.props().room.clone()} user={self.user_context.user.as_ref().unwrap().clone()} pass_message_to_ws={{let reactor = reactor.clone(); move |message| reactor.send(WebSocketReactorControl::Send(message))}} {translation} on_open_thread={ctx.link().callback(Msg::OpenThread)} {pinned} {can_pin} />
+                        if topic.is_some() || pin_bar.is_some() {
+                            <div class="sticky top-0 bg-white dark:bg-zinc-800">
+                                if let Some(pin_bar) = pin_bar {
+                                    {pin_bar}
+                                }
+                                if let Some(topic) = topic {
+                                    <div class="px-5 py-1 text-sm italic dark:text-gray-300" title={self.room_metadata.as_ref().and_then(|room| room.description.clone())}>{topic}</div>
+                                }
+                            </div>
+                        }
+                    </div>
+                    <div class="row-span-1 grid grid-cols-6 px-5 gap-4 justify-center content-center block ">
+                        if self.is_connected {
+                            <TypeBar {translation} pass_message_to_ws={{let reactor = reactor.clone(); move |message| reactor.send(WebSocketReactorControl::Send(message))}} user={self.user_context.user.as_ref().unwrap().clone()} room={ctx.props().room.clone()} reply_to={self.replying_to.clone()} on_cancel_reply={ctx.link().callback(|_| Msg::CancelReply)} {restriction} />
+                        } else {
+                            <DisconnectedBar {translation} called_back={self.called_back} is_outdated={self.is_outdated} />
+                        }
+                    </div>
+                </div>
+                if let Some(thread_messages) = &self.thread {
+                    <ThreadPanel messages={thread_messages.clone()} {translation} on_close={ctx.link().callback(|_| Msg::CloseThread)} />
+                } else {
+                    <MemberList members={self.members.clone()} {translation} />
+                }
             </div>
         }
     }
@@ -1,16 +1,25 @@
 // Copyright ⓒ 2022 LABEYE Loïc
 // This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
 
-use crate::components::common::{Form, FormInput};
+use crate::components::common::{Form, FormInput, I18N};
 use crate::router::Route;
+use crate::utils::requester::Requester;
 use crate::{components::common::FormButton, utils::client_context::ClientContext};
 use std::rc::Rc;
-use tchatchers_core::{room::RoomNameValidator, validation_error_message::ValidationErrorMessage};
+use tchatchers_core::{
+    direct_message::DirectConversationSummary,
+    message_read::RoomUnread,
+    room::{Room, RoomNameValidator},
+    room_member::RoomInvitation,
+    validation_error_message::ValidationErrorMessage,
+};
 use validator::Validate;
 use web_sys::HtmlInputElement;
 use yew::{
-    function_component, html, use_context, AttrValue, Component, Context, Html, NodeRef, Properties,
+    function_component, html, use_context, AttrValue, Component, Context, Html, InputEvent,
+    NodeRef, Properties, TargetCast,
 };
+use yew_router::prelude::Link;
 use yew_router::scope_ext::RouterScopeExt;
 
 #[function_component(JoinRoomHOC)]
@@ -28,19 +37,63 @@ pub struct Props {
 pub enum Msg {
     SubmitForm,
     VerificationError(String),
+    UnreadFetched(Vec<RoomUnread>),
+    ConversationsFetched(Vec<DirectConversationSummary>),
+    SearchRooms(String),
+    RoomsFetched(Vec<Room>),
+    InvitationsFetched(Vec<RoomInvitation>),
+    AcceptInvitation(String),
 }
 
 #[derive(Default)]
 pub struct JoinRoom {
     room_name: NodeRef,
     verification_error: Option<AttrValue>,
+    unread: Vec<RoomUnread>,
+    conversations: Vec<DirectConversationSummary>,
+    rooms: Vec<Room>,
+    invitations: Vec<RoomInvitation>,
 }
 
 impl Component for JoinRoom {
     type Message = Msg;
     type Properties = Props;
 
-    fn create(_ctx: &Context<Self>) -> Self {
+    fn create(ctx: &Context<Self>) -> Self {
+        let mut req = Requester::get("/api/rooms/unread");
+        req.bearer(ctx.props().user_context.bearer.clone());
+        let link = ctx.link().clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let resp = req.send().await;
+            if resp.ok() {
+                if let Ok(unread) = bincode::deserialize(&resp.binary().await.unwrap()) {
+                    link.send_message(Msg::UnreadFetched(unread));
+                }
+            }
+        });
+        let mut req = Requester::get("/api/dm");
+        req.bearer(ctx.props().user_context.bearer.clone());
+        let link = ctx.link().clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let resp = req.send().await;
+            if resp.ok() {
+                if let Ok(conversations) = bincode::deserialize(&resp.binary().await.unwrap()) {
+                    link.send_message(Msg::ConversationsFetched(conversations));
+                }
+            }
+        });
+        let mut req = Requester::get("/api/invitations");
+        req.bearer(ctx.props().user_context.bearer.clone());
+        let link = ctx.link().clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let resp = req.send().await;
+            if resp.ok() {
+                if let Ok(invitations) = bincode::deserialize(&resp.binary().await.unwrap()) {
+                    link.send_message(Msg::InvitationsFetched(invitations));
+                }
+            }
+        });
+        ctx.link().send_message(Msg::SearchRooms(String::new()));
         Self::default()
     }
 
@@ -66,6 +119,45 @@ impl Component for JoinRoom {
             Msg::VerificationError(error) => {
                 self.verification_error = Some(error.into());
             }
+            Msg::UnreadFetched(unread) => {
+                self.unread = unread.into_iter().filter(|room| room.unread > 0).collect();
+            }
+            Msg::ConversationsFetched(conversations) => {
+                self.conversations = conversations;
+            }
+            Msg::SearchRooms(search) => {
+                let search = js_sys::encode_uri_component(&search.to_lowercase());
+                let mut req = Requester::get(&format!("/api/rooms?search={search}"));
+                req.bearer(ctx.props().user_context.bearer.clone());
+                let link = ctx.link().clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let resp = req.send().await;
+                    if resp.ok() {
+                        if let Ok(rooms) = bincode::deserialize(&resp.binary().await.unwrap()) {
+                            link.send_message(Msg::RoomsFetched(rooms));
+                        }
+                    }
+                });
+                return false;
+            }
+            Msg::RoomsFetched(rooms) => {
+                self.rooms = rooms;
+            }
+            Msg::InvitationsFetched(invitations) => {
+                self.invitations = invitations;
+            }
+            Msg::AcceptInvitation(room) => {
+                let mut req = Requester::post(&format!("/api/invitations/{room}/accept"));
+                req.bearer(ctx.props().user_context.bearer.clone());
+                let link = ctx.link().clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let resp = req.send().await;
+                    if resp.ok() {
+                        link.navigator().unwrap().push(&Route::Room { room });
+                    }
+                });
+                return false;
+            }
         }
         true
     }
@@ -80,10 +172,66 @@ impl Component for JoinRoom {
 
     fn view(&self, ctx: &Context<Self>) -> Html {
         let translation = &ctx.props().user_context.translation;
+        let oninput = ctx.link().callback(|e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            Msg::SearchRooms(input.value())
+        });
         html! {
             <Form label="join_a_room_title" {translation} default="Join a room" onsubmit={ctx.link().callback(|_| Msg::SubmitForm)} form_error={&self.verification_error} >
-                <FormInput label={"room_name"} autofocus=true {translation} default={"Room name"} minlength="1" attr_ref={&self.room_name} required=true />
+                <FormInput label={"room_name"} autofocus=true {translation} default={"Room name"} minlength="1" attr_ref={&self.room_name} required=true list="existing_rooms" {oninput} />
+                <datalist id="existing_rooms">
+                    { for self.rooms.iter().map(|room| html! {
+                        <option key={room.name.clone()} value={room.name.clone()}>{room.topic.clone()}</option>
+                    })}
+                </datalist>
                 <FormButton label={"join_room"} default={"Join"} {translation} />
+                if !self.unread.is_empty() {
+                    <div class="mt-6 text-gray-600 dark:text-gray-200">
+                        <I18N label={"unread_messages"} default={"Unread messages"} {translation} />
+                        <ul class="mt-2">
+                        { for self.unread.iter().map(|room| html! {
+                            <li key={room.room.clone()} class="flex justify-between">
+                                <Link<Route> to={Route::Room { room: room.room.clone() }} classes="hover:underline">{&room.room}</Link<Route>>
+                                <span class="rounded-full bg-purple-500 text-white text-xs px-2">{room.unread}</span>
+                            </li>
+                        })}
+                        </ul>
+                    </div>
+                }
+                if !self.invitations.is_empty() {
+                    <div class="mt-6 text-gray-600 dark:text-gray-200">
+                        <I18N label={"room_invitations"} default={"Invitations"} {translation} />
+                        <ul class="mt-2">
+                        { for self.invitations.iter().map(|invitation| {
+                            let room = invitation.room.clone();
+                            let onclick = ctx.link().callback(move |_| Msg::AcceptInvitation(room.clone()));
+                            html! {
+                                <li key={invitation.room.clone()} class="flex justify-between">
+                                    <span>{&invitation.room}</span>
+                                    <button type="button" class="hover:underline" {onclick}>
+                                        <I18N label={"accept_invitation"} default={"Accept"} {translation} />
+                                    </button>
+                                </li>
+                            }
+                        })}
+                        </ul>
+                    </div>
+                }
+                if !self.conversations.is_empty() {
+                    <div class="mt-6 text-gray-600 dark:text-gray-200">
+                        <I18N label={"direct_messages"} default={"Direct messages"} {translation} />
+                        <ul class="mt-2">
+                        { for self.conversations.iter().map(|conversation| html! {
+                            <li key={conversation.last_message.room.clone()} class="flex justify-between">
+                                <Link<Route> to={Route::Room { room: conversation.last_message.room.clone() }} classes="hover:underline">{&conversation.user.name}</Link<Route>>
+                                if conversation.unread > 0 {
+                                    <span class="rounded-full bg-purple-500 text-white text-xs px-2">{conversation.unread}</span>
+                                }
+                            </li>
+                        })}
+                        </ul>
+                    </div>
+                }
             </Form>
         }
     }
@@ -0,0 +1,35 @@
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+use std::rc::Rc;
+
+use crate::components::common::I18N;
+use tchatchers_core::{locale::TranslationMap, user::PartialUser};
+use yew::{function_component, html, Html, Properties};
+
+const DEFAULT_PFP: &str = "/assets/no_pfp.webp";
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    pub members: Vec<PartialUser>,
+    pub translation: Rc<TranslationMap>,
+}
+
+#[function_component(MemberList)]
+pub fn member_list(props: &Props) -> Html {
+    let translation = &props.translation;
+    html! {
+        <aside class="hidden md:flex flex-col gap-2 w-48 px-3 pt-4 overflow-auto border-l dark:border-zinc-700 dark:text-gray-200">
+            <small class="font-semibold uppercase">
+                <I18N label={"room_members"} default={"Members"} {translation} />
+                {format!(" ({})", props.members.len())}
+            </small>
+            {props.members.iter().map(|member| html! {
+                <div class="flex items-center gap-2" title={member.login.clone()}>
+                    <img class="h-8 w-8 rounded-full" src={member.pfp.clone().unwrap_or_else(|| DEFAULT_PFP.into())} alt="No img"/>
+                    <span class="truncate">{&member.name}</span>
+                </div>
+            }).collect::<Html>()}
+        </aside>
+    }
+}
@@ -8,12 +8,14 @@ pub mod disconnected_bar;
 pub mod feed;
 pub mod join_room;
 pub mod logout;
+pub mod member_list;
 pub mod modal;
 pub mod navbar;
 pub mod right_menu;
 pub mod settings;
 pub mod signin;
 pub mod signup;
+pub mod thread_panel;
 pub mod toast;
 pub mod type_bar;
 
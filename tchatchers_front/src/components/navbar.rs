@@ -3,10 +3,18 @@ use std::{collections::HashMap, rc::Rc};
 // Copyright ⓒ 2022 LABEYE Loïc
 // This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
 use super::common::I18N;
-use crate::{router::Route, utils::client_context::ClientContext};
+use crate::{
+    router::Route,
+    utils::{client_context::ClientContext, requester::Requester},
+};
+use gloo_timers::callback::Interval;
+use tchatchers_core::notification::NotificationInbox;
 use yew::{function_component, html, use_context, Component, Context, Html, Properties};
 use yew_router::{prelude::Link, Routable};
 
+/// The delay between two refreshes of the notification inbox, in milliseconds.
+const INBOX_REFRESH_INTERVAL: u32 = 30_000;
+
 #[function_component]
 pub fn NavbarHOC() -> Html {
     let client_context =
@@ -19,26 +27,111 @@ pub struct Props {
     app_context: ClientContext,
 }
 
-#[derive(Default, Debug, PartialEq)]
-pub struct Navbar;
+pub enum Msg {
+    FetchInbox,
+    InboxFetched(NotificationInbox),
+    ToggleInbox,
+}
+
+#[derive(Default)]
+pub struct Navbar {
+    inbox: NotificationInbox,
+    is_inbox_open: bool,
+    inbox_refresh: Option<Interval>,
+}
+
+impl Navbar {
+    /// Refreshes the notification inbox periodically, once the user is
+    /// logged in.
+    fn watch_inbox(&mut self, ctx: &Context<Self>) {
+        if ctx.props().app_context.user.is_some() && self.inbox_refresh.is_none() {
+            ctx.link().send_message(Msg::FetchInbox);
+            let link = ctx.link().clone();
+            self.inbox_refresh = Some(Interval::new(INBOX_REFRESH_INTERVAL, move || {
+                link.send_message(Msg::FetchInbox)
+            }));
+        }
+    }
+}
 
 impl Component for Navbar {
-    type Message = ();
+    type Message = Msg;
     type Properties = Props;
 
-    fn create(_ctx: &Context<Self>) -> Self {
-        Self
+    fn create(ctx: &Context<Self>) -> Self {
+        let mut navbar = Self::default();
+        navbar.watch_inbox(ctx);
+        navbar
+    }
+
+    fn changed(&mut self, ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
+        if ctx.props().app_context.user.is_none() {
+            *self = Self::default();
+        } else {
+            self.watch_inbox(ctx);
+        }
+        true
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::FetchInbox => {
+                let mut req = Requester::get("/api/notifications");
+                req.bearer(ctx.props().app_context.bearer.clone());
+                let link = ctx.link().clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let resp = req.send().await;
+                    if resp.ok() {
+                        if let Ok(inbox) = bincode::deserialize(&resp.binary().await.unwrap()) {
+                            link.send_message(Msg::InboxFetched(inbox));
+                        }
+                    }
+                });
+                false
+            }
+            Msg::InboxFetched(inbox) => {
+                self.inbox = inbox;
+                true
+            }
+            Msg::ToggleInbox => {
+                self.is_inbox_open = !self.is_inbox_open;
+                // The notifications are read once the inbox is opened.
+                if self.is_inbox_open && self.inbox.unread > 0 {
+                    let mut req = Requester::post("/api/notifications/read");
+                    req.bearer(ctx.props().app_context.bearer.clone());
+                    wasm_bindgen_futures::spawn_local(async move {
+                        let _ = req.send().await;
+                    });
+                    self.inbox.unread = 0;
+                }
+                true
+            }
+        }
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
         let client_context = &ctx.props().app_context;
         let translation = &client_context.translation;
+        let notifications = self.inbox.notifications.iter().map(|notification| {
+            let classes = match notification.read_at {
+                Some(_) => "block px-4 py-2 text-sm text-gray-600 dark:text-gray-300",
+                None => "block px-4 py-2 text-sm font-bold text-gray-800 dark:text-white",
+            };
+            html! {
+                <li key={notification.id}>
+                    <Link<Route> to={Route::Room { room: notification.room.clone() }} {classes}>
+                        <span>{format!("{} #{}", notification.author.name, notification.room)}</span>
+                        <p class="truncate font-normal">{&notification.content}</p>
+                    </Link<Route>>
+                </li>
+            }
+        });
         html! {
             <nav class="flex items-center justify-between flex-wrap border-b-2 dark:border-zinc-700 px-6 row-span-1 shadow-md">
                 <Link<Route> to={if client_context.user.is_some() { Route::JoinRoom } else { Route::SignIn }} classes="flex items-center flex-shrink-0 text-white mr-6 text-transparent bg-clip-text bg-gradient-to-r from-purple-400 to-indigo-600 hover:animate-pulse">
                     <img src="/favicon.ico" class="h-8 w-8 sm:h-12 sm:w-12 md:h-16 md:w-16"/>
                 </Link<Route>>
-                <div>
+                <div class="flex items-center">
                     {
                         (*client_context.navlink).clone().into_iter().map(|n| html!{
                             <Link<Route> key={n.id} to={Route::from_path(&n.href, &HashMap::default()).unwrap()} classes="inline-block text-sm px-4 py-2 leading-none text-gray-600 dark:text-white" >
@@ -46,6 +139,28 @@ impl Component for Navbar {
                             </Link<Route> >
                         }).collect::<Html>()
                     }
+                    if client_context.user.is_some() {
+                        <div class="relative">
+                            <button class="relative px-4 py-2 text-gray-600 dark:text-white" onclick={ctx.link().callback(|_| Msg::ToggleInbox)}>
+                                <svg xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 24 24" stroke-width="1.5" stroke="currentColor" class="w-6 h-6">
+                                    <path stroke-linecap="round" stroke-linejoin="round" d="M14.857 17.082a23.848 23.848 0 005.454-1.31A8.967 8.967 0 0118 9.75v-.7V9A6 6 0 006 9v.75a8.967 8.967 0 01-2.312 6.022c1.733.64 3.56 1.085 5.455 1.31m5.714 0a24.255 24.255 0 01-5.714 0m5.714 0a3 3 0 11-5.714 0" />
+                                </svg>
+                                if self.inbox.unread > 0 {
+                                    <span class="absolute top-0 right-2 rounded-full bg-purple-500 text-white text-xs px-1">{self.inbox.unread}</span>
+                                }
+                            </button>
+                            if self.is_inbox_open {
+                                <ul class="absolute right-0 z-10 mt-2 w-72 max-h-96 overflow-y-auto rounded-md shadow-lg bg-white dark:bg-zinc-700" onclick={ctx.link().callback(|_| Msg::ToggleInbox)}>
+                                    if self.inbox.notifications.is_empty() {
+                                        <li class="px-4 py-2 text-sm text-gray-600 dark:text-gray-300">
+                                            <I18N label={"no_notifications"} default={"No notifications"} {translation}/>
+                                        </li>
+                                    }
+                                    {for notifications}
+                                </ul>
+                            }
+                        </div>
+                    }
                 </div>
             </nav>
         }
@@ -1,17 +1,19 @@
 use std::rc::Rc;
 
-use rmenu_service::MessageRMenuProps;
-use tchatchers_core::{api_response::ApiResponse, profile::Profile, ws_message::WsMessage};
-use yew::{function_component, html, use_context, Html};
-use yew_agent::{reactor::use_reactor_subscription, worker::use_worker_subscription};
+use rmenu_service::{MessageAction, MessageRMenuProps, RMenuBus, RMenusBusEvents};
+use tchatchers_core::{api_response::ApiResponse, message_read::MessageReader, profile::Profile};
+use yew::{function_component, html, use_context, use_effect_with, use_state, Html};
+use yew_agent::worker::use_worker_subscription;
 
 use crate::{
     components::common::I18N,
     utils::{client_context::ClientContext, requester::Requester},
 };
-use chat_service::{ChatReactor, WebSocketReactorControl};
 use toast_service::{Alert, ToastBus};
 
+/// The emojis that can be used to react from the right menu.
+const QUICK_REACTIONS: [&str; 6] = ["👍", "❤️", "😂", "😮", "😢", "🎉"];
+
 #[function_component(MessageRMenu)]
 pub fn message_rmenu(props: &MessageRMenuProps) -> Html {
     let client_context = use_context::<Rc<ClientContext>>().unwrap();
@@ -24,13 +26,11 @@ pub fn message_rmenu(props: &MessageRMenuProps) -> Html {
         let bearer = bearer.clone();
 
         let delete_message_id = {
-            let reactor = use_reactor_subscription::<ChatReactor>();
             let toaster = use_worker_subscription::<ToastBus>();
             let props = props.clone();
             move |_| {
                 let mut req = Requester::delete(&format!("/api/message/{}", props.message_id));
                 req.bearer(bearer.clone());
-                let reactor = reactor.clone();
                 let toaster = toaster.clone();
                 wasm_bindgen_futures::spawn_local(async move {
                     let res = req.send().await;
@@ -44,11 +44,6 @@ pub fn message_rmenu(props: &MessageRMenuProps) -> Html {
                         label,
                         default,
                     });
-                    if is_success {
-                        reactor.send(WebSocketReactorControl::Send(WsMessage::Delete(
-                            props.message_id,
-                        )));
-                    }
                 })
             }
         };
@@ -58,6 +53,121 @@ pub fn message_rmenu(props: &MessageRMenuProps) -> Html {
         </li>}
     };
 
+    let readers = use_state(|| None::<Vec<MessageReader>>);
+
+    {
+        // Only the author of a message can know who read it.
+        let readers = readers.clone();
+        let bearer = bearer.clone();
+        let is_self = props.is_self;
+        use_effect_with(props.message_id, move |message_id| {
+            if is_self {
+                let mut req = Requester::get(&format!("/api/message/{message_id}/readers"));
+                req.bearer(bearer);
+                wasm_bindgen_futures::spawn_local(async move {
+                    let res = req.send().await;
+                    if res.ok() {
+                        if let Ok(list) = bincode::deserialize(&res.binary().await.unwrap()) {
+                            readers.set(Some(list));
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    let seen_by_li = match readers.as_ref() {
+        Some(readers) if !readers.is_empty() => {
+            let names = readers
+                .iter()
+                .map(|reader| reader.user.name.as_str())
+                .collect::<Vec<&str>>()
+                .join(", ");
+            html! {
+            <li class="text-xs text-gray-400 pt-1">
+                <I18N label={"seen_by"} default={"Seen by"} {translation}/>{format!(" {names}")}
+            </li>}
+        }
+        _ => html! {},
+    };
+
+    let bus = use_worker_subscription::<RMenuBus>();
+
+    let react_li = {
+        let reactions = QUICK_REACTIONS.iter().map(|emoji| {
+            let bus = bus.clone();
+            let message_id = props.message_id;
+            let emoji = emoji.to_string();
+            html! {
+                <span class="cursor-pointer hover:scale-125" onclick={move |_| bus.send(RMenusBusEvents::MessageAction(MessageAction::React(message_id, emoji.clone())))}>
+                    {emoji.clone()}
+                </span>
+            }
+        });
+        html! {
+        <li class="flex gap-2 pb-1">
+            {for reactions}
+        </li>}
+    };
+
+    let reply_li = {
+        let bus = bus.clone();
+        let message_id = props.message_id;
+        html! {
+        <li class="hover:text-gray-300" onclick={move |_| bus.send(RMenusBusEvents::MessageAction(MessageAction::Reply(message_id)))}>
+            <I18N label={"reply"} default={"Reply"} {translation}/>
+        </li>}
+    };
+
+    let open_thread_li = {
+        let bus = bus.clone();
+        let message_id = props.message_id;
+        html! {
+        <li class="hover:text-gray-300" onclick={move |_| bus.send(RMenusBusEvents::MessageAction(MessageAction::OpenThread(message_id)))}>
+            <I18N label={"open_thread"} default={"Open thread"} {translation}/>
+        </li>}
+    };
+
+    let pin_li = {
+        let bus = bus.clone();
+        let message_id = props.message_id;
+        let (action, label, default) = match props.is_pinned {
+            true => (MessageAction::Unpin(message_id), "unpin_message", "Unpin"),
+            false => (MessageAction::Pin(message_id), "pin_message", "Pin"),
+        };
+        html! {
+        <li class="hover:text-gray-300" onclick={move |_| bus.send(RMenusBusEvents::MessageAction(action.clone()))}>
+            <I18N {label} {default} {translation}/>
+        </li>}
+    };
+
+    let edit_message_li = {
+        let edit_message_id = {
+            let bus = bus.clone();
+            let props = props.clone();
+            let prompt = translation.get_or_default("edit_message", "Edit message");
+            move |_| {
+                let Ok(Some(content)) = web_sys::window()
+                    .unwrap()
+                    .prompt_with_message_and_default(&prompt, &props.content)
+                else {
+                    return;
+                };
+                if content.trim().is_empty() || content == props.content {
+                    return;
+                }
+                bus.send(RMenusBusEvents::MessageAction(MessageAction::Edit(
+                    props.message_id,
+                    content,
+                )));
+            }
+        };
+        html! {
+        <li class="hover:text-gray-300" onclick={edit_message_id}>
+            <I18N label={"edit_message"} default={"Edit message"} {translation}/>
+        </li>}
+    };
+
     let report_message_li = {
         let report_message_id = {
             let props = props.clone();
@@ -90,16 +200,32 @@ pub fn message_rmenu(props: &MessageRMenuProps) -> Html {
     match client_context.user.as_ref().unwrap().profile {
         Profile::Moderator | Profile::Admin => html! {
             <ul>
+                {react_li}
+                {reply_li}
+                {open_thread_li}
+                {pin_li}
+                if props.is_self {
+                    {edit_message_li}
+                }
                 {delete_message_li}
+                {seen_by_li}
             </ul>
         },
         Profile::User => html! {
             <ul>
+            {react_li}
+            {reply_li}
+            {open_thread_li}
+            if props.can_pin {
+                {pin_li}
+            }
             if props.is_self {
+                {edit_message_li}
                 {delete_message_li}
             } else {
                 {report_message_li}
             }
+            {seen_by_li}
             </ul>
 
         },
@@ -144,6 +144,7 @@ impl Component for RightMenu {
                     self.visible = false;
                     self.menu_repositionned = false;
                 }
+                RMenusBusEvents::MessageAction(_) => return false,
             },
             Msg::RepositionMenu => {
                 let window = web_sys::window().unwrap();
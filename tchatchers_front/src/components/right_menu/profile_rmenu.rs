@@ -1,14 +1,18 @@
 use std::rc::Rc;
 
 use rmenu_service::ProfileRMenuProps;
-use tchatchers_core::{api_response::ApiResponse, profile::Profile};
+use tchatchers_core::{
+    api_response::ApiResponse, direct_message::DirectConversation, profile::Profile,
+};
 use yew::{function_component, html, use_context, Html};
+use yew_router::prelude::use_navigator;
 
 use toast_service::{Alert, ToastBus};
 use yew_agent::worker::use_worker_subscription;
 
 use crate::{
     components::common::I18N,
+    router::Route,
     utils::{client_context::ClientContext, requester::Requester},
 };
 
@@ -80,14 +84,29 @@ pub fn profile_rmenu(props: &ProfileRMenuProps) -> Html {
         </li>}
     };
 
-    match client_context.user.as_ref().unwrap().profile {
+    let user = client_context.user.as_ref().unwrap();
+
+    let navigator = use_navigator().unwrap();
+    let send_message_li = (user.id != props.user_id).then(|| {
+        let room = DirectConversation::new(user.id, props.user_id).room();
+        let send_message = move |_| navigator.push(&Route::Room { room: room.clone() });
+        html! {
+            <li class="hover:text-gray-300" onclick={send_message}>
+                <I18N label={"send_message"} default={"Send message"} {translation}/>
+            </li>
+        }
+    });
+
+    match user.profile {
         Profile::Moderator | Profile::Admin => html! {
             <ul>
+                {send_message_li}
                 {revoke_user_li}
             </ul>
         },
         _ => html! {
             <ul>
+                {send_message_li}
                 {report_user_li}
             </ul>
         },
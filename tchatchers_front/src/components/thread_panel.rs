@@ -0,0 +1,42 @@
+// Copyright ⓒ 2022 LABEYE Loïc
+// This tool is distributed under the MIT License, check out [here](https://github.com/nag763/tchatchers/blob/main/LICENSE.MD).
+
+use std::rc::Rc;
+
+use crate::components::common::I18N;
+use tchatchers_core::{locale::TranslationMap, ws_message::WsMessageContent};
+use yew::{classes, function_component, html, Callback, Html, Properties};
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    /// The messages of the thread, from its root message to its latest reply.
+    pub messages: Vec<WsMessageContent>,
+    pub translation: Rc<TranslationMap>,
+    pub on_close: Callback<()>,
+}
+
+#[function_component(ThreadPanel)]
+pub fn thread_panel(props: &Props) -> Html {
+    let translation = &props.translation;
+    let on_close = props.on_close.clone();
+    html! {
+        <aside class="flex flex-col gap-2 w-64 px-3 pt-4 overflow-auto border-l dark:border-zinc-700 dark:text-gray-200">
+            <div class="flex justify-between">
+                <small class="font-semibold uppercase">
+                    <I18N label={"thread"} default={"Thread"} {translation} />
+                </small>
+                <button type="button" onclick={move |_| on_close.emit(())}>{"✕"}</button>
+            </div>
+            {props.messages.iter().enumerate().map(|(i, message)| html! {
+                <div class={classes!("flex", "flex-col", (i != 0).then_some("ml-3"))}>
+                    <small class="font-semibold">{&message.author.name}</small>
+                    if message.is_deleted {
+                        <i><I18N label={"message_tombstone"} default={"This message has been deleted"} {translation} /></i>
+                    } else {
+                        <span class="break-words">{&message.content}</span>
+                    }
+                </div>
+            }).collect::<Html>()}
+        </aside>
+    }
+}
@@ -3,14 +3,19 @@
 
 use std::rc::Rc;
 
+use crate::components::common::I18N;
 use tchatchers_core::locale::TranslationMap;
-use tchatchers_core::ws_message::WsMessage;
+use tchatchers_core::ws_message::{PostRejection, WsMessage};
 use tchatchers_core::{user::PartialUser, ws_message::WsMessageContent};
 use web_sys::HtmlInputElement;
 use yew::{html, AttrValue, Callback, Component, Context, Html, NodeRef, Properties};
 
+/// Minimum delay, in milliseconds, between two typing notifications.
+const TYPING_NOTIFICATION_INTERVAL: f64 = 2_000.0;
+
 pub enum Msg {
     SubmitForm,
+    Input,
 }
 
 #[derive(Clone, PartialEq, Properties)]
@@ -19,11 +24,19 @@ pub struct Props {
     pub room: AttrValue,
     pub user: PartialUser,
     pub translation: Rc<TranslationMap>,
+    /// The message the user is replying to, if any.
+    #[prop_or_default]
+    pub reply_to: Option<WsMessageContent>,
+    pub on_cancel_reply: Callback<()>,
+    /// Why the user can't post right now, if they can't.
+    #[prop_or_default]
+    pub restriction: Option<PostRejection>,
 }
 
 #[derive(Default)]
 pub struct TypeBar {
     input_ref: NodeRef,
+    last_typing_notification: Option<f64>,
 }
 
 impl Component for TypeBar {
@@ -45,14 +58,42 @@ impl Component for TypeBar {
                         room: ctx.props().room.to_string(),
                         author: ctx.props().user.clone(),
                         content: input.value(),
+                        reply_to: ctx.props().reply_to.as_ref().map(|parent| parent.uuid),
                         ..WsMessageContent::default()
                     };
                     ctx.props().pass_message_to_ws.emit(WsMessage::Send(msg));
+                    if ctx.props().reply_to.is_some() {
+                        ctx.props().on_cancel_reply.emit(());
+                    }
                     input.set_value("");
+                    self.last_typing_notification = None;
                 }
 
                 true
             }
+            Msg::Input => {
+                let Some(input) = self.input_ref.cast::<HtmlInputElement>() else {
+                    return false;
+                };
+                let user = ctx.props().user.clone();
+                if input.value().is_empty() {
+                    if self.last_typing_notification.take().is_some() {
+                        ctx.props()
+                            .pass_message_to_ws
+                            .emit(WsMessage::StoppedTyping(user));
+                    }
+                } else {
+                    let now = js_sys::Date::now();
+                    if self
+                        .last_typing_notification
+                        .is_none_or(|last| now - last >= TYPING_NOTIFICATION_INTERVAL)
+                    {
+                        self.last_typing_notification = Some(now);
+                        ctx.props().pass_message_to_ws.emit(WsMessage::Typing(user));
+                    }
+                }
+                false
+            }
         }
     }
 
@@ -65,10 +106,35 @@ impl Component for TypeBar {
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
+        let translation = &ctx.props().translation;
+        let on_cancel_reply = ctx.props().on_cancel_reply.clone();
+        let placeholder = match ctx.props().restriction {
+            Some(PostRejection::AnnouncementOnly) => translation.get_or_default(
+                "announcement_only",
+                "Only the moderators can post in this room.",
+            ),
+            Some(PostRejection::SlowMode { retry_after }) => format!(
+                "{} ({retry_after}s)",
+                translation.get_or_default(
+                    "slow_mode_on",
+                    "Slow mode is on, please wait before posting again."
+                )
+            ),
+            None => translation.get_or_default("type_msg_here", "Type a message here"),
+        };
         html! {
             <div class="col-span-6">
+                if let Some(reply_to) = &ctx.props().reply_to {
+                    <div class="flex justify-between text-xs italic px-3 pb-1 dark:text-gray-300">
+                        <span class="truncate">
+                            <I18N label={"replying_to"} default={"Replying to"} {translation} />
+                            {format!(" {} : {}", reply_to.author.name, reply_to.content)}
+                        </span>
+                        <button type="button" class="ml-2" onclick={move |_| on_cancel_reply.emit(())}>{"✕"}</button>
+                    </div>
+                }
                 <form onsubmit={ctx.link().callback(|_| Msg::SubmitForm)} action="javascript:void(0);">
-                        <input autofocus=true class="shadow appearance-none border dark:border-zinc-800 rounded-xl px-3 text-gray-800 leading-tight focus:outline-none focus:shadow-outline focus:border-zinc-900 w-full h-10 disabled:bg-gray-100 dark:disabled:bg-zinc-700 bg-gray-300 dark:bg-zinc-700 dark:text-gray-200 dark:carret-indigo-500" type="text" placeholder={ctx.props().translation.as_ref().clone().get_or_default("type_msg_here", "Type a message here")} minlength="2" maxlength="127" ref={self.input_ref.clone()} />
+                        <input autofocus=true class="shadow appearance-none border dark:border-zinc-800 rounded-xl px-3 text-gray-800 leading-tight focus:outline-none focus:shadow-outline focus:border-zinc-900 w-full h-10 disabled:bg-gray-100 dark:disabled:bg-zinc-700 bg-gray-300 dark:bg-zinc-700 dark:text-gray-200 dark:carret-indigo-500" type="text" disabled={ctx.props().restriction.is_some()} {placeholder} minlength="2" maxlength="127" ref={self.input_ref.clone()} oninput={ctx.link().callback(|_| Msg::Input)} />
                         <button type="submit" hidden=true></button>
                 </form>
             </div>
@@ -6,7 +6,7 @@ pub mod keyed_list;
 pub mod language;
 pub mod requester;
 
-pub fn get_ws_room_address(room: &str) -> String {
+pub fn get_ws_room_address(room: &str, ticket: Option<&str>) -> String {
     let location = web_sys::window().unwrap().location();
     let host = location.host().unwrap();
 
@@ -15,11 +15,15 @@ pub fn get_ws_room_address(room: &str) -> String {
         "https:" => "wss:",
         _ => "ws:",
     };
-    format!(
+    let mut address = format!(
         "{}//{}/ws/{}?_={}",
         ws_protocol,
         host,
         room,
         js_sys::Date::new_0().get_time()
-    )
+    );
+    if let Some(ticket) = ticket {
+        address.push_str(&format!("&ticket={ticket}"));
+    }
+    address
 }